@@ -1,6 +1,8 @@
+use crate::error::OnePagerError;
+use crate::schema::{Schema, ValidationIssue};
 use csv::ReaderBuilder;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::path::Path;
 
@@ -10,122 +12,395 @@ pub struct CsvRecord {
     pub data: HashMap<String, String>,
 }
 
-pub fn clean_value(val: &str) -> String {
-    let v = val.trim();
+/// Columns every data file needs to identify a client.
+pub const KEY_COLUMNS: [&str; 2] = ["Org ID", "Nom du client"];
+
+/// Diagnostics for a CSV file, optionally checked against a column schema.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CsvReport {
+    pub valid: bool,
+    pub delimiter: String,
+    pub encoding: String,
+    pub headers: Vec<String>,
+    pub duplicate_headers: Vec<String>,
+    pub row_count: usize,
+    /// Rows whose field count differs from the header
+    pub ragged_rows: Vec<RaggedRow>,
+    /// Key columns absent from the header
+    pub missing_key_columns: Vec<String>,
+    pub empty_keys: Vec<EmptyKey>,
+    pub duplicate_keys: Vec<DuplicateKey>,
+    pub fill_rates: Vec<FillRate>,
+    pub issues: Vec<ValidationIssue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RaggedRow {
+    /// 1-based data row
+    pub row: usize,
+    pub fields: usize,
+    pub expected: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EmptyKey {
+    pub row: usize,
+    pub column: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DuplicateKey {
+    pub column: String,
+    pub value: String,
+    pub rows: Vec<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FillRate {
+    pub column: String,
+    pub filled: usize,
+    /// Share of rows with a non-empty value, from 0 to 1
+    pub rate: f64,
+}
+
+/// Tokens treated as missing values, compared case-insensitively.
+pub const DEFAULT_NULL_TOKENS: [&str; 6] = ["#n/a", "null", "none", "nan", "n.a", "na"];
+
+/// How raw cell text is turned into a tag value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleaningRules {
+    pub null_tokens: Vec<String>,
+    /// Keep the value as typed, skipping number normalization
+    pub preserve_text: bool,
+    /// Reads "4,5" as 4.5
+    pub decimal_comma: bool,
+    /// Removed from numbers, e.g. " " or "." ("1 200" -> "1200")
+    pub thousands_separator: Option<String>,
+    /// Collapses runs of whitespace, non-breaking spaces included, into one space
+    pub normalize_whitespace: bool,
+}
+
+impl Default for CleaningRules {
+    fn default() -> Self {
+        CleaningRules {
+            null_tokens: DEFAULT_NULL_TOKENS.iter().map(|t| t.to_string()).collect(),
+            preserve_text: false,
+            decimal_comma: false,
+            thousands_separator: None,
+            normalize_whitespace: false,
+        }
+    }
+}
+
+/// Global rules plus per-column overrides. A column entry replaces the
+/// global rules entirely; its unset fields take the built-in defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleaningConfig {
+    #[serde(flatten)]
+    pub defaults: CleaningRules,
+    pub columns: HashMap<String, CleaningRules>,
+}
+
+impl CleaningConfig {
+    pub fn rules_for(&self, column: &str) -> &CleaningRules {
+        self.columns.get(column).unwrap_or(&self.defaults)
+    }
+}
+
+pub fn clean_value_with(val: &str, rules: &CleaningRules) -> String {
+    let collapsed;
+    let v = if rules.normalize_whitespace {
+        collapsed = val.split_whitespace().collect::<Vec<_>>().join(" ");
+        collapsed.as_str()
+    } else {
+        val.trim()
+    };
+
     // Check for N/A variants
     let lower = v.to_lowercase();
-    if v.is_empty()
-        || lower == "#n/a"
-        || lower == "null"
-        || lower == "none"
-        || lower == "nan"
-        || lower == "n.a"
-        || lower == "na"
-    {
+    if v.is_empty() || rules.null_tokens.iter().any(|t| t.to_lowercase() == lower) {
         return "".to_string();
     }
+    if rules.preserve_text {
+        return v.to_string();
+    }
+
+    let number = normalize_number(v, rules);
 
     // Try to parse as float and check if integer
-    if let Ok(f) = v.parse::<f64>() {
+    if let Ok(f) = number.parse::<f64>() {
+        // Codes such as "007" postcodes keep their leading zeros
+        let digits = number.trim_start_matches(['-', '+']);
+        if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+            return v.to_string();
+        }
         // If it's effectively an integer (e.g. 10.0), return "10"
         if (f.fract()).abs() < f64::EPSILON {
             return (f as i64).to_string();
         }
-        // Otherwise return original string (or formatted float if prefer, but original is safer)
-        return v.to_string();
+        // Otherwise keep the text, with the separators normalized if rules asked for it
+        return number;
     }
 
     v.to_string()
 }
 
-pub fn read_csv_all<P: AsRef<Path>>(path: P) -> Result<Vec<HashMap<String, String>>, String> {
-    let path_ref = path.as_ref();
-    let file = File::open(path_ref).map_err(|e| e.to_string())?;
-    
-    // Auto-detect delimiter
-    // Read first line to guess
-    let mut delim = b',';
-    {
-        use std::io::{BufRead, BufReader};
-        let mut reader = BufReader::new(File::open(path_ref).map_err(|e| e.to_string())?);
-        let mut first_line = String::new();
-        if reader.read_line(&mut first_line).is_ok() {
-            if first_line.contains(';') {
-                delim = b';';
-            }
-        }
+/// Drops thousands separators and turns a decimal comma into a dot, as the rules say.
+fn normalize_number(v: &str, rules: &CleaningRules) -> String {
+    let mut number = v.to_string();
+    if let Some(sep) = rules.thousands_separator.as_deref().filter(|s| !s.is_empty()) {
+        number = number.replace(sep, "");
     }
+    if rules.decimal_comma {
+        number = number.replace(',', ".");
+    }
+    number
+}
+
+/// Reads a cell as a number with the same separator rules as `clean_value_with`.
+pub fn parse_number(value: &str, rules: &CleaningRules) -> Option<f64> {
+    normalize_number(value.trim(), rules)
+        .parse::<f64>()
+        .ok()
+        .filter(|f| f.is_finite())
+}
 
+/// `;` when the header line has one, then tab, otherwise `,`.
+pub fn detect_delimiter(first_line: &[u8]) -> u8 {
+    if first_line.contains(&b';') {
+        b';'
+    } else if first_line.contains(&b'\t') {
+        b'\t'
+    } else {
+        b','
+    }
+}
+
+/// Names the encoding from the byte order mark, or whether the bytes are valid UTF-8.
+pub fn detect_encoding(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        "UTF-8 (BOM)"
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        "UTF-16LE"
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        "UTF-16BE"
+    } else if std::str::from_utf8(bytes).is_ok() {
+        "UTF-8"
+    } else {
+        "Unknown (not UTF-8)"
+    }
+}
+
+fn open_reader(
+    path: &Path,
+    flexible: bool,
+) -> Result<(csv::Reader<File>, Vec<String>), OnePagerError> {
+    let first_line = {
+        use std::io::{BufRead, BufReader};
+        let mut reader =
+            BufReader::new(File::open(path).map_err(|e| OnePagerError::io(path, &e))?);
+        let mut line = Vec::new();
+        reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| OnePagerError::io(path, &e))?;
+        line
+    };
+
+    let file = File::open(path).map_err(|e| OnePagerError::io(path, &e))?;
     let mut rdr = ReaderBuilder::new()
-        .delimiter(delim)
+        .delimiter(detect_delimiter(&first_line))
         .has_headers(true)
+        .flexible(flexible)
         .from_reader(file);
 
-    let headers = rdr.headers().map_err(|e| e.to_string())?.clone();
-    let mut records = Vec::new();
+    // Excel's "CSV UTF-8" export starts with a BOM that would stick to the first header
+    let headers = rdr
+        .byte_headers()
+        .map_err(|e| OnePagerError::csv(path, &e))?
+        .iter()
+        .map(decode_field)
+        .enumerate()
+        .map(|(i, h)| if i == 0 { h.trim_start_matches('\u{feff}').to_string() } else { h })
+        .collect();
+    Ok((rdr, headers))
+}
 
-    for result in rdr.records() {
-        let record = result.map_err(|e| e.to_string())?;
-        let mut map = HashMap::new();
-        
-        for (i, field) in record.iter().enumerate() {
-            if let Some(header) = headers.get(i) {
-                map.insert(header.to_string(), clean_value(field));
-            }
+/// Exports with broken encodings, such as lone surrogates written by some
+/// tools, would fail the whole file. Invalid bytes become U+FFFD instead.
+fn decode_field(field: &[u8]) -> String {
+    String::from_utf8_lossy(field).into_owned()
+}
+
+fn record_to_map(
+    headers: &[String],
+    record: &csv::ByteRecord,
+    cleaning: &CleaningConfig,
+) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for (i, field) in record.iter().enumerate() {
+        if let Some(header) = headers.get(i) {
+            map.insert(header.to_string(), clean_value_with(&decode_field(field), cleaning.rules_for(header)));
         }
-        records.push(map);
+    }
+    map
+}
+
+pub fn read_csv_preview<P: AsRef<Path>>(
+    path: P,
+    limit: usize,
+) -> Result<Vec<HashMap<String, String>>, OnePagerError> {
+    read_csv_with(path, limit, &CleaningConfig::default())
+}
+
+pub fn read_csv_with<P: AsRef<Path>>(
+    path: P,
+    limit: usize,
+    cleaning: &CleaningConfig,
+) -> Result<Vec<HashMap<String, String>>, OnePagerError> {
+    let path = path.as_ref();
+    let (mut rdr, headers) = open_reader(path, false)?;
+    let mut records = Vec::new();
+
+    for result in rdr.byte_records().take(limit) {
+        let record = result.map_err(|e| OnePagerError::csv(path, &e))?;
+        records.push(record_to_map(&headers, &record, cleaning));
     }
 
     Ok(records)
 }
 
-pub fn read_csv_preview<P: AsRef<Path>>(path: P, limit: usize) -> Result<Vec<HashMap<String, String>>, String> {
-    let path_ref = path.as_ref();
-    let file = File::open(path_ref).map_err(|e| e.to_string())?;
+/// Reads the whole file and collects structural problems, key gaps, fill rates
+/// and schema issues.
+pub fn validate_csv<P: AsRef<Path>>(
+    path: P,
+    schema: Option<&Schema>,
+    cleaning: &CleaningConfig,
+) -> CsvReport {
+    let path = path.as_ref();
+    let mut report = CsvReport::default();
+    let failed = |mut report: CsvReport, message: String| {
+        report.issues.push(ValidationIssue {
+            row: 0,
+            column: String::new(),
+            value: String::new(),
+            message,
+        });
+        report
+    };
 
-    // Auto-detect delimiter
-    let mut delim = b',';
-    {
-        use std::io::{BufRead, BufReader};
-        let mut reader = BufReader::new(File::open(path_ref).map_err(|e| e.to_string())?);
-        let mut first_line = String::new();
-        if reader.read_line(&mut first_line).is_ok() {
-            if first_line.contains(';') {
-                delim = b';';
-            }
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return failed(report, OnePagerError::io(path, &e).to_string()),
+    };
+    report.encoding = detect_encoding(&bytes).to_string();
+    let first_line = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    report.delimiter = match detect_delimiter(first_line) {
+        b'\t' => "\\t".to_string(),
+        d => (d as char).to_string(),
+    };
+
+    let (mut rdr, headers) = match open_reader(path, true) {
+        Ok(reader) => reader,
+        Err(e) => return failed(report, e.to_string()),
+    };
+    let mut seen = HashSet::new();
+    for header in &headers {
+        if !seen.insert(header) && !report.duplicate_headers.contains(header) {
+            report.duplicate_headers.push(header.clone());
         }
     }
+    report.missing_key_columns = KEY_COLUMNS
+        .iter()
+        .filter(|k| !headers.iter().any(|h| h == *k))
+        .map(|k| k.to_string())
+        .collect();
+    if let Some(schema) = schema {
+        report.issues.extend(schema.check_headers(&headers));
+    }
 
-    let mut rdr = ReaderBuilder::new()
-        .delimiter(delim)
-        .has_headers(true)
-        .from_reader(file);
-
-    let headers = rdr.headers().map_err(|e| e.to_string())?.clone();
-    let mut records = Vec::new();
+    let mut filled = vec![0usize; headers.len()];
+    let mut keys: HashMap<(&str, String), Vec<usize>> = HashMap::new();
+    for (i, result) in rdr.byte_records().enumerate() {
+        let row = i + 1;
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => return failed(report, OnePagerError::csv(path, &e).to_string()),
+        };
+        report.row_count = row;
+        if record.len() != headers.len() {
+            report.ragged_rows.push(RaggedRow {
+                row,
+                fields: record.len(),
+                expected: headers.len(),
+            });
+        }
 
-    for (count, result) in rdr.records().enumerate() {
-        if count >= limit {
-            break;
+        let map = record_to_map(&headers, &record, cleaning);
+        for (count, header) in filled.iter_mut().zip(&headers) {
+            if map.get(header).is_some_and(|v| !v.is_empty()) {
+                *count += 1;
+            }
         }
-        let record = result.map_err(|e| e.to_string())?;
-        let mut map = HashMap::new();
-        
-        for (i, field) in record.iter().enumerate() {
-            if let Some(header) = headers.get(i) {
-                map.insert(header.to_string(), clean_value(field));
+        for key in KEY_COLUMNS {
+            match map.get(key) {
+                Some(value) if value.is_empty() => report.empty_keys.push(EmptyKey {
+                    row,
+                    column: key.to_string(),
+                }),
+                Some(value) => keys.entry((key, value.clone())).or_default().push(row),
+                None => {}
             }
         }
-        records.push(map);
+        if let Some(schema) = schema {
+            report.issues.extend(schema.validate_row(row, &map));
+        }
     }
 
-    Ok(records)
+    let mut duplicates: Vec<DuplicateKey> = keys
+        .into_iter()
+        .filter(|(_, rows)| rows.len() > 1)
+        .map(|((column, value), rows)| DuplicateKey {
+            column: column.to_string(),
+            value,
+            rows,
+        })
+        .collect();
+    duplicates.sort_by_key(|d| d.rows[0]);
+    report.duplicate_keys = duplicates;
+
+    report.fill_rates = headers
+        .iter()
+        .zip(filled)
+        .map(|(column, filled)| FillRate {
+            column: column.clone(),
+            filled,
+            rate: if report.row_count == 0 {
+                0.0
+            } else {
+                filled as f64 / report.row_count as f64
+            },
+        })
+        .collect();
+    report.headers = headers;
+
+    report.valid = report.row_count > 0
+        && report.duplicate_headers.is_empty()
+        && report.ragged_rows.is_empty()
+        && report.missing_key_columns.is_empty()
+        && report.issues.is_empty();
+    report
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn clean_value(val: &str) -> String {
+        clean_value_with(val, &CleaningRules::default())
+    }
+
     #[test]
     fn test_clean_value_integers() {
         assert_eq!(clean_value("10"), "10");
@@ -161,4 +436,120 @@ mod tests {
         assert_eq!(clean_value("  10.0  "), "10");
         assert_eq!(clean_value("  nan  "), "");
     }
+
+    #[test]
+    fn test_clean_value_keeps_leading_zeros() {
+        assert_eq!(clean_value("007"), "007");
+        assert_eq!(clean_value("0.5"), "0.5");
+        assert_eq!(clean_value("0"), "0");
+    }
+
+    #[test]
+    fn test_cleaning_rules() {
+        let country = CleaningRules {
+            null_tokens: vec!["#N/A".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(clean_value_with("NA", &country), "NA");
+        assert_eq!(clean_value_with("#n/a", &country), "");
+
+        let french = CleaningRules {
+            decimal_comma: true,
+            thousands_separator: Some(" ".to_string()),
+            normalize_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(clean_value_with("4,5", &french), "4.5");
+        assert_eq!(clean_value_with("1\u{a0}200,0", &french), "1200");
+        assert_eq!(clean_value_with(" Rue  de la\u{a0}Paix ", &french), "Rue de la Paix");
+
+        let text = CleaningRules {
+            preserve_text: true,
+            ..Default::default()
+        };
+        assert_eq!(clean_value_with("10.0", &text), "10.0");
+
+        let config: CleaningConfig = serde_json::from_str(
+            r#"{"decimal_comma": true, "columns": {"Survey": {"null_tokens": []}}}"#,
+        )
+        .unwrap();
+        assert!(config.rules_for("Rating").decimal_comma);
+        assert_eq!(clean_value_with("None", config.rules_for("Survey")), "None");
+    }
+
+    #[test]
+    fn test_detect_delimiter() {
+        assert_eq!(detect_delimiter(b"Org ID;Nom du client\n"), b';');
+        assert_eq!(detect_delimiter(b"Org ID\tNom du client\n"), b'\t');
+        assert_eq!(detect_delimiter(b"Org ID,Nom du client\n"), b',');
+    }
+
+    #[test]
+    fn test_validate_csv_report() {
+        let path = std::env::temp_dir().join("onepager_report_test.csv");
+        std::fs::write(
+            &path,
+            "\u{feff}Org ID;Nom du client;Revenue;Revenue\n1;Acme;10;\n2;;nan;\n1;Acme Bis;12\n3;Beta;5;6\n",
+        )
+        .unwrap();
+
+        let report = validate_csv(&path, None, &CleaningConfig::default());
+        assert!(!report.valid);
+        assert_eq!(report.delimiter, ";");
+        assert_eq!(report.encoding, "UTF-8 (BOM)");
+        assert_eq!(report.headers[0], "Org ID");
+        assert_eq!(report.duplicate_headers, vec!["Revenue"]);
+        assert_eq!(report.row_count, 4);
+        assert_eq!(
+            report.ragged_rows,
+            vec![RaggedRow { row: 3, fields: 3, expected: 4 }]
+        );
+        assert!(report.missing_key_columns.is_empty());
+        assert_eq!(
+            report.empty_keys,
+            vec![EmptyKey { row: 2, column: "Nom du client".to_string() }]
+        );
+        assert_eq!(report.duplicate_keys.len(), 1);
+        assert_eq!(report.duplicate_keys[0].rows, vec![1, 3]);
+        assert_eq!(report.fill_rates[1].filled, 3);
+        assert_eq!(report.fill_rates[1].rate, 0.75);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_validate_csv_with_schema() {
+        let path = std::env::temp_dir().join("onepager_schema_test.csv");
+        std::fs::write(&path, "Org ID;Nom du client;Revenue\n1;A;1200\n2;B;12k\n").unwrap();
+        let schema: Schema = serde_json::from_str(
+            r#"{"columns": {"Revenue": {"type": "integer"}, "Email": {"type": "email"}}}"#,
+        )
+        .unwrap();
+
+        let report = validate_csv(&path, Some(&schema), &CleaningConfig::default());
+        assert!(!report.valid);
+        assert_eq!(report.row_count, 2);
+        assert_eq!(report.issues.len(), 2);
+        assert_eq!(report.issues[0].column, "Email");
+        assert_eq!((report.issues[1].row, report.issues[1].value.as_str()), (2, "12k"));
+
+        assert!(validate_csv(&path, None, &CleaningConfig::default()).valid);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_invalid_utf8_replaced() {
+        let path = std::env::temp_dir().join("onepager_invalid_utf8_test.csv");
+        // A lone surrogate encoded as UTF-8, then a Latin-1 byte
+        std::fs::write(&path, b"Org ID;Nom du client\n1;Caf\xED\xA0\x80\n2;Caf\xE9\n").unwrap();
+
+        let rows = read_csv_with(&path, usize::MAX, &CleaningConfig::default()).unwrap();
+        assert_eq!(rows[0]["Nom du client"], "Caf\u{FFFD}\u{FFFD}\u{FFFD}");
+        assert_eq!(rows[1]["Nom du client"], "Caf\u{FFFD}");
+
+        let report = validate_csv(&path, None, &CleaningConfig::default());
+        assert_eq!(report.encoding, "Unknown (not UTF-8)");
+        assert_eq!(report.row_count, 2);
+        assert!(report.issues.is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
 }
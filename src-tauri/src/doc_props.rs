@@ -0,0 +1,253 @@
+use crate::ooxml::Package;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const CORE_PART: &str = "docProps/core.xml";
+pub const APP_PART: &str = "docProps/app.xml";
+pub const CUSTOM_PART: &str = "docProps/custom.xml";
+
+const CUSTOM_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.custom-properties+xml";
+const CUSTOM_REL_TYPE: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/custom-properties";
+const CUSTOM_FMTID: &str = "{D5CDD505-2E9C-101B-9397-08002B2CF9AE}";
+
+/// Patterns for the document properties, e.g. `"<<Nom du client>> - One Pager"`.
+/// Unset properties keep the template value.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DocPropsConfig {
+    pub title: Option<String>,
+    pub subject: Option<String>,
+    pub author: Option<String>,
+    pub keywords: Option<String>,
+    pub company: Option<String>,
+    /// Custom properties written as text (`vt:lpwstr`)
+    pub custom: BTreeMap<String, String>,
+}
+
+/// Rewrites core, app and custom properties. `resolve` turns a pattern into
+/// XML-escaped text with the row tags filled in, its first error stops the
+/// rewrite. `dcterms:modified` is set to `modified` even when nothing is
+/// configured.
+pub fn apply<E>(
+    package: &mut Package,
+    config: &DocPropsConfig,
+    mut resolve: impl FnMut(&str) -> Result<String, E>,
+    modified: chrono::DateTime<chrono::Utc>,
+) -> Result<(), E> {
+    if let Some(mut core) = package.get_text(CORE_PART) {
+        let fields = [
+            ("dc:title", &config.title),
+            ("dc:subject", &config.subject),
+            ("dc:creator", &config.author),
+            ("cp:keywords", &config.keywords),
+        ];
+        for (element, pattern) in fields {
+            if let Some(pattern) = pattern {
+                core = set_element(
+                    &core,
+                    element,
+                    "",
+                    &resolve(pattern)?,
+                    "</cp:coreProperties>",
+                );
+            }
+        }
+        core = set_element(
+            &core,
+            "dcterms:modified",
+            r#" xsi:type="dcterms:W3CDTF""#,
+            &modified.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            "</cp:coreProperties>",
+        );
+        package.set(CORE_PART, core.into_bytes());
+    }
+
+    if let Some(company) = &config.company {
+        if let Some(app) = package.get_text(APP_PART) {
+            let app = set_element(&app, "Company", "", &resolve(company)?, "</Properties>");
+            package.set(APP_PART, app.into_bytes());
+        }
+    }
+
+    if !config.custom.is_empty() {
+        let resolved: Vec<(String, String)> = config
+            .custom
+            .iter()
+            .map(|(name, pattern)| Ok((name.clone(), resolve(pattern)?)))
+            .collect::<Result<_, E>>()?;
+        set_custom_properties(package, &resolved);
+    }
+    Ok(())
+}
+
+/// Replaces the content of `<element>`, or inserts it before `insert_before`.
+fn set_element(xml: &str, element: &str, attrs: &str, value: &str, insert_before: &str) -> String {
+    let name = regex::escape(element);
+    let re = Regex::new(&format!(
+        r"(?s)<{name}(\s[^>]*)?/>|<{name}(\s[^>]*)?>.*?</{name}>"
+    ))
+    .unwrap();
+
+    if let Some(caps) = re.captures(xml) {
+        let existing_attrs = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .map(|m| m.as_str())
+            .unwrap_or(attrs);
+        let replacement = format!("<{element}{existing_attrs}>{value}</{element}>");
+        let range = caps.get(0).unwrap().range();
+        return format!(
+            "{}{}{}",
+            &xml[..range.start],
+            replacement,
+            &xml[range.end..]
+        );
+    }
+
+    let element_xml = format!("<{element}{attrs}>{value}</{element}>");
+    match xml.rfind(insert_before) {
+        Some(pos) => format!("{}{}{}", &xml[..pos], element_xml, &xml[pos..]),
+        None => xml.to_string(),
+    }
+}
+
+fn set_custom_properties(package: &mut Package, properties: &[(String, String)]) {
+    let mut xml = match package.get_text(CUSTOM_PART) {
+        Some(xml) => xml,
+        None => {
+            package.add_override(CUSTOM_PART, CUSTOM_CONTENT_TYPE);
+            package.add_relationship("", CUSTOM_REL_TYPE, CUSTOM_PART);
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                "\r\n",
+                r#"<Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/custom-properties" xmlns:vt="http://schemas.openxmlformats.org/officeDocument/2006/docPropsVTypes"></Properties>"#
+            )
+            .to_string()
+        }
+    };
+
+    let re_pid = Regex::new(r#"\spid="(\d+)""#).unwrap();
+    let mut next_pid = re_pid
+        .captures_iter(&xml)
+        .filter_map(|c| c[1].parse::<u32>().ok())
+        .max()
+        .unwrap_or(1)
+        + 1;
+
+    for (name, value) in properties {
+        let name_attr = crate::pptx_engine::escape_xml(name);
+        let re_existing = Regex::new(&format!(
+            r#"(?s)<property\s[^>]*name="{}"[^>]*>.*?</property>"#,
+            regex::escape(&name_attr)
+        ))
+        .unwrap();
+
+        let pid = match re_existing.find(&xml) {
+            Some(m) => re_pid
+                .captures(m.as_str())
+                .map(|c| c[1].to_string())
+                .unwrap_or_default(),
+            None => {
+                next_pid += 1;
+                (next_pid - 1).to_string()
+            }
+        };
+        let property = format!(
+            r#"<property fmtid="{}" pid="{}" name="{}"><vt:lpwstr>{}</vt:lpwstr></property>"#,
+            CUSTOM_FMTID, pid, name_attr, value
+        );
+
+        xml = match re_existing.find(&xml) {
+            Some(m) => format!("{}{}{}", &xml[..m.start()], property, &xml[m.end()..]),
+            None => match xml.rfind("</Properties>") {
+                Some(pos) => format!("{}{}{}", &xml[..pos], property, &xml[pos..]),
+                None => xml,
+            },
+        };
+    }
+
+    package.set(CUSTOM_PART, xml.into_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ooxml::CONTENT_TYPES;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_apply_doc_props() {
+        let mut package = Package::default();
+        package.set(CONTENT_TYPES, b"<Types></Types>".to_vec());
+        package.set("_rels/.rels", br#"<Relationships><Relationship Id="rId1" Type="x" Target="ppt/presentation.xml"/></Relationships>"#.to_vec());
+        package.set(
+            CORE_PART,
+            br#"<cp:coreProperties><dc:title>Template_FR_v3</dc:title><dc:creator>Designer</dc:creator><dcterms:modified xsi:type="dcterms:W3CDTF">2020-01-01T00:00:00Z</dcterms:modified></cp:coreProperties>"#.to_vec(),
+        );
+        package.set(APP_PART, b"<Properties><Company/></Properties>".to_vec());
+
+        let config = DocPropsConfig {
+            title: Some("<<Nom du client>> One Pager".to_string()),
+            author: Some("Account Team".to_string()),
+            keywords: Some("q4".to_string()),
+            company: Some("Partoo".to_string()),
+            custom: [("OrgId".to_string(), "<<Org ID>>".to_string())].into(),
+            ..Default::default()
+        };
+        let resolve = |p: &str| {
+            Ok::<_, ()>(
+                p.replace("<<Nom du client>>", "A &amp; B")
+                    .replace("<<Org ID>>", "42"),
+            )
+        };
+        let now = chrono::Utc
+            .with_ymd_and_hms(2026, 10, 18, 9, 30, 0)
+            .unwrap();
+        apply(&mut package, &config, resolve, now).unwrap();
+
+        let core = package.get_text(CORE_PART).unwrap();
+        assert!(core.contains("<dc:title>A &amp; B One Pager</dc:title>"));
+        assert!(core.contains("<dc:creator>Account Team</dc:creator>"));
+        assert!(core.contains("<cp:keywords>q4</cp:keywords></cp:coreProperties>"));
+        assert!(core.contains(
+            r#"<dcterms:modified xsi:type="dcterms:W3CDTF">2026-10-18T09:30:00Z</dcterms:modified>"#
+        ));
+
+        let app = package.get_text(APP_PART).unwrap();
+        assert_eq!(app, "<Properties><Company>Partoo</Company></Properties>");
+
+        let custom = package.get_text(CUSTOM_PART).unwrap();
+        assert!(custom.contains(r#"pid="2" name="OrgId"><vt:lpwstr>42</vt:lpwstr>"#));
+        assert!(package
+            .get_text(CONTENT_TYPES)
+            .unwrap()
+            .contains("/docProps/custom.xml"));
+        assert!(package
+            .get_text("_rels/.rels")
+            .unwrap()
+            .contains(r#"Id="rId2""#));
+        // Without configuration only the modification date changes
+        let mut package = Package::default();
+        let template = r#"<cp:coreProperties><dc:title>T</dc:title><dcterms:modified xsi:type="dcterms:W3CDTF">2020-01-01T00:00:00Z</dcterms:modified></cp:coreProperties>"#;
+        package.set(CORE_PART, template.as_bytes().to_vec());
+        apply(
+            &mut package,
+            &DocPropsConfig::default(),
+            |p| Ok::<_, ()>(p.to_string()),
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            package.get_text(CORE_PART).unwrap(),
+            template.replace("2020-01-01T00:00:00Z", "2026-10-18T09:30:00Z")
+        );
+        assert!(!package.contains(CUSTOM_PART));
+
+        // Errors of the patterns are returned
+        let failing = apply(&mut package, &config, |_| Err("bad tag"), now);
+        assert_eq!(failing, Err("bad tag"));
+    }
+}
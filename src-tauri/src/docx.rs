@@ -0,0 +1,438 @@
+use crate::richtext::{self, Piece};
+use crate::styles::RunStyle;
+use regex::Regex;
+
+/// Children of `<w:rPr>` in schema order
+const RUN_PROPERTIES: [&str; 39] = [
+    "w:rStyle",
+    "w:rFonts",
+    "w:b",
+    "w:bCs",
+    "w:i",
+    "w:iCs",
+    "w:caps",
+    "w:smallCaps",
+    "w:strike",
+    "w:dstrike",
+    "w:outline",
+    "w:shadow",
+    "w:emboss",
+    "w:imprint",
+    "w:noProof",
+    "w:snapToGrid",
+    "w:vanish",
+    "w:webHidden",
+    "w:color",
+    "w:spacing",
+    "w:w",
+    "w:kern",
+    "w:position",
+    "w:sz",
+    "w:szCs",
+    "w:highlight",
+    "w:u",
+    "w:effect",
+    "w:bdr",
+    "w:shd",
+    "w:fitText",
+    "w:vertAlign",
+    "w:rtl",
+    "w:cs",
+    "w:em",
+    "w:lang",
+    "w:eastAsianLayout",
+    "w:specVanish",
+    "w:oMath",
+];
+
+/// Children of `<w:pPr>` in schema order
+const PARAGRAPH_PROPERTIES: [&str; 36] = [
+    "w:pStyle",
+    "w:keepNext",
+    "w:keepLines",
+    "w:pageBreakBefore",
+    "w:framePr",
+    "w:widowControl",
+    "w:numPr",
+    "w:suppressLineNumbers",
+    "w:pBdr",
+    "w:shd",
+    "w:tabs",
+    "w:suppressAutoHyphens",
+    "w:kinsoku",
+    "w:wordWrap",
+    "w:overflowPunct",
+    "w:topLinePunct",
+    "w:autoSpaceDE",
+    "w:autoSpaceDN",
+    "w:bidi",
+    "w:adjustRightInd",
+    "w:snapToGrid",
+    "w:spacing",
+    "w:ind",
+    "w:contextualSpacing",
+    "w:mirrorIndents",
+    "w:suppressOverlap",
+    "w:jc",
+    "w:textDirection",
+    "w:textAlignment",
+    "w:textboxTightWrap",
+    "w:outlineLvl",
+    "w:divId",
+    "w:cnfStyle",
+    "w:rPr",
+    "w:sectPr",
+    "w:pPrChange",
+];
+
+/// Word splits text into runs at every formatting, spelling or revision
+/// change, often in the middle of a tag. Moves the pieces of every tag into
+/// the run where it starts so that it can be resolved, never across
+/// paragraphs. Returns `None` when no tag is split.
+pub fn join_split_tags(xml: &str) -> Option<String> {
+    let re_text = Regex::new(r"(<w:t(?:\s[^>]*)?>)([^<]*)</w:t>").unwrap();
+    let re_tag = Regex::new(r"&lt;&lt;.*?&gt;&gt;").unwrap();
+    let re_paragraph = Regex::new(r"<w:p[\s>]|</w:p>").unwrap();
+    let pieces: Vec<regex::Captures> = re_text.captures_iter(xml).collect();
+    let mut texts: Vec<String> = pieces.iter().map(|c| c[2].to_string()).collect();
+    let mut changed = false;
+
+    let mut first = 0;
+    while first < pieces.len() {
+        // Pieces of the same paragraph
+        let mut last = first;
+        while last + 1 < pieces.len() {
+            let between =
+                &xml[pieces[last].get(0).unwrap().end()..pieces[last + 1].get(0).unwrap().start()];
+            if re_paragraph.is_match(between) {
+                break;
+            }
+            last += 1;
+        }
+
+        let joined: String = texts[first..=last].concat();
+        let mut owner: Vec<usize> = Vec::with_capacity(joined.len());
+        for (i, text) in texts[first..=last].iter().enumerate() {
+            owner.extend(std::iter::repeat_n(first + i, text.len()));
+        }
+        for tag in re_tag.find_iter(&joined) {
+            let start = owner[tag.start()];
+            if owner[tag.end() - 1] != start {
+                owner[tag.range()].fill(start);
+                changed = true;
+            }
+        }
+        if changed {
+            for (i, text) in texts[first..=last].iter_mut().enumerate() {
+                *text = joined
+                    .char_indices()
+                    .filter(|(b, _)| owner[*b] == first + i)
+                    .map(|(_, c)| c)
+                    .collect();
+            }
+        }
+        first = last + 1;
+    }
+    if !changed {
+        return None;
+    }
+
+    let mut out = String::with_capacity(xml.len());
+    let mut last_end = 0;
+    for (caps, text) in pieces.iter().zip(&texts) {
+        let whole = caps.get(0).unwrap();
+        out.push_str(&xml[last_end..whole.start()]);
+        if *text == caps[2] {
+            out.push_str(whole.as_str());
+        } else {
+            out.push_str(&preserved(&caps[1]));
+            out.push_str(text);
+            out.push_str("</w:t>");
+        }
+        last_end = whole.end();
+    }
+    out.push_str(&xml[last_end..]);
+    Some(out)
+}
+
+/// `<w:t>` keeping leading and trailing spaces, which Word drops otherwise.
+fn preserved(open: &str) -> String {
+    richtext::set_attr(open, "xml:space", "preserve")
+}
+
+/// Splits texts holding `\n` with `<w:br/>` inside their run.
+/// Returns `None` when no text has a line break.
+pub fn line_breaks(xml: &str) -> Option<String> {
+    if !xml.contains('\n') {
+        return None;
+    }
+    let re_text = Regex::new(r"(<w:t(?:\s[^>]*)?>)([^<]*\n[^<]*)</w:t>").unwrap();
+    let replaced = re_text.replace_all(xml, |caps: &regex::Captures| {
+        let open = preserved(&caps[1]);
+        caps[2]
+            .split('\n')
+            .map(|line| format!("{}{}</w:t>", open, line))
+            .collect::<Vec<_>>()
+            .join("<w:br/>")
+    });
+    match replaced {
+        std::borrow::Cow::Owned(text) => Some(text),
+        std::borrow::Cow::Borrowed(_) => None,
+    }
+}
+
+/// Rewrites the Word paragraphs holding values marked by the `paragraphs`,
+/// `markdown` and style tag filters, as `richtext::apply` does for slides:
+/// new paragraphs keep the paragraph properties, bullets get a `•` and a
+/// hanging indent, emphasized or styled pieces become runs of their own.
+/// Returns `None` without markers.
+pub fn rich_text(xml: &str) -> Option<String> {
+    if !richtext::holds_markers(xml) {
+        return None;
+    }
+    let re_paragraph = Regex::new(r"(?s)<w:p(?:\s[^>]*[^/>])?>.*?</w:p>").unwrap();
+    let text = re_paragraph.replace_all(xml, |caps: &regex::Captures| {
+        if richtext::holds_markers(&caps[0]) {
+            rewrite_paragraph(&caps[0])
+        } else {
+            caps[0].to_string()
+        }
+    });
+    Some(richtext::strip_markers(&text))
+}
+
+struct Paragraph {
+    bullet: Option<String>,
+    content: String,
+}
+
+fn rewrite_paragraph(xml: &str) -> String {
+    let re_parts =
+        Regex::new(r"(?s)^(<w:p(?:\s[^>]*)?>)(<w:pPr\b(?:[^>]*?/>|.*?</w:pPr>))?(.*)</w:p>$")
+            .unwrap();
+    let Some(parts) = re_parts.captures(xml) else {
+        return xml.to_string();
+    };
+    let open = &parts[1];
+    let p_pr = parts.get(2).map_or("", |m| m.as_str());
+    let body = &parts[3];
+
+    let re_run = Regex::new(
+        r"(?s)(<w:r(?:\s[^>]*)?>)\s*(<w:rPr\b(?:[^>]*?/>|.*?</w:rPr>))?\s*(<w:t(?:\s[^>]*)?>)([^<]*)</w:t>\s*</w:r>",
+    )
+    .unwrap();
+    let mut paragraphs = vec![Paragraph {
+        bullet: None,
+        content: String::new(),
+    }];
+    let mut last = 0;
+    for run in re_run.captures_iter(body) {
+        let whole = run.get(0).unwrap();
+        let current = paragraphs.last_mut().unwrap();
+        current.content.push_str(&body[last..whole.start()]);
+        last = whole.end();
+        let text = &run[4];
+        if !richtext::holds_markers(text) {
+            current.content.push_str(whole.as_str());
+            continue;
+        }
+
+        let r_open = &run[1];
+        let r_pr = run.get(2).map_or("", |m| m.as_str());
+        let t_open = preserved(&run[3]);
+        // The bullet character takes the formatting of the value
+        let bullet = format!("{}{}<w:t>•</w:t><w:tab/></w:r>", r_open, r_pr);
+        for piece in richtext::pieces(text) {
+            match piece {
+                Piece::Text(text, style) => {
+                    let r_pr = run_properties(r_pr, &style);
+                    paragraphs
+                        .last_mut()
+                        .unwrap()
+                        .content
+                        .push_str(&format!("{}{}{}{}</w:t></w:r>", r_open, r_pr, t_open, text));
+                }
+                Piece::Paragraph => paragraphs.push(Paragraph {
+                    bullet: None,
+                    content: String::new(),
+                }),
+                Piece::Bullet if paragraphs.last().unwrap().content.is_empty() => {
+                    paragraphs.last_mut().unwrap().bullet = Some(bullet.clone());
+                }
+                Piece::Bullet => paragraphs.push(Paragraph {
+                    bullet: Some(bullet.clone()),
+                    content: String::new(),
+                }),
+            }
+        }
+    }
+    paragraphs
+        .last_mut()
+        .unwrap()
+        .content
+        .push_str(&body[last..]);
+
+    // Paragraph ids must stay unique, Word gives new paragraphs their own
+    let re_ids = Regex::new(r#"\sw14:(?:paraId|textId)="[^"]*""#).unwrap();
+    paragraphs
+        .into_iter()
+        .enumerate()
+        .map(|(i, paragraph)| {
+            let open = match i {
+                0 => open.to_string(),
+                _ => re_ids.replace_all(open, "").into_owned(),
+            };
+            match paragraph.bullet {
+                Some(bullet) => format!(
+                    "{}{}{}{}</w:p>",
+                    open,
+                    bulleted(p_pr),
+                    bullet,
+                    paragraph.content
+                ),
+                None => format!("{}{}{}</w:p>", open, p_pr, paragraph.content),
+            }
+        })
+        .collect()
+}
+
+/// Run properties with the style applied.
+fn run_properties(r_pr: &str, style: &RunStyle) -> String {
+    if style.bold.is_none() && style.italic.is_none() && style.color.is_none() {
+        return r_pr.to_string();
+    }
+    let mut r_pr = if r_pr.is_empty() {
+        "<w:rPr/>".to_string()
+    } else {
+        r_pr.to_string()
+    };
+    for (set, name) in [(style.bold, "w:b"), (style.italic, "w:i")] {
+        let child = match set {
+            Some(true) => format!("<{}/>", name),
+            Some(false) => format!(r#"<{} w:val="0"/>"#, name),
+            None => continue,
+        };
+        r_pr = richtext::set_child(&r_pr, name, &child, &RUN_PROPERTIES);
+    }
+    if let Some(color) = &style.color {
+        let child = format!(r#"<w:color w:val="{}"/>"#, color);
+        r_pr = richtext::set_child(&r_pr, "w:color", &child, &RUN_PROPERTIES);
+    }
+    r_pr
+}
+
+/// Paragraph properties with a hanging indent for the `•` and tab, out of
+/// any numbered list.
+fn bulleted(p_pr: &str) -> String {
+    let p_pr = if p_pr.is_empty() { "<w:pPr/>" } else { p_pr };
+    let p_pr = richtext::set_child(p_pr, "w:numPr", "", &PARAGRAPH_PROPERTIES);
+    richtext::set_child(
+        &p_pr,
+        "w:ind",
+        r#"<w:ind w:left="360" w:hanging="360"/>"#,
+        &PARAGRAPH_PROPERTIES,
+    )
+}
+
+/// Puts an empty paragraph back into the table cells and text boxes left
+/// without one, Word requires it. Returns `None` when none is empty.
+pub fn keep_paragraphs(xml: &str) -> Option<String> {
+    let re_empty = Regex::new(
+        r"(?s)(<w:tc(?:\s[^>]*)?>\s*(?:<w:tcPr\b(?:[^>]*?/>|.*?</w:tcPr>))?\s*)(</w:tc>)|(<w:txbxContent(?:\s[^>]*)?>\s*)(</w:txbxContent>)",
+    )
+    .unwrap();
+    let replaced = re_empty.replace_all(xml, "$1$3<w:p/>$2$4");
+    match replaced {
+        std::borrow::Cow::Owned(text) => Some(text),
+        std::borrow::Cow::Borrowed(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_split_tags() {
+        let xml = concat!(
+            r#"<w:p><w:r><w:t>Dear &lt;</w:t></w:r><w:proofErr w:type="spellStart"/>"#,
+            r#"<w:r><w:rPr><w:b/></w:rPr><w:t>&lt;Client</w:t></w:r><w:r><w:t>&gt;&gt;, welcome</w:t></w:r></w:p>"#,
+            r#"<w:p><w:r><w:t>&lt;&lt;Open</w:t></w:r></w:p><w:p><w:r><w:t>&gt;&gt;</w:t></w:r></w:p>"#
+        );
+        assert_eq!(
+            join_split_tags(xml).unwrap(),
+            concat!(
+                r#"<w:p><w:r><w:t xml:space="preserve">Dear &lt;&lt;Client&gt;&gt;</w:t></w:r><w:proofErr w:type="spellStart"/>"#,
+                r#"<w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve"></w:t></w:r><w:r><w:t xml:space="preserve">, welcome</w:t></w:r></w:p>"#,
+                r#"<w:p><w:r><w:t>&lt;&lt;Open</w:t></w:r></w:p><w:p><w:r><w:t>&gt;&gt;</w:t></w:r></w:p>"#
+            )
+        );
+        assert!(
+            join_split_tags("<w:p><w:r><w:t>&lt;&lt;Client&gt;&gt;</w:t></w:r></w:p>").is_none()
+        );
+    }
+
+    #[test]
+    fn test_rich_text() {
+        let style = RunStyle {
+            color: Some("FF0000".to_string()),
+            ..Default::default()
+        };
+        let value = format!(
+            "{}{}",
+            richtext::markdown("**Top** rated\n- Fast"),
+            richtext::styled(" -12%", &style)
+        );
+        let xml = format!(
+            r#"<w:p w14:paraId="1A"><w:pPr><w:numPr><w:numId w:val="2"/></w:numPr><w:jc w:val="left"/></w:pPr><w:r><w:rPr><w:rFonts w:ascii="Arial"/><w:sz w:val="20"/></w:rPr><w:t>{}</w:t></w:r></w:p><w:p/>"#,
+            value
+        );
+        assert_eq!(
+            rich_text(&xml).unwrap(),
+            concat!(
+                r#"<w:p w14:paraId="1A"><w:pPr><w:numPr><w:numId w:val="2"/></w:numPr><w:jc w:val="left"/></w:pPr>"#,
+                r#"<w:r><w:rPr><w:rFonts w:ascii="Arial"/><w:b/><w:sz w:val="20"/></w:rPr><w:t xml:space="preserve">Top</w:t></w:r>"#,
+                r#"<w:r><w:rPr><w:rFonts w:ascii="Arial"/><w:sz w:val="20"/></w:rPr><w:t xml:space="preserve"> rated</w:t></w:r></w:p>"#,
+                r#"<w:p><w:pPr><w:ind w:left="360" w:hanging="360"/><w:jc w:val="left"/></w:pPr>"#,
+                r#"<w:r><w:rPr><w:rFonts w:ascii="Arial"/><w:sz w:val="20"/></w:rPr><w:t>•</w:t><w:tab/></w:r>"#,
+                r#"<w:r><w:rPr><w:rFonts w:ascii="Arial"/><w:sz w:val="20"/></w:rPr><w:t xml:space="preserve">Fast</w:t></w:r>"#,
+                r#"<w:r><w:rPr><w:rFonts w:ascii="Arial"/><w:color w:val="FF0000"/><w:sz w:val="20"/></w:rPr><w:t xml:space="preserve"> -12%</w:t></w:r></w:p><w:p/>"#
+            )
+        );
+        assert!(rich_text("<w:p><w:r><w:t>Plain</w:t></w:r></w:p>").is_none());
+    }
+
+    #[test]
+    fn test_required_removes_paragraph() {
+        use crate::ooxml::DocumentKind;
+        use crate::tags;
+        let xml = concat!(
+            r#"<w:body><w:p w14:paraId="1"><w:r><w:t>Rating: &lt;&lt;Rating|required&gt;&gt;</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:jc w:val="left"/></w:pPr><w:r><w:t>Kept</w:t></w:r></w:p>"#,
+            r#"<w:tbl><w:tr><w:tc><w:tcPr><w:tcW w:w="10"/></w:tcPr><w:p><w:r><w:t>&lt;&lt;Rating|required:shape&gt;&gt;</w:t></w:r></w:p></w:tc></w:tr></w:tbl></w:body>"#
+        );
+        let lookup = |name: &str| (name == "Rating").then(String::new);
+        let resolved = tags::resolve(xml, DocumentKind::Document, lookup, None, |_, _, _| None);
+        assert_eq!(
+            resolved.text.unwrap(),
+            concat!(
+                r#"<w:body><w:p><w:pPr><w:jc w:val="left"/></w:pPr><w:r><w:t>Kept</w:t></w:r></w:p>"#,
+                r#"<w:tbl><w:tr><w:tc><w:tcPr><w:tcW w:w="10"/></w:tcPr><w:p/></w:tc></w:tr></w:tbl></w:body>"#
+            )
+        );
+        assert_eq!(resolved.missing, vec!["Rating", "Rating"]);
+    }
+
+    #[test]
+    fn test_line_breaks() {
+        assert_eq!(
+            line_breaks(
+                r#"<w:r><w:t xml:space="preserve">1 rue
+Paris</w:t></w:r>"#
+            )
+            .unwrap(),
+            r#"<w:r><w:t xml:space="preserve">1 rue</w:t><w:br/><w:t xml:space="preserve">Paris</w:t></w:r>"#
+        );
+        assert!(line_breaks("<w:body>\n</w:body>").is_none());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::ooxml::PackageError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Why a file could not be read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IoKind {
+    NotFound,
+    PermissionDenied,
+    /// Held open by another program, usually PowerPoint or Excel
+    Locked,
+    Other,
+}
+
+/// Errors surfaced to the frontend. Serialized as `{"code": "...", ...context}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum OnePagerError {
+    Io {
+        path: String,
+        kind: IoKind,
+        message: String,
+    },
+    Csv {
+        path: String,
+        /// 1-based line in the file
+        line: Option<u64>,
+        /// 1-based field index
+        column: Option<usize>,
+        message: String,
+    },
+    /// The file is not a readable ZIP package
+    Zip { path: String, message: String },
+    Xml {
+        path: String,
+        part: String,
+        message: String,
+    },
+    /// The package opened but cannot be used as a template
+    Template { path: String, message: String },
+    /// A tag, expression or translation could not be resolved
+    Mapping {
+        path: String,
+        part: String,
+        tag: String,
+        message: String,
+    },
+    /// Settings that leave nothing to generate or point to nothing usable
+    Config { message: String },
+    /// Post-processing of a run: PDF export, manifest, summary, archives
+    Output { message: String },
+}
+
+/// A tag of a part that could not be resolved, turned into
+/// `OnePagerError::Mapping` once the template and part are known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagError {
+    pub tag: String,
+    pub message: String,
+}
+
+impl OnePagerError {
+    pub fn mapping(path: &Path, part: &str, e: TagError) -> Self {
+        OnePagerError::Mapping {
+            path: path.to_string_lossy().to_string(),
+            part: part.to_string(),
+            tag: e.tag,
+            message: e.message,
+        }
+    }
+
+    pub fn io(path: &Path, e: &std::io::Error) -> Self {
+        // Windows sharing violations, or the `~$` owner file Office keeps next to open documents
+        let owner_file = path
+            .file_name()
+            .map(|name| path.with_file_name(format!("~${}", name.to_string_lossy())));
+        let kind = if matches!(e.raw_os_error(), Some(32) | Some(33))
+            || owner_file.is_some_and(|f| f.exists())
+        {
+            IoKind::Locked
+        } else {
+            match e.kind() {
+                std::io::ErrorKind::NotFound => IoKind::NotFound,
+                std::io::ErrorKind::PermissionDenied => IoKind::PermissionDenied,
+                _ => IoKind::Other,
+            }
+        };
+        OnePagerError::Io {
+            path: path.to_string_lossy().to_string(),
+            kind,
+            message: e.to_string(),
+        }
+    }
+
+    pub fn csv(path: &Path, e: &csv::Error) -> Self {
+        if let csv::ErrorKind::Io(io) = e.kind() {
+            return Self::io(path, io);
+        }
+        let column = match e.kind() {
+            csv::ErrorKind::Utf8 { err, .. } => Some(err.field() + 1),
+            _ => None,
+        };
+        let message = match e.kind() {
+            csv::ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => format!("Expected {} fields, found {}", expected_len, len),
+            _ => e.to_string(),
+        };
+        OnePagerError::Csv {
+            path: path.to_string_lossy().to_string(),
+            line: e.position().map(|p| p.line()),
+            column,
+            message,
+        }
+    }
+
+    pub fn package(path: &Path, e: PackageError) -> Self {
+        let path = path.to_string_lossy().to_string();
+        match e {
+            PackageError::Io(message) => OnePagerError::Io {
+                path,
+                kind: IoKind::Other,
+                message,
+            },
+            PackageError::Zip(message) => OnePagerError::Zip { path, message },
+            PackageError::Encoding { name, message } => OnePagerError::Xml {
+                path,
+                part: name,
+                message,
+            },
+            other => OnePagerError::Template {
+                path,
+                message: other.to_string(),
+            },
+        }
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        OnePagerError::Config {
+            message: message.into(),
+        }
+    }
+
+    pub fn output(message: impl Into<String>) -> Self {
+        OnePagerError::Output {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for OnePagerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnePagerError::Io {
+                path,
+                kind: IoKind::Locked,
+                ..
+            } => write!(f, "{} is open in another program", path),
+            OnePagerError::Io { path, message, .. } => write!(f, "{}: {}", path, message),
+            OnePagerError::Csv {
+                path,
+                line,
+                column,
+                message,
+            } => {
+                write!(f, "{}", path)?;
+                if let Some(line) = line {
+                    write!(f, ", line {}", line)?;
+                }
+                if let Some(column) = column {
+                    write!(f, ", column {}", column)?;
+                }
+                write!(f, ": {}", message)
+            }
+            OnePagerError::Zip { path, message } => {
+                write!(f, "{} is not a valid Office file: {}", path, message)
+            }
+            OnePagerError::Xml {
+                path,
+                part,
+                message,
+            } => write!(f, "{} ({}): {}", path, part, message),
+            OnePagerError::Template { path, message } => write!(f, "{}: {}", path, message),
+            OnePagerError::Mapping {
+                path,
+                part,
+                tag,
+                message,
+            } => write!(f, "{} ({}), {}: {}", path, part, tag, message),
+            OnePagerError::Config { message } | OnePagerError::Output { message } => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OnePagerError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_with_code_and_context() {
+        let e = OnePagerError::Csv {
+            path: "data.csv".to_string(),
+            line: Some(12),
+            column: None,
+            message: "Expected 5 fields, found 4".to_string(),
+        };
+        let json = serde_json::to_value(&e).unwrap();
+        assert_eq!(json["code"], "csv");
+        assert_eq!(json["line"], 12);
+        assert_eq!(
+            e.to_string(),
+            "data.csv, line 12: Expected 5 fields, found 4"
+        );
+    }
+
+    #[test]
+    fn test_io_kinds() {
+        let dir = std::env::temp_dir().join("one_pager_test_error_locked");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let deck = dir.join("OnePager.pptx");
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(matches!(
+            OnePagerError::io(&deck, &missing),
+            OnePagerError::Io {
+                kind: IoKind::NotFound,
+                ..
+            }
+        ));
+
+        std::fs::write(dir.join("~$OnePager.pptx"), b"").unwrap();
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let e = OnePagerError::io(&deck, &denied);
+        assert!(matches!(
+            e,
+            OnePagerError::Io {
+                kind: IoKind::Locked,
+                ..
+            }
+        ));
+        assert!(e.to_string().ends_with("is open in another program"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,616 @@
+use crate::csv_handler::{parse_number, CleaningRules};
+use crate::error::TagError;
+use crate::slides::is_truthy;
+use regex::Regex;
+
+/// An evaluation failure, `position` is the character offset in the expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl ExprError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        ExprError {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn render(&self) -> String {
+        match self {
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => format!("{}", *n as i64),
+            Value::Number(n) => n.to_string(),
+            Value::Text(s) => s.clone(),
+            Value::Bool(b) => b.to_string(),
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Number(n) => *n != 0.0,
+            Value::Text(s) => is_truthy(s),
+            Value::Bool(b) => *b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+// Two-character operators first so `<=` is not read as `<`
+const OPERATORS: [&str; 16] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "&", "=",
+];
+
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>, ExprError> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()))
+        {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse::<f64>()
+                .map_err(|_| ExprError::new(format!("Invalid number {}", text), start))?;
+            tokens.push((Token::Number(n), start));
+            continue;
+        }
+        // Straight or typographic quotes, PowerPoint converts them as you type
+        let closing = match c {
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            '\u{201c}' | '\u{201d}' => Some('\u{201d}'),
+            '\u{2018}' | '\u{2019}' => Some('\u{2019}'),
+            _ => None,
+        };
+        if let Some(closing) = closing {
+            i += 1;
+            let begin = i;
+            while i < chars.len() && chars[i] != closing {
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(ExprError::new("Unterminated text", start));
+            }
+            tokens.push((Token::Text(chars[begin..i].iter().collect()), start));
+            i += 1;
+            continue;
+        }
+        if c == '[' {
+            // Column names with spaces: [Nom du client]
+            let end = chars[i..]
+                .iter()
+                .position(|&ch| ch == ']')
+                .ok_or_else(|| ExprError::new("Unclosed [", start))?;
+            tokens.push((Token::Ident(chars[i + 1..i + end].iter().collect()), start));
+            i += end + 1;
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' || c == '#' {
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '#' | '.'))
+            {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), start));
+            continue;
+        }
+        match c {
+            '(' => tokens.push((Token::LParen, start)),
+            ')' => tokens.push((Token::RParen, start)),
+            ',' => tokens.push((Token::Comma, start)),
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let op = OPERATORS
+                    .iter()
+                    .find(|op| rest.starts_with(*op))
+                    .ok_or_else(|| ExprError::new(format!("Unexpected character {}", c), start))?;
+                tokens.push((Token::Op(op), start));
+                i += op.chars().count();
+                continue;
+            }
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// Nesting of parentheses, calls and prefix operators allowed in an
+/// expression, deeper ones would exhaust the stack.
+const MAX_DEPTH: usize = 64;
+
+struct Evaluator<'a, F: Fn(&str) -> Option<String>> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+    end: usize,
+    lookup: &'a F,
+    rules: &'a CleaningRules,
+}
+
+impl<F: Fn(&str) -> Option<String>> Evaluator<'_, F> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(_, p)| *p)
+            .unwrap_or(self.end)
+    }
+
+    fn eat_op(&mut self, ops: &[&str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            Some(Token::Ident(word)) => {
+                let op = match word.to_lowercase().as_str() {
+                    "and" => "&&",
+                    "or" => "||",
+                    "not" => "!",
+                    _ => return None,
+                };
+                if !ops.contains(&op) {
+                    return None;
+                }
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), ExprError> {
+        if self.peek() == Some(&token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(ExprError::new(
+                format!("Expected {}", what),
+                self.position(),
+            ))
+        }
+    }
+
+    fn number(&self, value: &Value, position: usize) -> Result<f64, ExprError> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            Value::Text(s) if s.trim().is_empty() => {
+                Err(ExprError::new("Empty value used as a number", position))
+            }
+            Value::Text(s) => parse_number(s, self.rules)
+                .ok_or_else(|| ExprError::new(format!("\"{}\" is not a number", s), position)),
+        }
+    }
+
+    /// Runs `parse` one nesting level deeper.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Value, ExprError>,
+    ) -> Result<Value, ExprError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ExprError::new(
+                "Expression nested too deeply",
+                self.position(),
+            ));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn expression(&mut self) -> Result<Value, ExprError> {
+        let mut left = self.and()?;
+        while self.eat_op(&["||"]).is_some() {
+            let right = self.and()?;
+            left = Value::Bool(left.truthy() || right.truthy());
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Value, ExprError> {
+        let mut left = self.not()?;
+        while self.eat_op(&["&&"]).is_some() {
+            let right = self.not()?;
+            left = Value::Bool(left.truthy() && right.truthy());
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Value, ExprError> {
+        if self.eat_op(&["!"]).is_some() {
+            return Ok(Value::Bool(!self.nested(Self::not)?.truthy()));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Value, ExprError> {
+        let left = self.additive()?;
+        let position = self.position();
+        let Some(op) = self.eat_op(&["==", "=", "!=", "<", "<=", ">", ">="]) else {
+            return Ok(left);
+        };
+        let right = self.additive()?;
+
+        let ordering = match (self.number(&left, position), self.number(&right, position)) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => Some(left.render().cmp(&right.render())),
+        };
+        let ordering = ordering.ok_or_else(|| ExprError::new("Cannot compare", position))?;
+        Ok(Value::Bool(match op {
+            "==" | "=" => ordering.is_eq(),
+            "!=" => ordering.is_ne(),
+            "<" => ordering.is_lt(),
+            "<=" => ordering.is_le(),
+            ">" => ordering.is_gt(),
+            _ => ordering.is_ge(),
+        }))
+    }
+
+    fn additive(&mut self) -> Result<Value, ExprError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let position = self.position();
+            let Some(op) = self.eat_op(&["+", "-", "&"]) else {
+                return Ok(left);
+            };
+            let right = self.multiplicative()?;
+            left = match op {
+                "&" => Value::Text(left.render() + &right.render()),
+                "+" => {
+                    Value::Number(self.number(&left, position)? + self.number(&right, position)?)
+                }
+                _ => Value::Number(self.number(&left, position)? - self.number(&right, position)?),
+            };
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Value, ExprError> {
+        let mut left = self.unary()?;
+        loop {
+            let position = self.position();
+            let Some(op) = self.eat_op(&["*", "/", "%"]) else {
+                return Ok(left);
+            };
+            let right = self.unary()?;
+            let (a, b) = (
+                self.number(&left, position)?,
+                self.number(&right, position)?,
+            );
+            if op != "*" && b == 0.0 {
+                return Err(ExprError::new("Division by zero", position));
+            }
+            left = Value::Number(match op {
+                "*" => a * b,
+                "/" => a / b,
+                _ => a % b,
+            });
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, ExprError> {
+        let position = self.position();
+        if self.eat_op(&["-"]).is_some() {
+            let value = self.nested(Self::unary)?;
+            return Ok(Value::Number(-self.number(&value, position)?));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Value, ExprError> {
+        let position = self.position();
+        let Some((token, _)) = self.tokens.get(self.pos).cloned() else {
+            return Err(ExprError::new("Unexpected end of expression", position));
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Value::Number(n)),
+            Token::Text(s) => Ok(Value::Text(s)),
+            Token::LParen => {
+                let value = self.nested(Self::expression)?;
+                self.expect(Token::RParen, ")")?;
+                Ok(value)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::LParen) => {
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    loop {
+                        args.push((self.position(), self.nested(Self::expression)?));
+                        if self.peek() != Some(&Token::Comma) {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                self.expect(Token::RParen, ")")?;
+                self.call(&name, args, position)
+            }
+            Token::Ident(name) => match name.to_lowercase().as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => (self.lookup)(&name)
+                    .map(Value::Text)
+                    .ok_or_else(|| ExprError::new(format!("Unknown column {}", name), position)),
+            },
+            _ => Err(ExprError::new("Unexpected token", position)),
+        }
+    }
+
+    fn call(
+        &self,
+        name: &str,
+        args: Vec<(usize, Value)>,
+        position: usize,
+    ) -> Result<Value, ExprError> {
+        let arity = |n: std::ops::RangeInclusive<usize>| {
+            if n.contains(&args.len()) {
+                Ok(())
+            } else {
+                Err(ExprError::new(
+                    format!("Wrong number of arguments for {}", name),
+                    position,
+                ))
+            }
+        };
+        let num = |i: usize| self.number(&args[i].1, args[i].0);
+
+        match name.to_lowercase().as_str() {
+            "if" => {
+                arity(2..=3)?;
+                Ok(if args[0].1.truthy() {
+                    args[1].1.clone()
+                } else {
+                    args.get(2)
+                        .map(|a| a.1.clone())
+                        .unwrap_or(Value::Text(String::new()))
+                })
+            }
+            "round" => {
+                arity(1..=2)?;
+                let factor = 10f64.powi(if args.len() == 2 { num(1)? as i32 } else { 0 });
+                Ok(Value::Number((num(0)? * factor).round() / factor))
+            }
+            "floor" => {
+                arity(1..=1)?;
+                Ok(Value::Number(num(0)?.floor()))
+            }
+            "ceil" => {
+                arity(1..=1)?;
+                Ok(Value::Number(num(0)?.ceil()))
+            }
+            "abs" => {
+                arity(1..=1)?;
+                Ok(Value::Number(num(0)?.abs()))
+            }
+            "min" | "max" => {
+                arity(1..=usize::MAX)?;
+                let mut values = Vec::new();
+                for i in 0..args.len() {
+                    values.push(num(i)?);
+                }
+                let pick = if name.eq_ignore_ascii_case("min") {
+                    f64::min
+                } else {
+                    f64::max
+                };
+                Ok(Value::Number(
+                    values.into_iter().reduce(pick).unwrap_or_default(),
+                ))
+            }
+            "coalesce" => Ok(args
+                .into_iter()
+                .map(|a| a.1)
+                .find(|v| !v.render().trim().is_empty())
+                .unwrap_or(Value::Text(String::new()))),
+            "upper" => {
+                arity(1..=1)?;
+                Ok(Value::Text(args[0].1.render().to_uppercase()))
+            }
+            "lower" => {
+                arity(1..=1)?;
+                Ok(Value::Text(args[0].1.render().to_lowercase()))
+            }
+            _ => Err(ExprError::new(
+                format!("Unknown function {}", name),
+                position,
+            )),
+        }
+    }
+}
+
+/// Evaluates one expression against the row values. Only the operators and
+/// functions above are available, nothing reaches outside the row.
+pub fn evaluate(
+    expr: &str,
+    lookup: &impl Fn(&str) -> Option<String>,
+    rules: &CleaningRules,
+) -> Result<String, ExprError> {
+    let tokens = tokenize(expr)?;
+    let mut evaluator = Evaluator {
+        tokens,
+        pos: 0,
+        depth: 0,
+        end: expr.chars().count(),
+        lookup,
+        rules,
+    };
+    let value = evaluator.expression()?;
+    if evaluator.pos < evaluator.tokens.len() {
+        return Err(ExprError::new("Unexpected input", evaluator.position()));
+    }
+    Ok(value.render())
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Replaces every `<<= expression>>` of an XML part with its value.
+/// Returns `None` when the part has no expression.
+pub fn resolve_expressions(
+    xml: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+    rules: &CleaningRules,
+) -> Result<Option<String>, TagError> {
+    let re_expr = Regex::new(r"(?:<<|&lt;&lt;)=([^<>]*?)(?:>>|&gt;&gt;)").unwrap();
+    if !re_expr.is_match(xml) {
+        return Ok(None);
+    }
+
+    let mut out = String::with_capacity(xml.len());
+    let mut last = 0;
+    for caps in re_expr.captures_iter(xml) {
+        let whole = caps.get(0).unwrap();
+        let expr = unescape_xml(&caps[1]);
+        let value = evaluate(&expr, &lookup, rules).map_err(|e| TagError {
+            tag: format!("<<={}>>", expr),
+            message: format!("{} (column {})", e.message, e.position + 1),
+        })?;
+        out.push_str(&xml[last..whole.start()]);
+        out.push_str(&crate::pptx_engine::escape_xml(&value));
+        last = whole.end();
+    }
+    out.push_str(&xml[last..]);
+    Ok(Some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "Reviews_Q4" => Some("120".to_string()),
+            "Reviews_Q3" => Some("95".to_string()),
+            "Rating" => Some("4.6".to_string()),
+            "Clicks" => Some("1 234".to_string()),
+            "Impressions" => Some("50000".to_string()),
+            "Nom du client" => Some("ACME".to_string()),
+            "Empty" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    fn eval(expr: &str) -> Result<String, ExprError> {
+        let rules = CleaningRules {
+            thousands_separator: Some(" ".to_string()),
+            ..Default::default()
+        };
+        evaluate(expr, &lookup, &rules)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(eval("Reviews_Q4 - Reviews_Q3").unwrap(), "25");
+        assert_eq!(
+            eval("if(Rating >= 4.5, \"Excellent\", \"Good\")").unwrap(),
+            "Excellent"
+        );
+        assert_eq!(
+            eval("if(Rating >= 4.8, “Excellent”, “Good”)").unwrap(),
+            "Good"
+        );
+        assert_eq!(eval("round(Clicks / Impressions * 100, 1)").unwrap(), "2.5");
+        assert_eq!(
+            eval("[Nom du client] & ' (' & upper('fr') & ')'").unwrap(),
+            "ACME (FR)"
+        );
+        assert_eq!(eval("coalesce(Empty, 0) + 1").unwrap(), "1");
+        assert_eq!(eval("Rating > 4 and not Empty").unwrap(), "true");
+        assert_eq!(eval("-(2 + 3) * 2").unwrap(), "-10");
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        assert_eq!(
+            eval("Unknown + 1").unwrap_err().message,
+            "Unknown column Unknown"
+        );
+        let err = eval("Reviews_Q4 / Empty").unwrap_err();
+        assert_eq!(err.message, "Empty value used as a number");
+        assert_eq!(err.position, 11);
+        assert!(eval("round(1").is_err());
+        assert!(eval("system(\"rm\")").is_err());
+
+        let deep = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(
+            eval(&deep).unwrap_err().message,
+            "Expression nested too deeply"
+        );
+        assert!(eval(&format!("{}1", "-".repeat(100_000))).is_err());
+        assert!(eval(&format!(
+            "{}1{}",
+            "abs(".repeat(100_000),
+            ")".repeat(100_000)
+        ))
+        .is_err());
+        assert_eq!(
+            eval(&format!(
+                "{}1{}",
+                "(".repeat(MAX_DEPTH),
+                ")".repeat(MAX_DEPTH)
+            ))
+            .unwrap(),
+            "1"
+        );
+    }
+
+    #[test]
+    fn test_resolve_expressions() {
+        let xml = "<a:t>+&lt;&lt;= Reviews_Q4 - Reviews_Q3&gt;&gt; reviews, &lt;&lt;= if(Rating &gt;= 4.5, &quot;A&amp;B&quot;, &quot;C&quot;)&gt;&gt;</a:t>";
+        let rules = CleaningRules::default();
+        assert_eq!(
+            resolve_expressions(xml, lookup, &rules).unwrap().unwrap(),
+            "<a:t>+25 reviews, A&amp;B</a:t>"
+        );
+        let err =
+            resolve_expressions("<a:t>&lt;&lt;= 1 / 0&gt;&gt;</a:t>", lookup, &rules).unwrap_err();
+        assert_eq!(err.tag, "<<= 1 / 0>>");
+        assert!(err.message.starts_with("Division by zero"));
+        assert!(
+            resolve_expressions("<a:t>&lt;&lt;Rating&gt;&gt;</a:t>", lookup, &rules)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
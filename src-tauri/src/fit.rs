@@ -0,0 +1,796 @@
+use crate::ooxml::{self, Package};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use walkdir::WalkDir;
+
+const EMU_PER_POINT: f64 = 12700.0;
+/// Used when a shape, its placeholders and the master text styles give no size
+const DEFAULT_FONT_SIZE: f64 = 18.0;
+/// Advance in em of characters without font metrics
+const FALLBACK_ADVANCE: f64 = 0.5;
+const LINE_SPACING: f64 = 1.2;
+const ELLIPSIS: char = '…';
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FitMode {
+    /// Lower the run font sizes
+    #[default]
+    Shrink,
+    /// Let PowerPoint scale the text with `normAutofit fontScale`
+    Autofit,
+    /// Cut the text and end it with `…`
+    Ellipsis,
+}
+
+/// Fits substituted text into its shape, using font metrics of local
+/// TrueType/OpenType files to estimate the rendered size.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TextFitConfig {
+    #[serde(default)]
+    pub mode: FitMode,
+    /// Points. The largest size of a shape is not reduced below it.
+    #[serde(default = "default_min_font_size")]
+    pub min_font_size: f64,
+    /// Folders searched for `.ttf`, `.otf` and `.ttc` files, the system
+    /// font folders when empty
+    #[serde(default)]
+    pub font_dirs: Vec<String>,
+}
+
+fn default_min_font_size() -> f64 {
+    10.0
+}
+
+impl Default for TextFitConfig {
+    fn default() -> Self {
+        TextFitConfig {
+            mode: FitMode::default(),
+            min_font_size: default_min_font_size(),
+            font_dirs: Vec::new(),
+        }
+    }
+}
+
+/// One text adjustment, reported in the run manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextFit {
+    pub part: String,
+    pub shape: String,
+    pub action: String,
+    /// The size of the box is unknown, the text was left as is
+    #[serde(default)]
+    pub skipped: bool,
+}
+
+/// Font files by family name and weight, read on first use.
+#[derive(Default)]
+pub struct FontBook {
+    faces: HashMap<(String, bool), (PathBuf, u32)>,
+    loaded: Mutex<HashMap<PathBuf, Arc<Vec<u8>>>>,
+}
+
+impl FontBook {
+    pub fn load(dirs: &[String]) -> Self {
+        let dirs: Vec<PathBuf> = if dirs.is_empty() {
+            system_font_dirs()
+        } else {
+            dirs.iter().map(PathBuf::from).collect()
+        };
+        let mut faces = HashMap::new();
+        for dir in dirs {
+            for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+                let path = entry.path();
+                let is_font = path.extension().is_some_and(|e| {
+                    let e = e.to_string_lossy().to_lowercase();
+                    e == "ttf" || e == "otf" || e == "ttc"
+                });
+                let Some(data) = is_font.then(|| std::fs::read(path).ok()).flatten() else {
+                    continue;
+                };
+                let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+                for index in 0..count {
+                    let Ok(face) = ttf_parser::Face::parse(&data, index) else {
+                        continue;
+                    };
+                    let Some(family) = family_name(&face) else {
+                        continue;
+                    };
+                    faces
+                        .entry((family.to_lowercase(), face.is_bold()))
+                        .or_insert_with(|| (path.to_path_buf(), index));
+                }
+            }
+        }
+        FontBook {
+            faces,
+            loaded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Width in points of `text` set in `typeface` at `size` points.
+    fn text_width(&self, text: &str, typeface: &str, bold: bool, size: f64) -> f64 {
+        let key = typeface.to_lowercase();
+        let face = self
+            .faces
+            .get(&(key.clone(), bold))
+            .or_else(|| self.faces.get(&(key, !bold)));
+        let data = face.and_then(|(path, index)| Some((self.data(path)?, *index)));
+        let face = data
+            .as_ref()
+            .and_then(|(data, index)| ttf_parser::Face::parse(data, *index).ok());
+        let em: f64 = match &face {
+            Some(face) => {
+                let units = face.units_per_em() as f64;
+                text.chars()
+                    .map(|c| {
+                        face.glyph_index(c)
+                            .and_then(|g| face.glyph_hor_advance(g))
+                            .map_or(FALLBACK_ADVANCE, |a| a as f64 / units)
+                    })
+                    .sum()
+            }
+            None => text.chars().count() as f64 * FALLBACK_ADVANCE,
+        };
+        em * size
+    }
+
+    fn data(&self, path: &PathBuf) -> Option<Arc<Vec<u8>>> {
+        let mut loaded = self.loaded.lock().ok()?;
+        if let Some(data) = loaded.get(path) {
+            return Some(data.clone());
+        }
+        let data = Arc::new(std::fs::read(path).ok()?);
+        loaded.insert(path.clone(), data.clone());
+        Some(data)
+    }
+}
+
+fn family_name(face: &ttf_parser::Face) -> Option<String> {
+    let names: Vec<_> = face.names().into_iter().collect();
+    [
+        ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+        ttf_parser::name_id::FAMILY,
+    ]
+    .iter()
+    .find_map(|id| {
+        names
+            .iter()
+            .filter(|n| n.name_id == *id)
+            .find_map(|n| n.to_string())
+    })
+}
+
+fn system_font_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from(r"C:\Windows\Fonts"),
+        PathBuf::from("/Library/Fonts"),
+        PathBuf::from("/System/Library/Fonts"),
+        PathBuf::from("/usr/share/fonts"),
+    ];
+    if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+        let home = PathBuf::from(home);
+        dirs.push(home.join("Library/Fonts"));
+        dirs.push(home.join(".local/share/fonts"));
+        dirs.push(home.join(r"AppData\Local\Microsoft\Windows\Fonts"));
+    }
+    dirs.into_iter().filter(|d| d.is_dir()).collect()
+}
+
+/// Major (headings) and minor (body) latin fonts of the first theme.
+pub fn theme_fonts(package: &Package) -> (String, String) {
+    let theme = package
+        .names()
+        .find(|n| n.starts_with("ppt/theme/theme") && n.ends_with(".xml"))
+        .and_then(|n| package.get_text(n))
+        .unwrap_or_default();
+    let font = |kind: &str| {
+        let re = Regex::new(&format!(
+            r#"(?s)<a:{}Font>.*?<a:latin typeface="([^"]*)""#,
+            kind
+        ))
+        .unwrap();
+        re.captures(&theme)
+            .map_or_else(|| "Calibri".to_string(), |c| c[1].to_string())
+    };
+    (font("major"), font("minor"))
+}
+
+/// Slide layout and master of a slide, where placeholders take their
+/// position, insets and text sizes from.
+#[derive(Debug, Default)]
+pub struct Masters {
+    layout: String,
+    master: String,
+}
+
+pub fn masters(package: &Package, slide: &str) -> Masters {
+    let related = |part: &str, rel_type: &str| {
+        package
+            .relationships(part)
+            .into_iter()
+            .find(|r| r.rel_type.ends_with(rel_type))
+            .map(|r| ooxml::resolve_target(part, &r.target))
+    };
+    let layout = related(slide, "/slideLayout");
+    let master = layout.as_deref().and_then(|l| related(l, "/slideMaster"));
+    let text = |part: Option<String>| part.and_then(|p| package.get_text(&p)).unwrap_or_default();
+    Masters {
+        layout: text(layout),
+        master: text(master),
+    }
+}
+
+/// Type and index of a placeholder shape, `obj` and `0` when unset.
+fn placeholder(shape: &str) -> Option<(&str, Option<&str>)> {
+    let ph = Regex::new(r"<p:ph\b[^>]*>").unwrap().find(shape)?.as_str();
+    Some((attr(ph, "type").unwrap_or("obj"), attr(ph, "idx")))
+}
+
+/// Master placeholders come in fewer types than layout ones.
+fn master_type(kind: &str) -> &str {
+    match kind {
+        "title" | "ctrTitle" => "title",
+        "dt" | "ftr" | "sldNum" | "hdr" => kind,
+        _ => "body",
+    }
+}
+
+impl Masters {
+    /// Layout then master placeholders a slide placeholder inherits from:
+    /// matched by index in the layout when it has one, by type otherwise.
+    fn inherited<'a>(&'a self, shape: &str) -> Vec<&'a str> {
+        let Some((kind, idx)) = placeholder(shape) else {
+            return Vec::new();
+        };
+        let find = |xml: &'a str, matches: &dyn Fn(&str, Option<&str>) -> bool| {
+            re_shape()
+                .find_iter(xml)
+                .map(|m| m.as_str())
+                .find(|sp| placeholder(sp).is_some_and(|(k, i)| matches(k, i)))
+        };
+        let layout = match idx {
+            Some(idx) => find(&self.layout, &|_, i| i == Some(idx)),
+            None => find(&self.layout, &|k, _| k == kind),
+        };
+        let kind = layout.and_then(placeholder).map_or(kind, |(k, _)| k);
+        let master = find(&self.master, &|k, _| master_type(k) == master_type(kind));
+        layout.into_iter().chain(master).collect()
+    }
+
+    /// Level 1 size of the master text style used by the placeholder.
+    fn style_size(&self, shape: &str) -> Option<f64> {
+        let style = match placeholder(shape).map(|(kind, _)| master_type(kind)) {
+            Some("title") => "titleStyle",
+            Some("body") => "bodyStyle",
+            _ => "otherStyle",
+        };
+        let re_style = Regex::new(&format!(r"(?s)<p:{0}>.*?</p:{0}>", style)).unwrap();
+        level1_size(re_style.find(&self.master)?.as_str())
+    }
+}
+
+/// `sz` of the level 1 default run properties of a list style.
+fn level1_size(xml: &str) -> Option<f64> {
+    let re_level = Regex::new(r"(?s)<a:lvl1pPr\b(?:[^>]*?/>|.*?</a:lvl1pPr>)").unwrap();
+    let re_def = Regex::new(r"<a:defRPr\b[^>]*>").unwrap();
+    let level = re_level.find(xml)?.as_str();
+    attr(re_def.find(level)?.as_str(), "sz")
+        .and_then(|sz| sz.parse::<f64>().ok())
+        .map(|sz| sz / 100.0)
+}
+
+fn re_shape() -> Regex {
+    Regex::new(r"(?s)<p:sp(?:\s[^>]*)?>.*?</p:sp>").unwrap()
+}
+
+fn attr<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let re = Regex::new(&format!(r#"\s{}="([^"]*)""#, name)).unwrap();
+    re.captures(element).map(|c| c.get(1).unwrap().as_str())
+}
+
+fn shape_id(shape: &str) -> Option<&str> {
+    let re_nv = Regex::new(r"<p:cNvPr\b[^>]*>").unwrap();
+    attr(re_nv.find(shape)?.as_str(), "id")
+}
+
+/// Ids of the shapes holding a `<<tag>>`, taken before the tags are resolved.
+pub fn tagged_shapes(xml: &str) -> HashSet<String> {
+    re_shape()
+        .find_iter(xml)
+        .filter(|m| m.as_str().contains("&lt;&lt;") || m.as_str().contains("<<"))
+        .filter_map(|m| shape_id(m.as_str()).map(str::to_string))
+        .collect()
+}
+
+struct Run {
+    /// Byte range of the `<a:t>` text in the shape
+    text: std::ops::Range<usize>,
+    /// Byte range of the `<a:rPr>`, empty at the run start when absent
+    r_pr: std::ops::Range<usize>,
+    size: f64,
+    typeface: String,
+    bold: bool,
+}
+
+/// Lines of a shape: runs by `<a:br/>`-separated line, by paragraph.
+struct Layout {
+    runs: Vec<Run>,
+    lines: Vec<Vec<usize>>,
+    width: f64,
+    height: f64,
+    wrap: bool,
+}
+
+impl Layout {
+    /// Whether the text fits once every size is scaled by `scale`,
+    /// with `texts` standing for the current run texts.
+    fn fits(&self, fonts: &FontBook, texts: &[String], scale: f64) -> bool {
+        let mut height = 0.0;
+        for line in &self.lines {
+            let width: f64 = line
+                .iter()
+                .map(|&i| {
+                    let run = &self.runs[i];
+                    fonts.text_width(&texts[i], &run.typeface, run.bold, run.size * scale)
+                })
+                .sum();
+            let size = line.iter().map(|&i| self.runs[i].size).fold(0.0, f64::max);
+            // Empty lines take the height of the largest text
+            let size = if size > 0.0 { size } else { self.max_size() };
+            if !self.wrap && width > self.width {
+                return false;
+            }
+            let wrapped = if self.wrap {
+                (width / self.width).ceil().max(1.0)
+            } else {
+                1.0
+            };
+            height += wrapped * size * scale * LINE_SPACING;
+        }
+        height <= self.height
+    }
+
+    fn max_size(&self) -> f64 {
+        self.runs.iter().map(|r| r.size).fold(0.0, f64::max)
+    }
+}
+
+fn layout(shape: &str, major: &str, minor: &str, masters: &Masters) -> Option<Layout> {
+    // Placeholders leave out what their layout or master placeholder sets
+    let inherited = masters.inherited(shape);
+    let chain = || std::iter::once(shape).chain(inherited.iter().copied());
+    let re_ext = Regex::new(r#"(?s)<a:xfrm\b.*?<a:ext cx="(\d+)" cy="(\d+)""#).unwrap();
+    let ext = chain().find_map(|sp| re_ext.captures(sp))?;
+    let re_body = Regex::new(r"<a:bodyPr\b[^>]*>").unwrap();
+    if !re_body.is_match(shape) {
+        return None;
+    }
+    let body_attr = |name: &str| {
+        chain()
+            .filter_map(|sp| re_body.find(sp))
+            .find_map(|body| attr(body.as_str(), name))
+    };
+    let inset = |name: &str, default: f64| {
+        body_attr(name)
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(default)
+    };
+    let width = (ext[1].parse::<f64>().ok()? - inset("lIns", 91440.0) - inset("rIns", 91440.0))
+        / EMU_PER_POINT;
+    let height = (ext[2].parse::<f64>().ok()? - inset("tIns", 45720.0) - inset("bIns", 45720.0))
+        / EMU_PER_POINT;
+    if width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+
+    // Shape defaults: list style level 1, then title placeholders use the major font
+    let re_list = Regex::new(r"(?s)<a:lstStyle>.*?</a:lstStyle>").unwrap();
+    let default_size = chain()
+        .filter_map(|sp| re_list.find(sp))
+        .find_map(|list| level1_size(list.as_str()))
+        .or_else(|| masters.style_size(shape))
+        .unwrap_or(DEFAULT_FONT_SIZE);
+    let is_title = Regex::new(r#"<p:ph\b[^>]*type="(?:title|ctrTitle)""#)
+        .unwrap()
+        .is_match(shape);
+    let default_face = if is_title { major } else { minor };
+
+    let re_paragraph = Regex::new(r"(?s)<a:p(?:\s[^>]*)?>.*?</a:p>|<a:p/>").unwrap();
+    let re_item = Regex::new(
+        r"(?s)<a:r>\s*(<a:rPr\b(?:[^>]*?/>|.*?</a:rPr>))?\s*<a:t>([^<]*)</a:t>\s*</a:r>|<a:br\b",
+    )
+    .unwrap();
+    let re_latin = Regex::new(r#"<a:latin typeface="([^"]*)""#).unwrap();
+    let mut runs = Vec::new();
+    let mut lines = Vec::new();
+    for paragraph in re_paragraph.find_iter(shape) {
+        let mut line = Vec::new();
+        for item in re_item.captures_iter(paragraph.as_str()) {
+            let Some(text) = item.get(2) else {
+                lines.push(std::mem::take(&mut line));
+                continue;
+            };
+            let r_pr = item.get(1).map_or("", |m| m.as_str());
+            let typeface = re_latin
+                .captures(r_pr)
+                .map_or(default_face, |c| c.get(1).unwrap().as_str());
+            let typeface = match typeface {
+                "+mj-lt" => major,
+                "+mn-lt" => minor,
+                other => other,
+            };
+            let offset = paragraph.start();
+            let start = item.get(0).unwrap().start() + offset;
+            line.push(runs.len());
+            runs.push(Run {
+                text: text.start() + offset..text.end() + offset,
+                r_pr: item.get(1).map_or(start + 5..start + 5, |m| {
+                    m.start() + offset..m.end() + offset
+                }),
+                size: attr(r_pr, "sz")
+                    .and_then(|sz| sz.parse::<f64>().ok())
+                    .map_or(default_size, |sz| sz / 100.0),
+                typeface: typeface.to_string(),
+                bold: attr(r_pr, "b") == Some("1"),
+            });
+        }
+        lines.push(line);
+    }
+    if runs.is_empty() {
+        return None;
+    }
+    Some(Layout {
+        runs,
+        lines,
+        width,
+        height,
+        wrap: body_attr("wrap") != Some("none"),
+    })
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Fits the tagged shapes of a slide whose estimated text overflows its box.
+/// Returns the new part text, `None` when unchanged, and the adjustments.
+pub fn fit_shapes(
+    xml: &str,
+    part: &str,
+    tagged: &HashSet<String>,
+    fonts: &FontBook,
+    theme: &(String, String),
+    masters: &Masters,
+    config: &TextFitConfig,
+) -> (Option<String>, Vec<TextFit>) {
+    let mut fits = Vec::new();
+    let text = re_shape().replace_all(xml, |caps: &regex::Captures| {
+        let shape = &caps[0];
+        if !shape_id(shape).is_some_and(|id| tagged.contains(id)) {
+            return shape.to_string();
+        }
+        let re_nv = Regex::new(r"<p:cNvPr\b[^>]*>").unwrap();
+        let name = re_nv
+            .find(shape)
+            .and_then(|m| attr(m.as_str(), "name"))
+            .unwrap_or_default()
+            .to_string();
+        let Some(layout) = layout(shape, &theme.0, &theme.1, masters) else {
+            if shape.contains("<a:t>") {
+                fits.push(TextFit {
+                    part: part.to_string(),
+                    shape: name,
+                    action: "not checked, box size unknown".to_string(),
+                    skipped: true,
+                });
+            }
+            return shape.to_string();
+        };
+        let texts: Vec<String> = layout
+            .runs
+            .iter()
+            .map(|r| unescape(&shape[r.text.clone()]))
+            .collect();
+        if layout.fits(fonts, &texts, 1.0) {
+            return shape.to_string();
+        }
+        let (fitted, action) = match config.mode {
+            FitMode::Shrink | FitMode::Autofit => {
+                // Whole percents, down to the minimum size
+                let min_percent = (config.min_font_size / layout.max_size() * 100.0).ceil();
+                let mut percent = 100.0;
+                while percent - 1.0 >= min_percent && !layout.fits(fonts, &texts, percent / 100.0) {
+                    percent -= 1.0;
+                }
+                let scale = percent / 100.0;
+                let overflow = if layout.fits(fonts, &texts, scale) {
+                    ""
+                } else {
+                    ", still overflowing"
+                };
+                if config.mode == FitMode::Shrink {
+                    let action = format!(
+                        "font size {} -> {} pt{}",
+                        layout.max_size(),
+                        (layout.max_size() * scale * 100.0).round() / 100.0,
+                        overflow
+                    );
+                    (shrink(shape, &layout, scale), action)
+                } else {
+                    let action = format!("font scale {:.0}%{}", scale * 100.0, overflow);
+                    (autofit(shape, scale), action)
+                }
+            }
+            FitMode::Ellipsis => match truncate(shape, &layout, fonts, texts) {
+                Some((fitted, kept)) => (fitted, format!("truncated to {} characters", kept)),
+                None => return shape.to_string(),
+            },
+        };
+        fits.push(TextFit {
+            part: part.to_string(),
+            shape: name,
+            action,
+            skipped: false,
+        });
+        fitted
+    });
+    if fits.iter().all(|f| f.skipped) {
+        (None, fits)
+    } else {
+        (Some(text.into_owned()), fits)
+    }
+}
+
+/// Rewrites the byte ranges of `shape`, which must be sorted and disjoint.
+fn splice(shape: &str, edits: Vec<(std::ops::Range<usize>, String)>) -> String {
+    let mut out = String::with_capacity(shape.len());
+    let mut last = 0;
+    for (range, text) in edits {
+        out.push_str(&shape[last..range.start]);
+        out.push_str(&text);
+        last = range.end;
+    }
+    out.push_str(&shape[last..]);
+    out
+}
+
+fn shrink(shape: &str, layout: &Layout, scale: f64) -> String {
+    let re_sz = Regex::new(r#"\ssz="\d+""#).unwrap();
+    let edits = layout
+        .runs
+        .iter()
+        .map(|run| {
+            let sz = format!(r#" sz="{}""#, (run.size * scale * 100.0).round() as u32);
+            let r_pr = &shape[run.r_pr.clone()];
+            let r_pr = if r_pr.is_empty() {
+                format!("<a:rPr{}/>", sz)
+            } else {
+                let r_pr = re_sz.replace(r_pr, "");
+                r_pr.replacen("<a:rPr", &format!("<a:rPr{}", sz), 1)
+            };
+            (run.r_pr.clone(), r_pr)
+        })
+        .collect();
+    splice(shape, edits)
+}
+
+fn autofit(shape: &str, scale: f64) -> String {
+    let re_autofit = Regex::new(r"<a:noAutofit/>|<a:spAutoFit/>|<a:normAutofit\b[^>]*/>").unwrap();
+    let shape = re_autofit.replace_all(shape, "").into_owned();
+    let normautofit = format!(
+        r#"<a:normAutofit fontScale="{}"/>"#,
+        (scale * 100.0).round() as u32 * 1000
+    );
+    let re_body = Regex::new(r"<a:bodyPr\b[^>]*?(/?)>").unwrap();
+    let Some(body) = re_body.captures(&shape) else {
+        return shape;
+    };
+    let whole = body.get(0).unwrap();
+    if body[1].is_empty() {
+        // Autofit goes after the preset text warp when there is one
+        let re_warp =
+            Regex::new(r"(?s)^\s*<a:prstTxWarp\b(?:[^>]*?/>|.*?</a:prstTxWarp>)").unwrap();
+        let pos = whole.end() + re_warp.find(&shape[whole.end()..]).map_or(0, |m| m.end());
+        format!("{}{}{}", &shape[..pos], normautofit, &shape[pos..])
+    } else {
+        format!(
+            "{}{}>{}</a:bodyPr>{}",
+            &shape[..whole.start()],
+            whole.as_str().trim_end_matches("/>"),
+            normautofit,
+            &shape[whole.end()..]
+        )
+    }
+}
+
+/// Drops characters from the end of the text until it fits with a `…`.
+/// Returns the shape and the number of characters kept.
+fn truncate(
+    shape: &str,
+    layout: &Layout,
+    fonts: &FontBook,
+    mut texts: Vec<String>,
+) -> Option<(String, usize)> {
+    let original = texts.clone();
+    loop {
+        let last = texts.iter().rposition(|t| !t.is_empty())?;
+        let mut chars: Vec<char> = texts[last].chars().collect();
+        if chars.last() == Some(&ELLIPSIS) {
+            chars.pop();
+        }
+        chars.pop();
+        while chars.last().is_some_and(|c| c.is_whitespace()) {
+            chars.pop();
+        }
+        let cut: String = chars.into_iter().collect();
+        texts[last] = if cut.is_empty() {
+            cut
+        } else {
+            format!("{}{}", cut, ELLIPSIS)
+        };
+        if texts.iter().all(|t| t.is_empty()) {
+            return None;
+        }
+        if layout.fits(fonts, &texts, 1.0) {
+            break;
+        }
+    }
+    let kept = texts
+        .iter()
+        .map(|t| t.trim_end_matches(ELLIPSIS).chars().count())
+        .sum();
+    let edits = layout
+        .runs
+        .iter()
+        .zip(texts.iter().zip(&original))
+        .filter(|(_, (text, original))| text != original)
+        .map(|(run, (text, _))| (run.text.clone(), crate::pptx_engine::escape_xml(text)))
+        .collect();
+    Some((splice(shape, edits), kept))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2 x 0.5 inch box without insets: 144 x 36 pt
+    fn slide(body_pr: &str, text: &str) -> String {
+        format!(
+            concat!(
+                r#"<p:sld><p:cSld><p:spTree><p:sp><p:nvSpPr><p:cNvPr id="2" name="Client"/></p:nvSpPr>"#,
+                r#"<p:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="1828800" cy="457200"/></a:xfrm></p:spPr>"#,
+                r#"<p:txBody>{}<a:p><a:r><a:rPr lang="fr-FR" sz="2400"/><a:t>{}</a:t></a:r></a:p></p:txBody></p:sp>"#,
+                r#"</p:spTree></p:cSld></p:sld>"#
+            ),
+            body_pr, text
+        )
+    }
+
+    const BODY: &str = r#"<a:bodyPr wrap="none" lIns="0" tIns="0" rIns="0" bIns="0"/>"#;
+
+    #[test]
+    fn test_tagged_shapes() {
+        let xml = slide(BODY, "&lt;&lt;Nom du client&gt;&gt;");
+        assert_eq!(tagged_shapes(&xml), HashSet::from(["2".to_string()]));
+        assert!(tagged_shapes(&slide(BODY, "Static")).is_empty());
+    }
+
+    #[test]
+    fn test_shrink_and_autofit() {
+        let fonts = FontBook::default();
+        let theme = ("Calibri".to_string(), "Calibri".to_string());
+        let tagged = HashSet::from(["2".to_string()]);
+        let config = TextFitConfig::default();
+
+        // 12 characters at 24 pt take 144 pt, just fits
+        let fitting = slide(BODY, "ABCDEFGHIJKL");
+        let (text, fits) = fit_shapes(
+            &fitting,
+            "s",
+            &tagged,
+            &fonts,
+            &theme,
+            &Masters::default(),
+            &config,
+        );
+        assert!(text.is_none() && fits.is_empty());
+
+        let long = slide(BODY, "ABCDEFGHIJKLMNOP");
+        let (text, fits) = fit_shapes(
+            &long,
+            "s",
+            &tagged,
+            &fonts,
+            &theme,
+            &Masters::default(),
+            &config,
+        );
+        assert!(text.unwrap().contains(r#"<a:rPr sz="1800" lang="fr-FR"/>"#));
+        assert_eq!(fits[0].shape, "Client");
+        assert_eq!(fits[0].action, "font size 24 -> 18 pt");
+
+        let config = TextFitConfig {
+            mode: FitMode::Autofit,
+            ..Default::default()
+        };
+        let (text, fits) = fit_shapes(
+            &long,
+            "s",
+            &tagged,
+            &fonts,
+            &theme,
+            &Masters::default(),
+            &config,
+        );
+        assert!(text
+            .unwrap()
+            .contains(r#"bIns="0"><a:normAutofit fontScale="75000"/></a:bodyPr>"#));
+        assert_eq!(fits[0].action, "font scale 75%");
+    }
+
+    #[test]
+    fn test_ellipsis() {
+        let config = TextFitConfig {
+            mode: FitMode::Ellipsis,
+            ..Default::default()
+        };
+        let long = slide(BODY, "Boulangerie &amp; Fils Martin");
+        let (text, fits) = fit_shapes(
+            &long,
+            "s",
+            &HashSet::from(["2".to_string()]),
+            &FontBook::default(),
+            &("Calibri".to_string(), "Calibri".to_string()),
+            &Masters::default(),
+            &config,
+        );
+        assert!(text.unwrap().contains("<a:t>Boulangerie…</a:t>"));
+        assert_eq!(fits[0].action, "truncated to 11 characters");
+    }
+
+    #[test]
+    fn test_placeholder_inherits_layout_and_master() {
+        let fonts = FontBook::default();
+        let theme = ("Calibri".to_string(), "Calibri".to_string());
+        let tagged = HashSet::from(["2".to_string()]);
+        let config = TextFitConfig::default();
+        let shape = concat!(
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="2" name="Body"/><p:cNvSpPr/><p:nvPr><p:ph idx="1"/></p:nvPr></p:nvSpPr>"#,
+            r#"<p:spPr/><p:txBody><a:bodyPr/><a:lstStyle/><a:p><a:r><a:rPr lang="fr-FR"/><a:t>ABCDEFGHIJKLMNOP</a:t></a:r></a:p></p:txBody></p:sp>"#
+        );
+        let masters = Masters {
+            layout: concat!(
+                r#"<p:sp><p:nvSpPr><p:cNvPr id="3" name="Text"/><p:cNvSpPr/><p:nvPr><p:ph type="body" idx="1"/></p:nvPr></p:nvSpPr>"#,
+                r#"<p:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="1828800" cy="457200"/></a:xfrm></p:spPr>"#,
+                r#"<p:txBody><a:bodyPr wrap="none" lIns="0" tIns="0" rIns="0" bIns="0"/><a:lstStyle/><a:p/></p:txBody></p:sp>"#
+            )
+            .to_string(),
+            master: r#"<p:txStyles><p:titleStyle><a:lvl1pPr><a:defRPr sz="4400"/></a:lvl1pPr></p:titleStyle><p:bodyStyle><a:lvl1pPr marL="0"><a:spcBef><a:spcPts val="0"/></a:spcBef><a:defRPr sz="2400"/></a:lvl1pPr></p:bodyStyle></p:txStyles>"#.to_string(),
+        };
+        let (text, fits) = fit_shapes(shape, "s", &tagged, &fonts, &theme, &masters, &config);
+        assert!(text.unwrap().contains(r#"<a:rPr sz="1800" lang="fr-FR"/>"#));
+        assert_eq!(fits[0].action, "font size 24 -> 18 pt");
+
+        // Without its layout the size of the box is unknown
+        let (text, fits) = fit_shapes(
+            shape,
+            "s",
+            &tagged,
+            &fonts,
+            &theme,
+            &Masters::default(),
+            &config,
+        );
+        assert!(text.is_none());
+        assert!(fits[0].skipped);
+        assert_eq!(fits[0].shape, "Body");
+    }
+}
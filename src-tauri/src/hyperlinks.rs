@@ -0,0 +1,141 @@
+use crate::pptx_engine::escape_xml;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use regex::Regex;
+
+/// Everything but RFC 3986 unreserved characters gets encoded.
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Resolves tags in the `Target` of external hyperlink relationships.
+///
+/// PowerPoint stores a typed `<<Tag>>` either XML-escaped or percent-encoded
+/// (`%3C%3CTag%3E%3E`), both are accepted. A tag opening the URL (optionally
+/// after a bare `http://` prefix) receives the value as a full URL, any other
+/// tag is a URL component and gets percent-encoded.
+/// Returns `None` when nothing was replaced.
+pub fn resolve_targets(rels_xml: &str, lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let re_rel = Regex::new(r"<Relationship\s[^>]*?/>").unwrap();
+    let re_target = Regex::new(r#"\sTarget="([^"]*)""#).unwrap();
+    let mut changed = false;
+
+    let result = re_rel.replace_all(rels_xml, |caps: &regex::Captures| {
+        let element = &caps[0];
+        if !element.contains(r#"TargetMode="External""#) || !element.contains("/hyperlink\"") {
+            return element.to_string();
+        }
+        let Some(target) = re_target.captures(element) else {
+            return element.to_string();
+        };
+        match resolve_url(&target[1], &lookup) {
+            Some(url) => {
+                changed = true;
+                let range = target.get(1).unwrap().range();
+                format!(
+                    "{}{}{}",
+                    &element[..range.start],
+                    url,
+                    &element[range.end..]
+                )
+            }
+            None => element.to_string(),
+        }
+    });
+
+    if changed {
+        Some(result.into_owned())
+    } else {
+        None
+    }
+}
+
+/// Resolves the tags of one XML-escaped URL attribute value.
+fn resolve_url(target: &str, lookup: &impl Fn(&str) -> Option<String>) -> Option<String> {
+    let re_tag = Regex::new(r"(?i)(?:&lt;|%3C){2}(.+?)(?:&gt;|%3E){2}").unwrap();
+    let mut out = String::new();
+    let mut last = 0;
+    let mut changed = false;
+
+    for caps in re_tag.captures_iter(target) {
+        let m = caps.get(0).unwrap();
+        let name = percent_decode_str(&caps[1]).decode_utf8_lossy().to_string();
+        let name = name
+            .replace("&amp;", "&")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'");
+        let Some(value) = lookup(&name) else {
+            continue;
+        };
+
+        let prefix = &target[last..m.start()];
+        let is_base = last == 0 && (prefix.is_empty() || is_bare_scheme(prefix));
+        if is_base {
+            // A full URL value replaces the placeholder scheme as well
+            if !value.contains("://") {
+                out.push_str(prefix);
+            }
+            out.push_str(&escape_xml(&value));
+        } else {
+            out.push_str(prefix);
+            out.push_str(&utf8_percent_encode(&value, URL_COMPONENT).to_string());
+        }
+        last = m.end();
+        changed = true;
+    }
+
+    if !changed {
+        return None;
+    }
+    out.push_str(&target[last..]);
+    Some(out)
+}
+
+fn is_bare_scheme(prefix: &str) -> bool {
+    let lower = prefix.to_lowercase();
+    lower == "http://" || lower == "https://"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "Org ID" => Some("12 & 3".to_string()),
+            "Dashboard" => Some("https://app.example.com/d?x=1&y=2".to_string()),
+            _ => None,
+        }
+    }
+
+    fn rels(target: &str) -> String {
+        format!(
+            r#"<Relationships><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="{}" TargetMode="External"/></Relationships>"#,
+            target
+        )
+    }
+
+    #[test]
+    fn test_component_values_are_encoded() {
+        let xml = rels("https://example.com/org?id=%3C%3COrg%20ID%3E%3E&amp;lang=fr");
+        let out = resolve_targets(&xml, lookup).unwrap();
+        assert!(out.contains(r#"Target="https://example.com/org?id=12%20%26%203&amp;lang=fr""#));
+    }
+
+    #[test]
+    fn test_full_url_values() {
+        let out = resolve_targets(&rels("&lt;&lt;Dashboard&gt;&gt;"), lookup).unwrap();
+        assert!(out.contains(r#"Target="https://app.example.com/d?x=1&amp;y=2""#));
+
+        let out = resolve_targets(&rels("http://%3c%3cDashboard%3e%3e"), lookup).unwrap();
+        assert!(out.contains(r#"Target="https://app.example.com/d?x=1&amp;y=2""#));
+    }
+
+    #[test]
+    fn test_unknown_tags_and_internal_rels_untouched() {
+        assert!(resolve_targets(&rels("%3C%3CMissing%3E%3E"), lookup).is_none());
+        let internal = r#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide" Target="slide%3C%3COrg ID%3E%3E.xml"/></Relationships>"#;
+        assert!(resolve_targets(internal, lookup).is_none());
+    }
+}
@@ -2,8 +2,34 @@ use tauri::Emitter;
 use std::collections::HashMap;
 
 mod csv_handler;
+mod doc_props;
+mod docx;
+mod error;
+mod expr;
+mod fit;
+mod hyperlinks;
+mod lookup;
+mod manifest;
+mod merge;
+mod ooxml;
+mod packaging;
+mod pdf_export;
 mod pptx_engine;
+mod project;
+mod richtext;
+mod sanitize;
+mod schema;
+mod shapes;
+mod slides;
+mod styles;
+mod summary;
+mod table;
+mod tags;
+mod translations;
+mod validate;
+mod xlsx;
 
+use error::OnePagerError;
 use pptx_engine::GenConfig;
 
 #[tauri::command]
@@ -12,12 +38,12 @@ fn greet(name: &str) -> String {
 }
 
 #[tauri::command]
-fn read_csv_preview_cmd(path: String) -> Result<Vec<HashMap<String, String>>, String> {
+fn read_csv_preview_cmd(path: String) -> Result<Vec<HashMap<String, String>>, OnePagerError> {
     csv_handler::read_csv_preview(path, 5)
 }
 
 #[tauri::command]
-async fn generate_presentations_cmd(app: tauri::AppHandle, config: GenConfig) -> Result<pptx_engine::GenStats, String> {
+async fn generate_presentations_cmd(app: tauri::AppHandle, config: GenConfig) -> Result<pptx_engine::GenStats, OnePagerError> {
     let result = std::thread::spawn(move || {
         pptx_engine::generate_pptx(config, |progress, msg| {
              let _ = app.emit("progress", (progress, msg));
@@ -26,7 +52,7 @@ async fn generate_presentations_cmd(app: tauri::AppHandle, config: GenConfig) ->
 
     match result {
         Ok(res) => res,
-        Err(_) => Err("Thread panicked".to_string())
+        Err(_) => Err(OnePagerError::output("Thread panicked"))
     }
 }
 
@@ -36,9 +62,13 @@ fn scan_template_structure_cmd(path: String) -> Vec<String> {
 }
 
 #[tauri::command]
-fn validate_csv_cmd(path: String) -> bool {
-    // We consider it valid if we can read 1 record
-    csv_handler::read_csv_preview(path, 1).is_ok()
+fn validate_csv_cmd(path: String, schema_path: Option<String>, project_file: Option<String>) -> Result<csv_handler::CsvReport, OnePagerError> {
+    let schema = match schema_path.filter(|p| !p.is_empty()) {
+        Some(p) => Some(schema::Schema::load(p).map_err(OnePagerError::config)?),
+        None => None,
+    };
+    let cleaning = project::apply(GenConfig { project_file, ..Default::default() })?.cleaning;
+    Ok(csv_handler::validate_csv(path, schema.as_ref(), &cleaning))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
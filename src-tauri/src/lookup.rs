@@ -0,0 +1,107 @@
+use crate::csv_handler::{read_csv_with, CleaningConfig};
+use crate::error::OnePagerError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// An auxiliary CSV joined onto the main rows, e.g. the account manager
+/// directory. Its columns become `<namespace>.<Column>` tags.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LookupSource {
+    pub path: String,
+    /// Column of the main CSV holding the join value
+    pub key: String,
+    /// Column of the lookup CSV to match against, defaults to `key`
+    #[serde(default)]
+    pub lookup_key: Option<String>,
+    pub namespace: String,
+}
+
+impl LookupSource {
+    /// Rows of the lookup file by normalized key. The first row wins on duplicates.
+    fn load(
+        &self,
+        cleaning: &CleaningConfig,
+    ) -> Result<HashMap<String, HashMap<String, String>>, OnePagerError> {
+        let lookup_key = self.lookup_key.as_deref().unwrap_or(&self.key);
+        let rows = read_csv_with(&self.path, usize::MAX, cleaning)?;
+        let mut by_key = HashMap::new();
+        for row in rows {
+            let Some(value) = row.get(lookup_key) else {
+                return Err(OnePagerError::config(format!(
+                    "Lookup {}: column {} not found in {}",
+                    self.namespace, lookup_key, self.path
+                )));
+            };
+            by_key.entry(normalize_key(value)).or_insert(row);
+        }
+        Ok(by_key)
+    }
+}
+
+/// Keys match ignoring case and surrounding whitespace.
+pub(crate) fn normalize_key(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+/// Adds the namespaced lookup columns to every row, without overwriting
+/// existing columns. Returns the key values without a match, by namespace.
+pub fn join_lookups(
+    rows: &mut [HashMap<String, String>],
+    sources: &[LookupSource],
+    cleaning: &CleaningConfig,
+) -> Result<BTreeMap<String, Vec<String>>, OnePagerError> {
+    let mut unmatched = BTreeMap::new();
+    for source in sources {
+        let by_key = source.load(cleaning)?;
+        let mut missing = BTreeSet::new();
+        for row in rows.iter_mut() {
+            let key = row.get(&source.key).cloned().unwrap_or_default();
+            let Some(found) = by_key.get(&normalize_key(&key)) else {
+                missing.insert(key);
+                continue;
+            };
+            for (column, value) in found {
+                row.entry(format!("{}.{}", source.namespace, column))
+                    .or_insert_with(|| value.clone());
+            }
+        }
+        if !missing.is_empty() {
+            unmatched.insert(source.namespace.clone(), missing.into_iter().collect());
+        }
+    }
+    Ok(unmatched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_lookups() {
+        let path = std::env::temp_dir().join("onepager_lookup_test.csv");
+        std::fs::write(
+            &path,
+            "Manager;Email\nalice MARTIN;alice@partoo.co\nBob Stone;bob@partoo.co\n",
+        )
+        .unwrap();
+        let source = LookupSource {
+            path: path.to_string_lossy().to_string(),
+            key: "Account Manager".to_string(),
+            lookup_key: Some("Manager".to_string()),
+            namespace: "am".to_string(),
+        };
+
+        let mut rows: Vec<HashMap<String, String>> = ["Alice Martin ", "Carol", "Bob Stone"]
+            .iter()
+            .map(|am| [("Account Manager".to_string(), am.to_string())].into())
+            .collect();
+        let unmatched = join_lookups(&mut rows, &[source], &CleaningConfig::default()).unwrap();
+
+        assert_eq!(rows[0]["am.Email"], "alice@partoo.co");
+        assert_eq!(rows[0]["am.Manager"], "alice MARTIN");
+        assert!(!rows[1].contains_key("am.Email"));
+        assert_eq!(rows[2]["am.Email"], "bob@partoo.co");
+        assert_eq!(unmatched["am"], vec!["Carol"]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::error::OnePagerError;
+use crate::fit::TextFit;
+use crate::pptx_engine::GenStats;
+use crate::validate::PackageIssue;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// One planned deck of a run, successful or not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub template: String,
+    pub client: String,
+    pub org_id: String,
+    pub language: String,
+    /// Paths are relative to the run folder.
+    pub output: Option<String>,
+    pub pdf: Option<String>,
+    pub error: Option<OnePagerError>,
+    /// Tags rendered without a value
+    #[serde(default)]
+    pub missing: Vec<String>,
+    /// Structural problems found in the written deck
+    #[serde(default)]
+    pub issues: Vec<PackageIssue>,
+    /// Text boxes shrunk or cut to fit their values
+    #[serde(default)]
+    pub fitted: Vec<TextFit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub generated_at: String,
+    pub stats: GenStats,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl RunManifest {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn write(&self, run_dir: &Path) -> Result<(), String> {
+        fs::write(run_dir.join(MANIFEST_FILE), self.to_json()?).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,445 @@
+use crate::ooxml::{self, Package};
+use crate::slides::{self, PRESENTATION_PART};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const REL_SLIDE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide";
+const REL_SLIDE_MASTER: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/slideMaster";
+
+/// A deck assembled from several templates, in order.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Composition {
+    /// Name of the combined template, used like a template file name
+    pub name: String,
+    /// Template files relative to `template_dir`. `{lang}` expands to each
+    /// selected language, e.g. `{lang}/OnePager.pptx`.
+    pub templates: Vec<String>,
+    /// Leaves the source templates out of the generation, so that only the
+    /// combined deck is written for them. They are rendered on their own too
+    /// otherwise.
+    #[serde(default)]
+    pub replace_sources: bool,
+}
+
+impl Composition {
+    /// Virtual template path of each combined deck, with its source templates.
+    /// The virtual path sits in the language folder so the usual filters apply.
+    pub fn expand(&self, template_dir: &str, languages: &[String]) -> Vec<(PathBuf, Vec<PathBuf>)> {
+        let root = Path::new(template_dir);
+        let file_name = if self.name.to_lowercase().ends_with(".pptx") {
+            self.name.clone()
+        } else {
+            format!("{}.pptx", self.name)
+        };
+
+        if !self.templates.iter().any(|t| t.contains("{lang}")) {
+            let sources = self.templates.iter().map(|t| root.join(t)).collect();
+            return vec![(root.join(file_name), sources)];
+        }
+        languages
+            .iter()
+            .map(|lang| {
+                let sources = self
+                    .templates
+                    .iter()
+                    .map(|t| root.join(t.replace("{lang}", lang)))
+                    .collect();
+                (root.join(lang).join(&file_name), sources)
+            })
+            .collect()
+    }
+}
+
+/// Appends the slides of every other package to `base`, in order.
+pub fn merge_packages(mut base: Package, others: &[Package]) -> Package {
+    for source in others {
+        let mut merger = Merger {
+            target: &mut base,
+            source,
+            mapped: HashMap::new(),
+        };
+        for slide in slides::slide_order(source) {
+            merger.copy_slide(&slide);
+        }
+    }
+    base
+}
+
+struct Merger<'a> {
+    target: &'a mut Package,
+    source: &'a Package,
+    /// Source part name -> target part name
+    mapped: HashMap<String, String>,
+}
+
+impl Merger<'_> {
+    fn copy_slide(&mut self, slide: &str) {
+        let new_slide = self.copy_part(slide);
+
+        let target = ooxml::relative_target(PRESENTATION_PART, &new_slide);
+        let rel_id = self
+            .target
+            .add_relationship(PRESENTATION_PART, REL_SLIDE, &target);
+
+        let Some(presentation) = self.target.get_text(PRESENTATION_PART) else {
+            return;
+        };
+        let re_id = Regex::new(r#"<p:sldId\s[^>]*?id="(\d+)""#).unwrap();
+        let next_id = re_id
+            .captures_iter(&presentation)
+            .filter_map(|c| c[1].parse::<u32>().ok())
+            .max()
+            .unwrap_or(255)
+            + 1;
+        let sld_id = format!(r#"<p:sldId id="{}" r:id="{}"/>"#, next_id, rel_id);
+
+        let presentation = if let Some(pos) = presentation.find("</p:sldIdLst>") {
+            insert_at(&presentation, pos, &sld_id)
+        } else if presentation.contains("<p:sldIdLst/>") {
+            presentation.replacen(
+                "<p:sldIdLst/>",
+                &format!("<p:sldIdLst>{}</p:sldIdLst>", sld_id),
+                1,
+            )
+        } else if let Some(pos) = presentation.find("<p:sldSz") {
+            insert_at(
+                &presentation,
+                pos,
+                &format!("<p:sldIdLst>{}</p:sldIdLst>", sld_id),
+            )
+        } else {
+            presentation
+        };
+
+        // With sections, every slide must belong to one: use the last section
+        let presentation = match presentation.rfind("</p14:sldIdLst>") {
+            Some(pos) => insert_at(
+                &presentation,
+                pos,
+                &format!(r#"<p14:sldId id="{}"/>"#, next_id),
+            ),
+            None => presentation,
+        };
+        self.target
+            .set(PRESENTATION_PART, presentation.into_bytes());
+    }
+
+    /// Copies a part and everything it depends on, reusing identical layouts
+    /// and media already present in the target. Returns the target part name.
+    fn copy_part(&mut self, part: &str) -> String {
+        if let Some(mapped) = self.mapped.get(part) {
+            return mapped.clone();
+        }
+        if let Some(existing) = self.find_equivalent(part) {
+            self.mapped.insert(part.to_string(), existing.clone());
+            return existing;
+        }
+        if part.starts_with("ppt/slideLayouts/") {
+            // Layouts come with their master, copied as a whole
+            if let Some(master) = self.related(part, "/slideMaster").first() {
+                self.copy_master(master);
+                if let Some(mapped) = self.mapped.get(part) {
+                    return mapped.clone();
+                }
+            }
+        }
+
+        let new_name = unique_name(self.target, part);
+        self.mapped.insert(part.to_string(), new_name.clone());
+        let data = self.source.get(part).unwrap_or_default().to_vec();
+        self.target.set(&new_name, data);
+        self.copy_content_type(part, &new_name);
+        self.copy_relationships(part, &new_name);
+        new_name
+    }
+
+    fn copy_master(&mut self, master: &str) {
+        let new_master = unique_name(self.target, master);
+        self.mapped.insert(master.to_string(), new_master.clone());
+        let xml = self.source.get_text(master).unwrap_or_default();
+        self.target.set(&new_master, Vec::new());
+        self.copy_content_type(master, &new_master);
+
+        // Each layout needs its own mapping before the rels are rewritten
+        for layout in self.related(master, "/slideLayout") {
+            let new_layout = unique_name(self.target, &layout);
+            self.mapped.insert(layout.clone(), new_layout.clone());
+            let data = self.source.get(&layout).unwrap_or_default().to_vec();
+            self.target.set(&new_layout, data);
+            self.copy_content_type(&layout, &new_layout);
+        }
+        for layout in self.related(master, "/slideLayout") {
+            let new_layout = self.mapped[&layout].clone();
+            self.copy_relationships(&layout, &new_layout);
+        }
+        self.copy_relationships(master, &new_master);
+
+        // Master and layout ids share one space across the presentation
+        let mut next_id = self.max_master_id() + 1;
+        let master_id = next_id;
+        next_id += 1;
+        let re_layout_id = Regex::new(r#"(<p:sldLayoutId\s[^>]*?id=")(\d+)(")"#).unwrap();
+        let xml = re_layout_id.replace_all(&xml, |caps: &regex::Captures| {
+            next_id += 1;
+            format!("{}{}{}", &caps[1], next_id - 1, &caps[3])
+        });
+        self.target.set(&new_master, xml.into_owned().into_bytes());
+
+        let target = ooxml::relative_target(PRESENTATION_PART, &new_master);
+        let rel_id = self
+            .target
+            .add_relationship(PRESENTATION_PART, REL_SLIDE_MASTER, &target);
+        if let Some(presentation) = self.target.get_text(PRESENTATION_PART) {
+            if let Some(pos) = presentation.find("</p:sldMasterIdLst>") {
+                let entry = format!(r#"<p:sldMasterId id="{}" r:id="{}"/>"#, master_id, rel_id);
+                let presentation = insert_at(&presentation, pos, &entry);
+                self.target
+                    .set(PRESENTATION_PART, presentation.into_bytes());
+            }
+        }
+    }
+
+    fn copy_relationships(&mut self, part: &str, new_part: &str) {
+        let rels_path = ooxml::rels_path_for(part);
+        let Some(rels_xml) = self.source.get_text(&rels_path) else {
+            return;
+        };
+
+        let mut dropped = Vec::new();
+        let mut rewritten = HashMap::new();
+        for rel in ooxml::parse_relationships(&rels_xml) {
+            if rel.external {
+                continue;
+            }
+            let source_target = ooxml::resolve_target(part, &rel.target);
+            let new_target = if rel.rel_type.ends_with("/notesMaster") {
+                // Notes reuse the target notes master, or are dropped without one
+                self.target
+                    .names()
+                    .find(|n| n.starts_with("ppt/notesMasters/") && n.ends_with(".xml"))
+                    .map(|n| n.to_string())
+            } else if rel.rel_type.ends_with("/notesSlide") && !self.has_notes_master() {
+                None
+            } else if rel.rel_type.ends_with("/slide") && !self.mapped.contains_key(&source_target)
+            {
+                // Back-references to slides outside of the merge (e.g. jump links)
+                None
+            } else {
+                Some(self.copy_part(&source_target))
+            };
+            match new_target {
+                Some(t) => {
+                    rewritten.insert(rel.id, ooxml::relative_target(new_part, &t));
+                }
+                None => dropped.push(rel.id),
+            }
+        }
+
+        let re_rel = Regex::new(r"<Relationship\s[^>]*?/>").unwrap();
+        let re_target = Regex::new(r#"(\sTarget=")[^"]*(")"#).unwrap();
+        let re_id = Regex::new(r#"\sId="([^"]+)""#).unwrap();
+        let new_rels = re_rel.replace_all(&rels_xml, |caps: &regex::Captures| {
+            let element = &caps[0];
+            let id = re_id
+                .captures(element)
+                .map(|c| c[1].to_string())
+                .unwrap_or_default();
+            if dropped.contains(&id) {
+                return String::new();
+            }
+            match rewritten.get(&id) {
+                Some(target) => re_target
+                    .replace(element, |c: &regex::Captures| {
+                        format!("{}{}{}", &c[1], target, &c[2])
+                    })
+                    .into_owned(),
+                None => element.to_string(),
+            }
+        });
+        self.target.set(
+            &ooxml::rels_path_for(new_part),
+            new_rels.into_owned().into_bytes(),
+        );
+    }
+
+    fn copy_content_type(&mut self, part: &str, new_part: &str) {
+        let ext = part.rsplit_once('.').map(|(_, e)| e).unwrap_or_default();
+        let Some(content_type) = self.source.content_type(part) else {
+            return;
+        };
+        if self.source.default_content_type(ext).as_deref() == Some(content_type.as_str()) {
+            self.target.ensure_default(ext, &content_type);
+        } else {
+            self.target.add_override(new_part, &content_type);
+        }
+    }
+
+    /// A target part identical to `part`: same media bytes, or for layouts the
+    /// same layout and master XML.
+    fn find_equivalent(&self, part: &str) -> Option<String> {
+        let data = self.source.get(part)?;
+        let candidates = |dir: &str| -> Vec<String> {
+            self.target
+                .parts
+                .iter()
+                .filter(|p| p.name.starts_with(dir) && !p.name.ends_with(".rels") && p.data == data)
+                .map(|p| p.name.clone())
+                .collect()
+        };
+
+        if part.starts_with("ppt/media/") {
+            return candidates("ppt/media/").into_iter().next();
+        }
+        if part.starts_with("ppt/slideLayouts/") {
+            let master_data = self
+                .related(part, "/slideMaster")
+                .first()
+                .and_then(|m| self.source.get(m));
+            return candidates("ppt/slideLayouts/").into_iter().find(|layout| {
+                let target_master = self
+                    .target
+                    .relationships(layout)
+                    .into_iter()
+                    .find(|r| r.rel_type.ends_with("/slideMaster"))
+                    .map(|r| ooxml::resolve_target(layout, &r.target));
+                target_master.as_deref().and_then(|m| self.target.get(m)) == master_data
+            });
+        }
+        None
+    }
+
+    /// Source parts related to `part` through a relationship type ending with `suffix`.
+    fn related(&self, part: &str, suffix: &str) -> Vec<String> {
+        self.source
+            .relationships(part)
+            .into_iter()
+            .filter(|r| !r.external && r.rel_type.ends_with(suffix))
+            .map(|r| ooxml::resolve_target(part, &r.target))
+            .collect()
+    }
+
+    fn has_notes_master(&self) -> bool {
+        self.target
+            .names()
+            .any(|n| n.starts_with("ppt/notesMasters/") && n.ends_with(".xml"))
+    }
+
+    fn max_master_id(&self) -> u64 {
+        let re_id = Regex::new(r#"<p:(?:sldMasterId|sldLayoutId)\s[^>]*?id="(\d+)""#).unwrap();
+        self.target
+            .parts
+            .iter()
+            .filter(|p| p.name == PRESENTATION_PART || p.name.starts_with("ppt/slideMasters/"))
+            .flat_map(|p| {
+                let xml = String::from_utf8_lossy(&p.data).to_string();
+                re_id
+                    .captures_iter(&xml)
+                    .filter_map(|c| c[1].parse::<u64>().ok())
+                    .collect::<Vec<_>>()
+            })
+            .max()
+            .unwrap_or(2147483647)
+    }
+}
+
+fn insert_at(text: &str, pos: usize, insert: &str) -> String {
+    format!("{}{}{}", &text[..pos], insert, &text[pos..])
+}
+
+/// `ppt/slides/slide3.xml` -> first free `ppt/slides/slideN.xml` in the target.
+fn unique_name(target: &Package, part: &str) -> String {
+    let (dir, file) = part.rsplit_once('/').unwrap_or(("", part));
+    let (stem, ext) = file.rsplit_once('.').unwrap_or((file, ""));
+    let base = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    (1..)
+        .map(|n| format!("{}/{}{}.{}", dir, base, n, ext))
+        .find(|name| !target.contains(name))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ooxml::CONTENT_TYPES;
+
+    /// A one-slide deck with its own layout, master, theme and image.
+    fn deck(slide_text: &str, layout_xml: &str, image: &[u8]) -> Package {
+        let mut p = Package::default();
+        p.set(CONTENT_TYPES, br#"<Types><Default Extension="xml" ContentType="application/xml"/><Default Extension="png" ContentType="image/png"/><Override PartName="/ppt/presentation.xml" ContentType="pres"/><Override PartName="/ppt/slides/slide1.xml" ContentType="slide"/><Override PartName="/ppt/slideLayouts/slideLayout1.xml" ContentType="layout"/><Override PartName="/ppt/slideMasters/slideMaster1.xml" ContentType="master"/><Override PartName="/ppt/theme/theme1.xml" ContentType="theme"/></Types>"#.to_vec());
+        p.set(PRESENTATION_PART, br#"<p:presentation><p:sldMasterIdLst><p:sldMasterId id="2147483648" r:id="rId1"/></p:sldMasterIdLst><p:sldIdLst><p:sldId id="256" r:id="rId2"/></p:sldIdLst><p:sldSz cx="1" cy="1"/></p:presentation>"#.to_vec());
+        p.set("ppt/_rels/presentation.xml.rels", br#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slideMaster" Target="slideMasters/slideMaster1.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide" Target="slides/slide1.xml"/></Relationships>"#.to_vec());
+        p.set(
+            "ppt/slides/slide1.xml",
+            format!("<p:sld><a:t>{}</a:t></p:sld>", slide_text).into_bytes(),
+        );
+        p.set("ppt/slides/_rels/slide1.xml.rels", br#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slideLayout" Target="../slideLayouts/slideLayout1.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/image" Target="../media/image1.png"/><Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com" TargetMode="External"/></Relationships>"#.to_vec());
+        p.set("ppt/media/image1.png", image.to_vec());
+        p.set(
+            "ppt/slideLayouts/slideLayout1.xml",
+            layout_xml.as_bytes().to_vec(),
+        );
+        p.set("ppt/slideLayouts/_rels/slideLayout1.xml.rels", br#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slideMaster" Target="../slideMasters/slideMaster1.xml"/></Relationships>"#.to_vec());
+        p.set("ppt/slideMasters/slideMaster1.xml", br#"<p:sldMaster><p:sldLayoutIdLst><p:sldLayoutId id="2147483649" r:id="rId1"/></p:sldLayoutIdLst></p:sldMaster>"#.to_vec());
+        p.set("ppt/slideMasters/_rels/slideMaster1.xml.rels", br#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slideLayout" Target="../slideLayouts/slideLayout1.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/theme" Target="../theme/theme1.xml"/></Relationships>"#.to_vec());
+        p.set("ppt/theme/theme1.xml", b"<a:theme/>".to_vec());
+        p
+    }
+
+    #[test]
+    fn test_merge_dedups_identical_layouts_and_media() {
+        let merged = merge_packages(
+            deck("one", "<p:sldLayout/>", b"png"),
+            &[deck("two", "<p:sldLayout/>", b"png")],
+        );
+
+        assert_eq!(
+            slides::slide_order(&merged),
+            vec!["ppt/slides/slide1.xml", "ppt/slides/slide2.xml"]
+        );
+        assert_eq!(
+            merged.get_text("ppt/slides/slide2.xml").unwrap(),
+            "<p:sld><a:t>two</a:t></p:sld>"
+        );
+        assert!(!merged.contains("ppt/slideLayouts/slideLayout2.xml"));
+        assert!(!merged.contains("ppt/media/image2.png"));
+
+        let rels = merged.relationships("ppt/slides/slide2.xml");
+        assert_eq!(rels[0].target, "../slideLayouts/slideLayout1.xml");
+        assert_eq!(rels[1].target, "../media/image1.png");
+        assert!(rels[2].external);
+
+        let presentation = merged.get_text(PRESENTATION_PART).unwrap();
+        assert!(presentation.contains(r#"<p:sldId id="257" r:id="rId3"/>"#));
+        assert!(merged
+            .get_text(CONTENT_TYPES)
+            .unwrap()
+            .contains(r#"<Override PartName="/ppt/slides/slide2.xml" ContentType="slide"/>"#));
+    }
+
+    #[test]
+    fn test_merge_copies_distinct_master() {
+        let merged = merge_packages(
+            deck("one", "<p:sldLayout/>", b"png"),
+            &[deck("two", "<p:sldLayout type=\"title\"/>", b"other")],
+        );
+
+        assert!(merged.contains("ppt/slideLayouts/slideLayout2.xml"));
+        assert!(merged.contains("ppt/slideMasters/slideMaster2.xml"));
+        assert!(merged.contains("ppt/theme/theme2.xml"));
+        assert!(merged.contains("ppt/media/image2.png"));
+
+        let layout_rels = merged.relationships("ppt/slideLayouts/slideLayout2.xml");
+        assert_eq!(layout_rels[0].target, "../slideMasters/slideMaster2.xml");
+        let master = merged
+            .get_text("ppt/slideMasters/slideMaster2.xml")
+            .unwrap();
+        assert!(master.contains(r#"<p:sldLayoutId id="2147483651" r:id="rId1"/>"#));
+
+        let presentation = merged.get_text(PRESENTATION_PART).unwrap();
+        assert!(presentation.contains(r#"<p:sldMasterId id="2147483650" r:id="rId3"/>"#));
+        assert!(presentation.contains(r#"<p:sldId id="257" r:id="rId4"/>"#));
+    }
+}
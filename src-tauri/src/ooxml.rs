@@ -0,0 +1,733 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::CompressionMethod;
+
+pub const CONTENT_TYPES: &str = "[Content_Types].xml";
+
+#[derive(Debug, Clone)]
+pub struct PackagePart {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub compression: CompressionMethod,
+    pub unix_mode: Option<u32>,
+}
+
+/// An OOXML package (pptx, docx, xlsx) held in memory, parts kept in archive order.
+#[derive(Debug, Clone, Default)]
+pub struct Package {
+    pub parts: Vec<PackagePart>,
+}
+
+/// The formats filled by the tag engine, told apart by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Presentation,
+    Document,
+    Workbook,
+}
+
+impl DocumentKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "pptx" => Some(DocumentKind::Presentation),
+            "docx" => Some(DocumentKind::Document),
+            "xlsx" => Some(DocumentKind::Workbook),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            DocumentKind::Presentation => "pptx",
+            DocumentKind::Document => "docx",
+            DocumentKind::Workbook => "xlsx",
+        }
+    }
+
+    /// Whether tags are resolved in `part`: every XML part of a presentation,
+    /// the body, headers and footers of a document, the strings and cells of
+    /// a workbook, and the document properties of all.
+    pub fn holds_tags(self, part: &str) -> bool {
+        if part.starts_with("docProps/") {
+            return true;
+        }
+        let file = part.rsplit('/').next().unwrap_or(part);
+        match self {
+            DocumentKind::Presentation => true,
+            DocumentKind::Document => {
+                part.starts_with("word/")
+                    && !part[5..].contains('/')
+                    && (file == "document.xml"
+                        || file.starts_with("header")
+                        || file.starts_with("footer"))
+            }
+            DocumentKind::Workbook => {
+                part == "xl/sharedStrings.xml" || part.starts_with("xl/worksheets/sheet")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relationship {
+    pub id: String,
+    pub rel_type: String,
+    pub target: String,
+    pub external: bool,
+}
+
+/// Entries smaller than this are not checked for their compression ratio,
+/// small XML parts routinely compress very well.
+const RATIO_CHECK_MIN_SIZE: u64 = 1024 * 1024;
+
+/// Safeguards applied when reading a package.
+#[derive(Debug, Clone)]
+pub struct PackageLimits {
+    pub max_entries: usize,
+    /// Decompressed bytes, per entry and for the whole package
+    pub max_entry_size: u64,
+    pub max_total_size: u64,
+    /// Decompressed size over compressed size
+    pub max_ratio: u64,
+}
+
+impl Default for PackageLimits {
+    fn default() -> Self {
+        PackageLimits {
+            max_entries: 10_000,
+            max_entry_size: 256 * 1024 * 1024,
+            max_total_size: 1024 * 1024 * 1024,
+            max_ratio: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageError {
+    Io(String),
+    Zip(String),
+    TooManyEntries {
+        count: usize,
+        limit: usize,
+    },
+    /// Absolute, parent-relative or duplicate entry name
+    UnsafeEntryName(String),
+    EntryTooLarge {
+        name: String,
+        limit: u64,
+    },
+    PackageTooLarge {
+        limit: u64,
+    },
+    CompressionRatio {
+        name: String,
+        ratio: u64,
+    },
+    Encoding {
+        name: String,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for PackageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageError::Io(e) => write!(f, "{}", e),
+            PackageError::Zip(e) => write!(f, "Invalid archive: {}", e),
+            PackageError::TooManyEntries { count, limit } => {
+                write!(f, "Too many entries: {} (limit {})", count, limit)
+            }
+            PackageError::UnsafeEntryName(name) => write!(f, "Unsafe entry name: {}", name),
+            PackageError::EntryTooLarge { name, limit } => {
+                write!(f, "Entry {} exceeds {} bytes", name, limit)
+            }
+            PackageError::PackageTooLarge { limit } => {
+                write!(f, "Package exceeds {} bytes once decompressed", limit)
+            }
+            PackageError::CompressionRatio { name, ratio } => {
+                write!(
+                    f,
+                    "Entry {} has a suspicious compression ratio ({}:1)",
+                    name, ratio
+                )
+            }
+            PackageError::Encoding { name, message } => write!(f, "{}: {}", name, message),
+        }
+    }
+}
+
+impl std::error::Error for PackageError {}
+
+impl From<PackageError> for String {
+    fn from(e: PackageError) -> Self {
+        e.to_string()
+    }
+}
+
+impl Package {
+    pub fn open(path: &Path) -> Result<Self, PackageError> {
+        Self::open_with_limits(path, &PackageLimits::default())
+    }
+
+    /// Reads every entry, refusing unsafe names and oversized or suspiciously
+    /// compressed content. XML parts are normalized to UTF-8.
+    pub fn open_with_limits(path: &Path, limits: &PackageLimits) -> Result<Self, PackageError> {
+        let file = File::open(path).map_err(|e| PackageError::Io(e.to_string()))?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| PackageError::Zip(e.to_string()))?;
+        if zip.len() > limits.max_entries {
+            return Err(PackageError::TooManyEntries {
+                count: zip.len(),
+                limit: limits.max_entries,
+            });
+        }
+
+        let mut parts: Vec<PackagePart> = Vec::with_capacity(zip.len());
+        let mut total = 0u64;
+        for i in 0..zip.len() {
+            let file = zip
+                .by_index(i)
+                .map_err(|e| PackageError::Zip(e.to_string()))?;
+            let name = file.name().to_string();
+            if !is_safe_entry_name(&name) || parts.iter().any(|p| p.name == name) {
+                return Err(PackageError::UnsafeEntryName(name));
+            }
+            let compression = file.compression();
+            let unix_mode = file.unix_mode();
+            let compressed = file.compressed_size();
+
+            // Declared sizes can lie, read one byte past the limit to find out
+            let mut data = Vec::new();
+            file.take(limits.max_entry_size + 1)
+                .read_to_end(&mut data)
+                .map_err(|e| PackageError::Zip(format!("{}: {}", name, e)))?;
+            let size = data.len() as u64;
+            if size > limits.max_entry_size {
+                return Err(PackageError::EntryTooLarge {
+                    name,
+                    limit: limits.max_entry_size,
+                });
+            }
+            if size >= RATIO_CHECK_MIN_SIZE && size / compressed.max(1) > limits.max_ratio {
+                return Err(PackageError::CompressionRatio {
+                    name,
+                    ratio: size / compressed.max(1),
+                });
+            }
+            total += size;
+            if total > limits.max_total_size {
+                return Err(PackageError::PackageTooLarge {
+                    limit: limits.max_total_size,
+                });
+            }
+
+            if is_xml_part(&name) {
+                if let Some(utf8) = decode_xml(&data).map_err(|message| PackageError::Encoding {
+                    name: name.clone(),
+                    message,
+                })? {
+                    data = utf8;
+                }
+            }
+            parts.push(PackagePart {
+                name,
+                data,
+                compression,
+                unix_mode,
+            });
+        }
+        Ok(Package { parts })
+    }
+
+    pub fn write_to(&self, path: &Path) -> std::io::Result<()> {
+        let out_file = File::create(path)?;
+        let mut zip_out = zip::ZipWriter::new(out_file);
+
+        for part in &self.parts {
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(part.compression)
+                .unix_permissions(part.unix_mode.unwrap_or(0o644));
+            zip_out.start_file(part.name.as_str(), options)?;
+            zip_out.write_all(&part.data)?;
+        }
+
+        zip_out.finish()?;
+        Ok(())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().map(|p| p.name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.parts.iter().any(|p| p.name == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.parts
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.data.as_slice())
+    }
+
+    pub fn get_text(&self, name: &str) -> Option<String> {
+        self.get(name)
+            .map(|d| String::from_utf8_lossy(d).to_string())
+    }
+
+    /// Replaces the part content, or appends a new deflated part.
+    pub fn set(&mut self, name: &str, data: Vec<u8>) {
+        if let Some(part) = self.parts.iter_mut().find(|p| p.name == name) {
+            part.data = data;
+        } else {
+            self.parts.push(PackagePart {
+                name: name.to_string(),
+                data,
+                compression: CompressionMethod::Deflated,
+                unix_mode: None,
+            });
+        }
+    }
+
+    pub fn relationships(&self, part: &str) -> Vec<Relationship> {
+        self.get_text(&rels_path_for(part))
+            .map(|xml| parse_relationships(&xml))
+            .unwrap_or_default()
+    }
+
+    /// Declares a part in `[Content_Types].xml`.
+    pub fn add_override(&mut self, part: &str, content_type: &str) {
+        if let Some(ct) = self.get_text(CONTENT_TYPES) {
+            let override_xml = format!(
+                r#"<Override PartName="/{}" ContentType="{}"/>"#,
+                part, content_type
+            );
+            if let Some(pos) = ct.rfind("</Types>") {
+                let ct = format!("{}{}{}", &ct[..pos], override_xml, &ct[pos..]);
+                self.set(CONTENT_TYPES, ct.into_bytes());
+            }
+        }
+    }
+
+    /// Content type of a part, from its override or its extension default.
+    pub fn content_type(&self, part: &str) -> Option<String> {
+        let ct = self.get_text(CONTENT_TYPES)?;
+        let re_override = Regex::new(&format!(
+            r#"<Override\s[^>]*?PartName="/{}"[^>]*?ContentType="([^"]+)""#,
+            regex::escape(part)
+        ))
+        .unwrap();
+        if let Some(caps) = re_override.captures(&ct) {
+            return Some(caps[1].to_string());
+        }
+        let ext = part.rsplit_once('.')?.1;
+        self.default_content_type(ext)
+    }
+
+    pub fn default_content_type(&self, ext: &str) -> Option<String> {
+        let ct = self.get_text(CONTENT_TYPES)?;
+        let re_default = Regex::new(&format!(
+            r#"(?i)<Default\s[^>]*?Extension="{}"[^>]*?ContentType="([^"]+)""#,
+            regex::escape(ext)
+        ))
+        .unwrap();
+        re_default.captures(&ct).map(|caps| caps[1].to_string())
+    }
+
+    /// Declares an extension default in `[Content_Types].xml` if missing.
+    pub fn ensure_default(&mut self, ext: &str, content_type: &str) {
+        if self.default_content_type(ext).is_some() {
+            return;
+        }
+        if let Some(ct) = self.get_text(CONTENT_TYPES) {
+            let default_xml = format!(
+                r#"<Default Extension="{}" ContentType="{}"/>"#,
+                ext, content_type
+            );
+            // Defaults conventionally come first
+            let pos = ct.find("<Override").or_else(|| ct.rfind("</Types>"));
+            if let Some(pos) = pos {
+                let ct = format!("{}{}{}", &ct[..pos], default_xml, &ct[pos..]);
+                self.set(CONTENT_TYPES, ct.into_bytes());
+            }
+        }
+    }
+
+    /// Adds a relationship from `source_part` (`""` for the package root),
+    /// creating the `.rels` part if needed. Returns the new relationship id.
+    pub fn add_relationship(&mut self, source_part: &str, rel_type: &str, target: &str) -> String {
+        let rels_path = rels_path_for(source_part);
+        let rels = self.get_text(&rels_path).unwrap_or_else(|| {
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                "\r\n",
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"></Relationships>"#
+            )
+            .to_string()
+        });
+        let next_id = parse_relationships(&rels)
+            .iter()
+            .filter_map(|r| r.id.strip_prefix("rId").and_then(|n| n.parse::<u32>().ok()))
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let id = format!("rId{}", next_id);
+        let rel_xml = format!(
+            r#"<Relationship Id="{}" Type="{}" Target="{}"/>"#,
+            id, rel_type, target
+        );
+        if let Some(pos) = rels.rfind("</Relationships>") {
+            let rels = format!("{}{}{}", &rels[..pos], rel_xml, &rels[pos..]);
+            self.set(&rels_path, rels.into_bytes());
+        }
+        id
+    }
+
+    /// Drops the parts and their own `.rels`, then removes every relationship
+    /// and content type override still pointing at them.
+    pub fn remove_parts(&mut self, names: &HashSet<String>) {
+        if names.is_empty() {
+            return;
+        }
+        let rels_of_removed: HashSet<String> = names.iter().map(|n| rels_path_for(n)).collect();
+        self.parts
+            .retain(|p| !names.contains(&p.name) && !rels_of_removed.contains(&p.name));
+
+        let re_rel = Regex::new(r"<Relationship\s[^>]*?/>").unwrap();
+        for part in self.parts.iter_mut().filter(|p| p.name.ends_with(".rels")) {
+            let source = source_part_for(&part.name);
+            let xml = String::from_utf8_lossy(&part.data).to_string();
+            let pruned = re_rel.replace_all(&xml, |caps: &regex::Captures| {
+                let rel = parse_relationship(&caps[0]);
+                match rel {
+                    Some(r)
+                        if !r.external && names.contains(&resolve_target(&source, &r.target)) =>
+                    {
+                        String::new()
+                    }
+                    _ => caps[0].to_string(),
+                }
+            });
+            if pruned != xml {
+                part.data = pruned.into_owned().into_bytes();
+            }
+        }
+
+        if let Some(ct) = self.get_text(CONTENT_TYPES) {
+            let re_override =
+                Regex::new(r#"<Override\s[^>]*?PartName="/([^"]+)"[^>]*?/>"#).unwrap();
+            let pruned = re_override.replace_all(&ct, |caps: &regex::Captures| {
+                if names.contains(&caps[1]) {
+                    String::new()
+                } else {
+                    caps[0].to_string()
+                }
+            });
+            if pruned != ct {
+                self.set(CONTENT_TYPES, pruned.into_owned().into_bytes());
+            }
+        }
+    }
+}
+
+/// `ppt/slides/slide1.xml` -> `ppt/slides/_rels/slide1.xml.rels`,
+/// and `""` (the package itself) -> `_rels/.rels`
+pub fn rels_path_for(part: &str) -> String {
+    match part.rsplit_once('/') {
+        Some((dir, file)) => format!("{}/_rels/{}.rels", dir, file),
+        None => format!("_rels/{}.rels", part),
+    }
+}
+
+/// `ppt/slides/_rels/slide1.xml.rels` -> `ppt/slides/slide1.xml`
+pub fn source_part_for(rels_path: &str) -> String {
+    let (dir, file) = rels_path.rsplit_once('/').unwrap_or(("", rels_path));
+    let dir = dir.strip_suffix("_rels").unwrap_or(dir);
+    format!("{}{}", dir, file.strip_suffix(".rels").unwrap_or(file))
+}
+
+/// Resolves a relationship target against its source part into a package part name.
+pub fn resolve_target(source_part: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut segments: Vec<&str> = source_part.split('/').collect();
+    segments.pop();
+    for seg in target.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    segments.join("/")
+}
+
+/// Inverse of `resolve_target`: the relative path from `source_part` to `part`.
+pub fn relative_target(source_part: &str, part: &str) -> String {
+    let from: Vec<&str> = source_part.split('/').collect();
+    let from_dir = &from[..from.len().saturating_sub(1)];
+    let to: Vec<&str> = part.split('/').collect();
+
+    let common = from_dir.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut segments: Vec<&str> = vec![".."; from_dir.len() - common];
+    segments.extend(&to[common..]);
+    segments.join("/")
+}
+
+pub fn parse_relationships(xml: &str) -> Vec<Relationship> {
+    let re_rel = Regex::new(r"<Relationship\s[^>]*?/>").unwrap();
+    re_rel
+        .find_iter(xml)
+        .filter_map(|m| parse_relationship(m.as_str()))
+        .collect()
+}
+
+fn parse_relationship(element: &str) -> Option<Relationship> {
+    let re_attr = Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
+    let mut rel = Relationship {
+        id: String::new(),
+        rel_type: String::new(),
+        target: String::new(),
+        external: false,
+    };
+    for caps in re_attr.captures_iter(element) {
+        match &caps[1] {
+            "Id" => rel.id = caps[2].to_string(),
+            "Type" => rel.rel_type = caps[2].to_string(),
+            "Target" => rel.target = caps[2].to_string(),
+            "TargetMode" => rel.external = &caps[2] == "External",
+            _ => {}
+        }
+    }
+    if rel.id.is_empty() {
+        None
+    } else {
+        Some(rel)
+    }
+}
+
+/// Relative, forward-slash names without `..`, drive letters or control characters.
+fn is_safe_entry_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('/')
+        && !name.contains('\\')
+        && !name.contains(':')
+        && !name.chars().any(|c| c.is_control())
+        && name.split('/').all(|segment| segment != "..")
+}
+
+pub fn is_xml_part(name: &str) -> bool {
+    name.ends_with(".xml") || name.ends_with(".rels") || name.ends_with(".vml")
+}
+
+/// Turns an XML part into UTF-8 from its byte order mark or encoding
+/// declaration, rewriting the declaration. Returns `None` for UTF-8 parts.
+fn decode_xml(data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let utf16 = |bytes: &[u8], little_endian: bool| -> Result<String, String> {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| {
+                if little_endian {
+                    u16::from_le_bytes([c[0], c[1]])
+                } else {
+                    u16::from_be_bytes([c[0], c[1]])
+                }
+            })
+            .collect();
+        String::from_utf16(&units).map_err(|_| "Invalid UTF-16 content".to_string())
+    };
+
+    let text = if let Some(rest) = data.strip_prefix(&[0xFF, 0xFE]) {
+        utf16(rest, true)?
+    } else if let Some(rest) = data.strip_prefix(&[0xFE, 0xFF]) {
+        utf16(rest, false)?
+    } else if data.starts_with(&[b'<', 0]) {
+        utf16(data, true)?
+    } else if data.starts_with(&[0, b'<']) {
+        utf16(data, false)?
+    } else {
+        let head = String::from_utf8_lossy(&data[..data.len().min(200)]).to_lowercase();
+        let re_decl = Regex::new(r#"^(?:\u{feff})?<\?xml[^>]*encoding=["']([^"']+)["']"#).unwrap();
+        let encoding = re_decl
+            .captures(&head)
+            .map(|c| c[1].to_string())
+            .unwrap_or_else(|| "utf-8".to_string());
+        match encoding.as_str() {
+            "utf-8" | "utf8" => {
+                return match std::str::from_utf8(data) {
+                    Ok(_) => Ok(None),
+                    Err(e) => Err(format!("Invalid UTF-8 at byte {}", e.valid_up_to())),
+                }
+            }
+            "iso-8859-1" | "latin1" | "us-ascii" => data.iter().map(|&b| b as char).collect(),
+            other => return Err(format!("Unsupported encoding {}", other)),
+        }
+    };
+
+    let text = text.trim_start_matches('\u{feff}');
+    let re_encoding = Regex::new(r#"^(<\?xml[^>]*encoding=)["'][^"']*["']"#).unwrap();
+    Ok(Some(
+        re_encoding
+            .replace(text, r#"${1}"UTF-8""#)
+            .into_owned()
+            .into_bytes(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_kinds() {
+        let kind = DocumentKind::from_path(Path::new("FR/Letter.DOCX")).unwrap();
+        assert_eq!(kind, DocumentKind::Document);
+        assert!(kind.holds_tags("word/document.xml"));
+        assert!(kind.holds_tags("word/footer2.xml"));
+        assert!(!kind.holds_tags("word/styles.xml"));
+        assert!(!kind.holds_tags("word/_rels/header1.xml.rels"));
+        let kind = DocumentKind::from_path(Path::new("Summary.xlsx")).unwrap();
+        assert!(kind.holds_tags("xl/worksheets/sheet1.xml"));
+        assert!(!kind.holds_tags("xl/styles.xml"));
+        assert!(DocumentKind::from_path(Path::new("~$notes.txt")).is_none());
+    }
+
+    #[test]
+    fn test_rels_paths() {
+        assert_eq!(
+            rels_path_for("ppt/slides/slide1.xml"),
+            "ppt/slides/_rels/slide1.xml.rels"
+        );
+        assert_eq!(
+            source_part_for("ppt/slides/_rels/slide1.xml.rels"),
+            "ppt/slides/slide1.xml"
+        );
+        assert_eq!(source_part_for("_rels/.rels"), "");
+        assert_eq!(
+            resolve_target("ppt/slides/slide1.xml", "../notesSlides/notesSlide1.xml"),
+            "ppt/notesSlides/notesSlide1.xml"
+        );
+        assert_eq!(
+            resolve_target("", "ppt/presentation.xml"),
+            "ppt/presentation.xml"
+        );
+    }
+
+    #[test]
+    fn test_remove_parts_prunes_references() {
+        let mut package = Package::default();
+        package.set(
+            CONTENT_TYPES,
+            br#"<Types><Default Extension="xml" ContentType="application/xml"/><Override PartName="/ppt/slides/slide1.xml" ContentType="s"/><Override PartName="/ppt/notesSlides/notesSlide1.xml" ContentType="n"/></Types>"#.to_vec(),
+        );
+        package.set("ppt/slides/slide1.xml", b"<p:sld/>".to_vec());
+        package.set(
+            "ppt/slides/_rels/slide1.xml.rels",
+            br#"<Relationships><Relationship Id="rId1" Type="layout" Target="../slideLayouts/slideLayout1.xml"/><Relationship Id="rId2" Type="notes" Target="../notesSlides/notesSlide1.xml"/></Relationships>"#.to_vec(),
+        );
+        package.set("ppt/notesSlides/notesSlide1.xml", b"<p:notes/>".to_vec());
+        package.set(
+            "ppt/notesSlides/_rels/notesSlide1.xml.rels",
+            br#"<Relationships><Relationship Id="rId1" Type="slide" Target="../slides/slide1.xml"/></Relationships>"#.to_vec(),
+        );
+
+        let removed: HashSet<String> = ["ppt/notesSlides/notesSlide1.xml".to_string()].into();
+        package.remove_parts(&removed);
+
+        assert!(!package.contains("ppt/notesSlides/notesSlide1.xml"));
+        assert!(!package.contains("ppt/notesSlides/_rels/notesSlide1.xml.rels"));
+        let rels = package.relationships("ppt/slides/slide1.xml");
+        assert_eq!(rels.len(), 1);
+        assert_eq!(rels[0].id, "rId1");
+        let ct = package.get_text(CONTENT_TYPES).unwrap();
+        assert!(ct.contains("/ppt/slides/slide1.xml"));
+        assert!(!ct.contains("notesSlide1"));
+    }
+
+    fn write_zip(path: &Path, entries: &[(&str, Vec<u8>)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_open_rejects_unsafe_packages() {
+        let dir = std::env::temp_dir().join("one_pager_test_package_limits");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("t.pptx");
+
+        write_zip(&path, &[("../../evil.xml", b"<x/>".to_vec())]);
+        assert_eq!(
+            Package::open(&path).unwrap_err(),
+            PackageError::UnsafeEntryName("../../evil.xml".to_string())
+        );
+
+        // 4 MiB of zeros deflates to a few KiB
+        write_zip(&path, &[("ppt/media/bomb.bin", vec![0u8; 4 * 1024 * 1024])]);
+        assert!(matches!(
+            Package::open(&path).unwrap_err(),
+            PackageError::CompressionRatio { .. }
+        ));
+
+        write_zip(&path, &[("ppt/slides/slide1.xml", vec![b' '; 2048])]);
+        let limits = PackageLimits {
+            max_entry_size: 1024,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Package::open_with_limits(&path, &limits).unwrap_err(),
+            PackageError::EntryTooLarge { .. }
+        ));
+
+        write_zip(&path, &[("ppt/slides/slide1.xml", vec![b'<', 0xC3, b'>'])]);
+        assert!(matches!(
+            Package::open(&path).unwrap_err(),
+            PackageError::Encoding { .. }
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_normalizes_utf16_parts() {
+        let dir = std::env::temp_dir().join("one_pager_test_package_utf16");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("t.pptx");
+
+        let xml = r#"<?xml version="1.0" encoding="UTF-16"?><a:t>Café</a:t>"#;
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(xml.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        let latin1 = br#"<?xml version="1.0" encoding="ISO-8859-1"?><a:t>Caf"#
+            .iter()
+            .copied()
+            .chain([0xE9])
+            .chain(*b"</a:t>")
+            .collect();
+        write_zip(
+            &path,
+            &[
+                ("ppt/slides/slide1.xml", utf16),
+                ("ppt/slides/slide2.xml", latin1),
+            ],
+        );
+
+        let package = Package::open(&path).unwrap();
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?><a:t>Café</a:t>"#;
+        assert_eq!(package.get_text("ppt/slides/slide1.xml").unwrap(), expected);
+        assert_eq!(package.get_text("ppt/slides/slide2.xml").unwrap(), expected);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use crate::manifest::{ManifestEntry, RunManifest, MANIFEST_FILE};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+pub const ARCHIVES_DIR: &str = "archives";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveGrouping {
+    /// One archive per client folder (`<Client>_<OrgID>.zip`)
+    Client,
+    /// One archive per template language (`FR.zip`, `EN.zip`, ...)
+    Language,
+    /// A single archive named after the run folder
+    #[default]
+    Run,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PackagingConfig {
+    #[serde(default)]
+    pub group_by: ArchiveGrouping,
+}
+
+/// Zips the run outputs into `<run_dir>/archives/`, grouped as configured.
+/// Every archive embeds the manifest restricted to its own entries.
+pub fn build_archives(
+    run_dir: &Path,
+    manifest: &RunManifest,
+    config: &PackagingConfig,
+) -> Result<Vec<PathBuf>, String> {
+    let run_name = run_dir
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let mut groups: BTreeMap<String, Vec<&ManifestEntry>> = BTreeMap::new();
+    for entry in manifest.entries.iter().filter(|e| e.output.is_some()) {
+        let key = match config.group_by {
+            ArchiveGrouping::Client => client_folder(entry),
+            ArchiveGrouping::Language => entry.language.clone(),
+            ArchiveGrouping::Run => run_name.clone(),
+        };
+        groups.entry(key).or_default().push(entry);
+    }
+
+    let archive_dir = run_dir.join(ARCHIVES_DIR);
+    fs::create_dir_all(&archive_dir).map_err(|e| e.to_string())?;
+
+    let mut archives = Vec::new();
+    for (key, entries) in groups {
+        let path = archive_dir.join(format!("{}.zip", key));
+        let group_manifest = RunManifest {
+            entries: entries.iter().map(|e| (*e).clone()).collect(),
+            ..manifest.clone()
+        };
+        write_archive(&path, run_dir, &entries, &group_manifest)?;
+        archives.push(path);
+    }
+    Ok(archives)
+}
+
+fn client_folder(entry: &ManifestEntry) -> String {
+    entry
+        .output
+        .as_deref()
+        .and_then(|o| Path::new(o).parent())
+        .map(|p| p.to_string_lossy().to_string())
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| format!("{}_{}", entry.client, entry.org_id))
+}
+
+fn write_archive(
+    path: &Path,
+    run_dir: &Path,
+    entries: &[&ManifestEntry],
+    manifest: &RunManifest,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+
+    // Decks and PDFs are already compressed, no point deflating them again
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for entry in entries {
+        for rel in [&entry.output, &entry.pdf].into_iter().flatten() {
+            let mut src = File::open(run_dir.join(rel)).map_err(|e| e.to_string())?;
+            zip.start_file(rel.replace('\\', "/"), stored)
+                .map_err(|e| e.to_string())?;
+            std::io::copy(&mut src, &mut zip).map_err(|e| e.to_string())?;
+        }
+    }
+
+    zip.start_file(MANIFEST_FILE, deflated)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(manifest.to_json()?.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pptx_engine::GenStats;
+
+    fn entry(output: &str, language: &str) -> ManifestEntry {
+        ManifestEntry {
+            template: "t.pptx".to_string(),
+            client: "ACME".to_string(),
+            org_id: "1".to_string(),
+            language: language.to_string(),
+            output: Some(output.to_string()),
+            pdf: None,
+            error: None,
+            missing: Vec::new(),
+            issues: Vec::new(),
+            fitted: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_archives_grouped_by_language() {
+        let run_dir = std::env::temp_dir().join("one_pager_test_packaging");
+        let _ = fs::remove_dir_all(&run_dir);
+        fs::create_dir_all(run_dir.join("ACME_1")).unwrap();
+        fs::write(run_dir.join("ACME_1/fr.pptx"), b"fr").unwrap();
+        fs::write(run_dir.join("ACME_1/en.pptx"), b"en").unwrap();
+
+        let manifest = RunManifest {
+            generated_at: "now".to_string(),
+            stats: GenStats::default(),
+            entries: vec![entry("ACME_1/fr.pptx", "FR"), entry("ACME_1/en.pptx", "EN")],
+        };
+        let config = PackagingConfig {
+            group_by: ArchiveGrouping::Language,
+        };
+        let archives = build_archives(&run_dir, &manifest, &config).unwrap();
+        assert_eq!(archives.len(), 2);
+
+        let fr = File::open(run_dir.join("archives/FR.zip")).unwrap();
+        let mut zip = zip::ZipArchive::new(fr).unwrap();
+        let mut names: Vec<String> = zip.file_names().map(|n| n.to_string()).collect();
+        names.sort();
+        assert_eq!(names, vec!["ACME_1/fr.pptx", MANIFEST_FILE]);
+
+        let mut json = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name(MANIFEST_FILE).unwrap(), &mut json).unwrap();
+        let parsed: RunManifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.entries.len(), 1);
+
+        fs::remove_dir_all(&run_dir).unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PdfExportConfig {
+    /// Converter executable. Defaults to `soffice` (LibreOffice) from the PATH.
+    #[serde(default = "default_command")]
+    pub command: String,
+    /// Max number of converter processes running at the same time.
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Per-file timeout, the converter process is killed when exceeded.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_command() -> String {
+    "soffice".to_string()
+}
+
+fn default_concurrency() -> usize {
+    2
+}
+
+fn default_timeout_secs() -> u64 {
+    120
+}
+
+impl Default for PdfExportConfig {
+    fn default() -> Self {
+        PdfExportConfig {
+            command: default_command(),
+            concurrency: default_concurrency(),
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfFailure {
+    pub source: String,
+    pub error: String,
+}
+
+/// Converts every deck to a PDF written next to it.
+/// Returns the produced PDFs and the failed conversions.
+pub fn convert_all(
+    decks: &[PathBuf],
+    config: &PdfExportConfig,
+    update_progress: impl Fn(usize, usize) + Sync + Send,
+) -> Result<(Vec<PathBuf>, Vec<PdfFailure>), String> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.concurrency.max(1))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let counter = std::sync::atomic::AtomicUsize::new(0);
+    let results: Vec<Result<PathBuf, PdfFailure>> = pool.install(|| {
+        decks
+            .par_iter()
+            .map(|deck| {
+                let res = convert_one(deck, config).map_err(|error| PdfFailure {
+                    source: deck.to_string_lossy().to_string(),
+                    error,
+                });
+                let c = counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                update_progress(c, decks.len());
+                res
+            })
+            .collect()
+    });
+
+    let mut pdfs = Vec::new();
+    let mut failures = Vec::new();
+    for res in results {
+        match res {
+            Ok(p) => pdfs.push(p),
+            Err(f) => failures.push(f),
+        }
+    }
+    Ok((pdfs, failures))
+}
+
+/// Runs `<command> --headless --convert-to pdf --outdir <dir> <deck>`,
+/// the calling convention of `soffice`.
+pub fn convert_one(deck: &Path, config: &PdfExportConfig) -> Result<PathBuf, String> {
+    let out_dir = deck
+        .parent()
+        .ok_or_else(|| format!("No parent folder for {:?}", deck))?;
+    let expected = deck.with_extension("pdf");
+
+    // Each soffice instance needs its own profile, otherwise concurrent
+    // conversions collide on the user installation lock.
+    let profile_dir = std::env::temp_dir().join(format!(
+        "one_pager_soffice_{}_{}",
+        std::process::id(),
+        deck.file_stem().unwrap_or_default().to_string_lossy()
+    ));
+    let profile_url = format!(
+        "-env:UserInstallation=file:///{}",
+        profile_dir
+            .to_string_lossy()
+            .replace('\\', "/")
+            .trim_start_matches('/')
+    );
+
+    let mut child = Command::new(&config.command)
+        .arg(profile_url)
+        .arg("--headless")
+        .arg("--convert-to")
+        .arg("pdf")
+        .arg("--outdir")
+        .arg(out_dir)
+        .arg(deck)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start '{}': {}", config.command, e))?;
+
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break status,
+            None if start.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                let _ = std::fs::remove_dir_all(&profile_dir);
+                return Err(format!("Timed out after {}s", config.timeout_secs));
+            }
+            None => std::thread::sleep(Duration::from_millis(50)),
+        }
+    };
+    let _ = std::fs::remove_dir_all(&profile_dir);
+
+    if !status.success() {
+        return Err(format!("Converter exited with {}", status));
+    }
+    if !expected.exists() {
+        return Err(format!("Converter did not produce {:?}", expected));
+    }
+    Ok(expected)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_stub(dir: &Path, body: &str) -> String {
+        let path = dir.join("stub_converter.sh");
+        fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_convert_with_stub() {
+        let dir = std::env::temp_dir().join("one_pager_test_pdf_ok");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let deck = dir.join("deck.pptx");
+        fs::write(&deck, b"pptx").unwrap();
+
+        // Last two args are "--outdir <dir>" then the deck
+        let stub = write_stub(
+            &dir,
+            r#"for last; do :; done; out="${last%.pptx}.pdf"; echo pdf > "$out""#,
+        );
+        let config = PdfExportConfig {
+            command: stub,
+            ..Default::default()
+        };
+
+        let (pdfs, failures) =
+            convert_all(std::slice::from_ref(&deck), &config, |_, _| {}).unwrap();
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(pdfs, vec![dir.join("deck.pdf")]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_convert_failures_are_reported() {
+        let dir = std::env::temp_dir().join("one_pager_test_pdf_fail");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let deck = dir.join("deck.pptx");
+        fs::write(&deck, b"pptx").unwrap();
+
+        let slow = PdfExportConfig {
+            command: write_stub(&dir, "sleep 5"),
+            concurrency: 1,
+            timeout_secs: 0,
+        };
+        let (pdfs, failures) = convert_all(std::slice::from_ref(&deck), &slow, |_, _| {}).unwrap();
+        assert!(pdfs.is_empty());
+        assert!(failures[0].error.contains("Timed out"));
+
+        let missing = PdfExportConfig {
+            command: write_stub(&dir, "exit 0"),
+            ..Default::default()
+        };
+        let (_, failures) = convert_all(&[deck], &missing, |_, _| {}).unwrap();
+        assert!(failures[0].error.contains("did not produce"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,38 @@
-use crate::csv_handler::read_csv_all;
+use crate::csv_handler::{read_csv_with, CleaningConfig};
+use crate::doc_props::{self, DocPropsConfig};
+use crate::docx;
+use crate::error::{OnePagerError, TagError};
+use crate::expr;
+use crate::fit::{self, FontBook, TextFit, TextFitConfig};
+use crate::hyperlinks;
+use crate::lookup::{self, LookupSource};
+use crate::manifest::{ManifestEntry, RunManifest};
+use crate::merge::{self, Composition};
+use crate::ooxml::{DocumentKind, Package};
+use crate::packaging::{self, PackagingConfig};
+use crate::pdf_export::{self, PdfExportConfig, PdfFailure};
+use crate::project;
+use crate::richtext;
+use crate::sanitize;
+use crate::shapes::{self, ShapeRule};
+use crate::slides::{self, SlideRule};
+use crate::styles::{self, StyleRule};
+use crate::summary::{self, SummaryConfig};
+use crate::table::{self, Details, TableBinding};
+use crate::tags;
+use crate::translations::{self, Translations};
+use crate::validate::{self, PackageIssue, ValidationFailure};
+use crate::xlsx;
 use rayon::prelude::*;
 use regex::Regex;
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct GenConfig {
     pub standard_csv: Option<String>,
     pub prev_year_csv: Option<String>,
@@ -17,42 +40,299 @@ pub struct GenConfig {
     pub output_dir: String,
     pub languages: Vec<String>,
     pub mappings: HashMap<String, String>,
+    #[serde(default)]
+    pub pdf_export: Option<PdfExportConfig>,
+    #[serde(default)]
+    pub packaging: Option<PackagingConfig>,
+    #[serde(default)]
+    pub part_scope: PartScope,
+    #[serde(default)]
+    pub strip_notes: bool,
+    #[serde(default)]
+    pub strip_comments: bool,
+    #[serde(default)]
+    pub doc_props: Option<DocPropsConfig>,
+    #[serde(default)]
+    pub slide_rules: Vec<SlideRule>,
+    #[serde(default)]
+    pub compositions: Vec<Composition>,
+    #[serde(default)]
+    pub summary: Option<SummaryConfig>,
+    #[serde(default)]
+    pub cleaning: CleaningConfig,
+    /// Text for empty tags by template language (`FR`, `EN`, ... or `ALL`)
+    #[serde(default)]
+    pub fallback_values: BTreeMap<String, String>,
+    /// Auxiliary CSVs joined onto the rows, available as `<<namespace.Column>>`
+    #[serde(default)]
+    pub lookups: Vec<LookupSource>,
+    /// Folder of `<lang>.json` dictionaries for `<<t:key>>` tags. Root templates
+    /// holding such tags are then rendered once per selected language that
+    /// has a dictionary.
+    #[serde(default)]
+    pub translations_dir: Option<String>,
+    /// JSON file holding the settings the window has no field for
+    #[serde(default)]
+    pub project_file: Option<String>,
+    /// Run styles of tag values, e.g. negative deltas in red
+    #[serde(default)]
+    pub style_rules: Vec<StyleRule>,
+    /// Shrinks or cuts values overflowing their text box
+    #[serde(default)]
+    pub text_fit: Option<TextFitConfig>,
+    /// Hides, deletes or refills shapes picked by name or alt text
+    #[serde(default)]
+    pub shape_rules: Vec<ShapeRule>,
+    /// Native tables filled from detail rows or column groups
+    #[serde(default)]
+    pub table_bindings: Vec<TableBinding>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Which package parts get their tags resolved. Everything is on by default.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PartScope {
+    pub slides: bool,
+    pub layouts: bool,
+    pub masters: bool,
+    /// Speaker notes, notes master and comments
+    pub notes: bool,
+    pub charts: bool,
+    pub doc_props: bool,
+}
+
+impl Default for PartScope {
+    fn default() -> Self {
+        PartScope {
+            slides: true,
+            layouts: true,
+            masters: true,
+            notes: true,
+            charts: true,
+            doc_props: true,
+        }
+    }
+}
+
+impl PartScope {
+    fn includes(&self, kind: PartKind) -> bool {
+        match kind {
+            PartKind::Slide => self.slides,
+            PartKind::Layout => self.layouts,
+            PartKind::Master => self.masters,
+            PartKind::Notes | PartKind::Comments => self.notes,
+            PartKind::Chart => self.charts,
+            PartKind::DocProps => self.doc_props,
+            PartKind::Other => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PartKind {
+    Slide,
+    Layout,
+    Master,
+    Notes,
+    Comments,
+    Chart,
+    DocProps,
+    Other,
+}
+
+impl PartKind {
+    fn of(name: &str) -> PartKind {
+        if name.starts_with("ppt/slides/") {
+            PartKind::Slide
+        } else if name.starts_with("ppt/slideLayouts/") {
+            PartKind::Layout
+        } else if name.starts_with("ppt/slideMasters/") {
+            PartKind::Master
+        } else if name.starts_with("ppt/notesSlides/") || name.starts_with("ppt/notesMasters/") {
+            PartKind::Notes
+        } else if name.starts_with("ppt/comments/")
+            || name == "ppt/commentAuthors.xml"
+            || name == "ppt/authors.xml"
+        {
+            PartKind::Comments
+        } else if name.starts_with("ppt/charts/") {
+            PartKind::Chart
+        } else if name.starts_with("docProps/") {
+            PartKind::DocProps
+        } else {
+            PartKind::Other
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GenStats {
     pub total_files: u32,
     pub total_time_secs: f64,
     pub success_count: u32,
     pub error_count: u32,
+    #[serde(default)]
+    pub pdf_count: u32,
+    #[serde(default)]
+    pub pdf_failures: Vec<PdfFailure>,
+    #[serde(default)]
+    pub archives: Vec<String>,
+    #[serde(default)]
+    pub summary_files: Vec<String>,
+    /// Empty tag occurrences by tag name
+    #[serde(default)]
+    pub missing_values: BTreeMap<String, u32>,
+    /// Join values without a lookup row, by namespace
+    #[serde(default)]
+    pub unmatched_lookup_keys: BTreeMap<String, Vec<String>>,
+    /// Written decks with structural problems
+    #[serde(default)]
+    pub validation_failures: Vec<ValidationFailure>,
+    /// Text boxes adjusted to fit their values
+    #[serde(default)]
+    pub fitted_shapes: u32,
+    /// Tagged text boxes not checked for overflow as their size is unknown
+    #[serde(default)]
+    pub unchecked_shapes: u32,
+    /// Combined decks not built, with the reason
+    #[serde(default)]
+    pub skipped_compositions: Vec<String>,
+    /// Decks that could not be written
+    #[serde(default)]
+    pub deck_errors: Vec<DeckError>,
+}
+
+/// A planned deck that failed, with the error as sent to the frontend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeckError {
+    pub template: String,
+    pub org_id: String,
+    pub language: String,
+    pub error: OnePagerError,
+}
+
+/// Loaded once per run and used by every deck.
+#[derive(Clone, Copy, Default)]
+struct Shared<'a> {
+    translations: Option<&'a Translations>,
+    fonts: Option<&'a FontBook>,
+    details: Option<&'a Details>,
+}
+
+/// A written deck and what happened while filling it.
+#[derive(Debug, Default)]
+struct Rendered {
+    path: PathBuf,
+    /// Tags that had no value
+    missing: Vec<String>,
+    fitted: Vec<TextFit>,
+    /// Structural problems of the written file
+    issues: Vec<PackageIssue>,
 }
 
 pub fn generate_pptx(
     config: GenConfig,
     update_progress: impl Fn(f64, String) + Sync + Send,
-) -> Result<GenStats, String> {
+) -> Result<GenStats, OnePagerError> {
     let start_time = std::time::Instant::now();
+    let config = project::apply(config)?;
     // 1. Scan Templates
-    let templates = scan_templates(&config.template_dir, &config.languages)?;
+    let mut templates = scan_templates(&config.template_dir, &config.languages)?;
+
+    // The index deck template is not a client template
+    let summary_template = config
+        .summary
+        .as_ref()
+        .and_then(|s| s.template.as_ref())
+        .map(|t| Path::new(&config.template_dir).join(t));
+    if let Some(index_template) = &summary_template {
+        templates.retain(|t| t != index_template);
+    }
+
+    // Combined decks, skipped for a language that lacks one of the sources
+    let mut composed: HashMap<PathBuf, Package> = HashMap::new();
+    let mut skipped_compositions = Vec::new();
+    for composition in &config.compositions {
+        for (virtual_path, sources) in composition.expand(&config.template_dir, &config.languages) {
+            let packages: Result<Vec<Package>, OnePagerError> = sources
+                .iter()
+                .map(|source| Package::open(source).map_err(|e| OnePagerError::package(source, e)))
+                .collect();
+            let mut packages = match packages {
+                Ok(packages) if !packages.is_empty() => packages,
+                Ok(_) => continue,
+                Err(e) => {
+                    let name = virtual_path
+                        .strip_prefix(&config.template_dir)
+                        .unwrap_or(&virtual_path);
+                    skipped_compositions.push(format!("{}: {}", name.display(), e));
+                    continue;
+                }
+            };
+            let base = packages.remove(0);
+            if composition.replace_sources {
+                templates.retain(|t| !sources.contains(t));
+            }
+            templates.push(virtual_path.clone());
+            composed.insert(virtual_path, merge::merge_packages(base, &packages));
+        }
+    }
+
     if templates.is_empty() {
-        return Err("No templates found for selected languages".to_string());
+        return Err(OnePagerError::config(
+            "No templates found for selected languages",
+        ));
     }
 
+    let translations = config
+        .translations_dir
+        .as_ref()
+        .map(Translations::load)
+        .transpose()?;
+    let fonts = config
+        .text_fit
+        .as_ref()
+        .map(|fit| FontBook::load(&fit.font_dirs));
+    let details = Details::load(&config.table_bindings, &config.cleaning)?;
+
     // 2. Load CSVs
     let mut data_groups = HashMap::new();
     if let Some(path) = &config.standard_csv {
-        let rows = read_csv_all(path)?;
+        let rows = read_csv_with(path, usize::MAX, &config.cleaning)?;
         data_groups.insert("standard", rows);
     }
     if let Some(path) = &config.prev_year_csv {
-        let rows = read_csv_all(path)?;
+        let rows = read_csv_with(path, usize::MAX, &config.cleaning)?;
         data_groups.insert("previous_year", rows);
     }
 
     if data_groups.is_empty() {
-        return Err("No CSV files loaded".to_string());
+        return Err(OnePagerError::config("No CSV files loaded"));
     }
 
+    let mut unmatched_lookup_keys: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for rows in data_groups.values_mut() {
+        let unmatched = lookup::join_lookups(rows, &config.lookups, &config.cleaning)?;
+        for (namespace, keys) in unmatched {
+            let known = unmatched_lookup_keys.entry(namespace).or_default();
+            for key in keys {
+                if !known.contains(&key) {
+                    known.push(key);
+                }
+            }
+        }
+    }
+
+    // Root templates without translation tags are rendered once as usual
+    let translated_templates: HashSet<&PathBuf> = templates
+        .iter()
+        .filter(|t| translations.is_some() && t.parent() == Some(Path::new(&config.template_dir)))
+        .filter(|t| match composed.get(*t) {
+            Some(package) => translations::holds_tags(package),
+            None => Package::open(t).is_ok_and(|p| translations::holds_tags(&p)),
+        })
+        .collect();
+
     // 3. Plan Operations
     let mut tasks = Vec::new();
 
@@ -100,6 +380,28 @@ pub fn generate_pptx(
                 let params = LangParams {
                     template_lang_folder: parent.to_string(),
                 };
+                let in_lang_folder = config
+                    .languages
+                    .iter()
+                    .any(|l| l.eq_ignore_ascii_case(&params.template_lang_folder));
+
+                // A translated root template gives one deck per language
+                let translated = !in_lang_folder && translated_templates.contains(template_path);
+                if let Some(translations) = translations.as_ref().filter(|_| translated) {
+                    for lang in &config.languages {
+                        let lang = lang.to_uppercase();
+                        if !translations.has_language(&lang) {
+                            continue;
+                        }
+                        if let Some(row_langs) = row.get("Language") {
+                            if !check_lang_match(row_langs, &lang) {
+                                continue;
+                            }
+                        }
+                        tasks.push((template_path.clone(), row.clone(), lang));
+                    }
+                    continue;
+                }
 
                 if let Some(row_langs) = row.get("Language") {
                     if !check_lang_match(row_langs, &params.template_lang_folder) {
@@ -111,41 +413,65 @@ pub fn generate_pptx(
                     // If column exists but empty -> cleaned to "".
                     // If column absent, we proceed.
                     // We need to know if column exists. In our Map, we can't distinguish "absent" from "empty" if we used clean_values on everything?
-                    // Actually read_csv_all preserves headers.
+                    // Actually read_csv_with preserves headers.
                     // For now, if "Language" key is present and not empty, check it.
                 }
 
-                tasks.push((template_path.clone(), row.clone()));
+                // Root templates are shared by every language
+                let lang = if in_lang_folder {
+                    params.template_lang_folder.to_uppercase()
+                } else {
+                    "ALL".to_string()
+                };
+
+                tasks.push((template_path.clone(), row.clone(), lang));
             }
         }
     }
 
     let total_tasks = tasks.len();
     if total_tasks == 0 {
-        return Err("No tasks generated (check Filters)".to_string());
+        return Err(OnePagerError::config("No tasks generated (check Filters)"));
     }
 
     // Create Timestamped Root Output Directory
     let now = std::time::SystemTime::now();
     let dt: chrono::DateTime<chrono::Local> = now.into();
     let timestamp_folder = format!("OnePagerGeneratedAt_{}", dt.format("%Y-%m-%d_%H-%M-%S"));
+    let generated_at = dt.to_rfc3339();
     let root_output_path = Path::new(&config.output_dir).join(timestamp_folder);
-    fs::create_dir_all(&root_output_path)
-        .map_err(|e| format!("Failed to create output dir: {}", e))?;
+    fs::create_dir_all(&root_output_path).map_err(|e| OnePagerError::io(&root_output_path, &e))?;
 
     // 4. Execute Tasks (Parallel)
+    let shared = Shared {
+        translations: translations.as_ref(),
+        fonts: fonts.as_ref(),
+        details: Some(&details),
+    };
     // We use a counter for progress
     let counter = std::sync::atomic::AtomicUsize::new(0);
 
-    let results: Vec<Result<(), String>> = tasks
+    let rendered: Vec<_> = tasks
         .par_iter()
-        .map(|(tmpl, row)| {
-            let res = process_single_pptx(
-                tmpl,
-                row,
-                root_output_path.to_str().unwrap(),
-                &config.mappings,
-            );
+        .map(|(tmpl, row, lang)| {
+            let output_dir = root_output_path.to_str().unwrap();
+            let res = match composed.get(tmpl) {
+                Some(package) => render_pptx(
+                    tmpl,
+                    package.clone(),
+                    row,
+                    lang,
+                    output_dir,
+                    &config,
+                    shared,
+                ),
+                None => process_single_pptx(tmpl, row, lang, output_dir, &config, shared),
+            };
+            // Catch decks PowerPoint would offer to repair
+            let res = res.map(|mut rendered| {
+                rendered.issues = validate::validate_file(&rendered.path);
+                rendered
+            });
             let c = counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
 
             // Update progress every 5 items or last one
@@ -159,16 +485,180 @@ pub fn generate_pptx(
         })
         .collect();
 
+    let mut missing_values: BTreeMap<String, u32> = BTreeMap::new();
+    let mut validation_failures = Vec::new();
+    let mut fitted_shapes = 0;
+    let mut unchecked_shapes = 0;
+    let mut details_by_task = Vec::with_capacity(rendered.len());
+    let mut results: Vec<Result<PathBuf, OnePagerError>> = Vec::with_capacity(rendered.len());
+    for res in rendered {
+        match res {
+            Ok(rendered) => {
+                for name in &rendered.missing {
+                    *missing_values.entry(name.clone()).or_default() += 1;
+                }
+                if !rendered.issues.is_empty() {
+                    validation_failures.push(ValidationFailure {
+                        output: rendered.path.to_string_lossy().to_string(),
+                        issues: rendered.issues.clone(),
+                    });
+                }
+                let unchecked = rendered.fitted.iter().filter(|f| f.skipped).count() as u32;
+                unchecked_shapes += unchecked;
+                fitted_shapes += rendered.fitted.len() as u32 - unchecked;
+                results.push(Ok(rendered.path.clone()));
+                details_by_task.push(rendered);
+            }
+            Err(e) => {
+                details_by_task.push(Rendered::default());
+                results.push(Err(e));
+            }
+        }
+    }
+
     let success_count = results.iter().filter(|r| r.is_ok()).count() as u32;
     let error_count = results.iter().filter(|r| r.is_err()).count() as u32;
-    let total_time_secs = start_time.elapsed().as_secs_f64();
 
-    Ok(GenStats {
+    // 5. Optional PDF Conversion
+    let mut pdfs = Vec::new();
+    let mut pdf_failures = Vec::new();
+    if let Some(pdf_config) = &config.pdf_export {
+        let decks: Vec<PathBuf> = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok().cloned())
+            .collect();
+        let (converted, failures) = pdf_export::convert_all(&decks, pdf_config, |c, total| {
+            update_progress(
+                (c as f64 / total as f64) * 100.0,
+                format!("Converted to PDF {}/{}", c, total),
+            );
+        })
+        .map_err(OnePagerError::output)?;
+        pdfs = converted;
+        pdf_failures = failures;
+    }
+
+    let deck_errors = tasks
+        .iter()
+        .zip(&results)
+        .filter_map(|((tmpl, row, lang), res)| {
+            let error = res.as_ref().err()?;
+            Some(DeckError {
+                template: tmpl.to_string_lossy().to_string(),
+                org_id: row.get("Org ID").cloned().unwrap_or_default(),
+                language: lang.clone(),
+                error: error.clone(),
+            })
+        })
+        .collect();
+
+    let mut stats = GenStats {
         total_files: total_tasks as u32,
-        total_time_secs,
+        total_time_secs: 0.0,
         success_count,
         error_count,
-    })
+        pdf_count: pdfs.len() as u32,
+        pdf_failures,
+        archives: Vec::new(),
+        summary_files: Vec::new(),
+        missing_values,
+        unmatched_lookup_keys,
+        validation_failures,
+        fitted_shapes,
+        unchecked_shapes,
+        skipped_compositions,
+        deck_errors,
+    };
+
+    // 6. Run Manifest
+    let relative = |p: &Path| {
+        p.strip_prefix(&root_output_path)
+            .unwrap_or(p)
+            .to_string_lossy()
+            .to_string()
+    };
+    let entries = tasks
+        .iter()
+        .zip(&results)
+        .zip(details_by_task)
+        .map(|(((tmpl, row, lang), res), rendered)| {
+            let pdf = res
+                .as_ref()
+                .ok()
+                .map(|p| p.with_extension("pdf"))
+                .filter(|p| pdfs.contains(p));
+            ManifestEntry {
+                template: tmpl.to_string_lossy().to_string(),
+                client: row.get("Nom du client").cloned().unwrap_or_default(),
+                org_id: row.get("Org ID").cloned().unwrap_or_default(),
+                language: lang.clone(),
+                output: res.as_ref().ok().map(|p| relative(p)),
+                pdf: pdf.map(|p| relative(&p)),
+                error: res.as_ref().err().cloned(),
+                missing: rendered.missing,
+                issues: rendered.issues,
+                fitted: rendered.fitted,
+            }
+        })
+        .collect();
+    stats.total_time_secs = start_time.elapsed().as_secs_f64();
+    let manifest = RunManifest {
+        generated_at,
+        stats: stats.clone(),
+        entries,
+    };
+    manifest
+        .write(&root_output_path)
+        .map_err(OnePagerError::output)?;
+
+    // 7. Optional Run Summary
+    if let Some(summary_config) = &config.summary {
+        update_progress(100.0, "Writing run summary".to_string());
+        let rows: Vec<&HashMap<String, String>> = tasks.iter().map(|(_, row, _)| row).collect();
+        let lines = summary::summary_rows(summary_config, &manifest.entries, &rows);
+        let summary_path = summary::write_summary(&root_output_path, summary_config, &lines)
+            .map_err(OnePagerError::output)?;
+        stats
+            .summary_files
+            .push(summary_path.to_string_lossy().to_string());
+
+        if let Some(index_template) = &summary_template {
+            let run_values: HashMap<String, String> = [
+                (
+                    "Generated At".to_string(),
+                    dt.format("%d/%m/%Y %H:%M").to_string(),
+                ),
+                ("Deck Count".to_string(), success_count.to_string()),
+            ]
+            .into();
+            let index_path = root_output_path.join("summary_index.pptx");
+            summary::render_index(
+                index_template,
+                &index_path,
+                &lines,
+                &run_values,
+                |xml, values| replace_tags(xml, values, &HashMap::new()),
+            )
+            .map_err(OnePagerError::output)?;
+            stats
+                .summary_files
+                .push(index_path.to_string_lossy().to_string());
+        }
+    }
+
+    // 8. Optional Packaging
+    if let Some(packaging_config) = &config.packaging {
+        update_progress(100.0, "Packaging archives".to_string());
+        let archives = packaging::build_archives(&root_output_path, &manifest, packaging_config)
+            .map_err(OnePagerError::output)?;
+        stats.archives = archives
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+    }
+
+    stats.total_time_secs = start_time.elapsed().as_secs_f64();
+    Ok(stats)
 }
 
 struct LangParams {
@@ -190,19 +680,553 @@ fn check_lang_match(row_val: &str, folder_lang: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Write;
+
     #[test]
     fn test_lang_match_basic() {
         assert!(check_lang_match("French", "FR"));
     }
+
+    fn write_template(path: &Path, parts: &[(&str, &str)]) {
+        let file = fs::File::create(path).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        for (name, content) in parts {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn sample_row() -> HashMap<String, String> {
+        let mut row = HashMap::new();
+        row.insert("Nom du client".to_string(), "ACME".to_string());
+        row.insert("Org ID".to_string(), "42".to_string());
+        row.insert("JJ/MM/AAAA".to_string(), "01/01/2026".to_string());
+        row
+    }
+
+    #[test]
+    fn test_part_scope_and_strip_notes() {
+        let dir = std::env::temp_dir().join("one_pager_test_part_scope");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let template = dir.join("template.pptx");
+        write_template(
+            &template,
+            &[
+                (
+                    "[Content_Types].xml",
+                    r#"<Types><Override PartName="/ppt/notesSlides/notesSlide1.xml" ContentType="n"/><Override PartName="/ppt/comments/comment1.xml" ContentType="c"/></Types>"#,
+                ),
+                (
+                    "ppt/slides/slide1.xml",
+                    "<a:t>&lt;&lt;Nom du client&gt;&gt;</a:t>",
+                ),
+                (
+                    "ppt/slides/_rels/slide1.xml.rels",
+                    r#"<Relationships><Relationship Id="rId2" Type="notesSlide" Target="../notesSlides/notesSlide1.xml"/><Relationship Id="rId3" Type="comments" Target="../comments/comment1.xml"/></Relationships>"#,
+                ),
+                (
+                    "ppt/notesSlides/notesSlide1.xml",
+                    "<a:t>&lt;&lt;Nom du client&gt;&gt;</a:t>",
+                ),
+                (
+                    "ppt/comments/comment1.xml",
+                    "<p:text>check &lt;&lt;Org ID&gt;&gt;</p:text>",
+                ),
+                (
+                    "docProps/core.xml",
+                    "<dc:title>&lt;&lt;Nom du client&gt;&gt;</dc:title>",
+                ),
+            ],
+        );
+
+        // Notes and docProps stay untouched
+        let config = GenConfig {
+            part_scope: PartScope {
+                notes: false,
+                doc_props: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let out = process_single_pptx(
+            &template,
+            &sample_row(),
+            "ALL",
+            dir.to_str().unwrap(),
+            &config,
+            Shared::default(),
+        )
+        .unwrap()
+        .path;
+        let package = Package::open(&out).unwrap();
+        assert_eq!(
+            package.get_text("ppt/slides/slide1.xml").unwrap(),
+            "<a:t>ACME</a:t>"
+        );
+        assert!(package
+            .get_text("ppt/notesSlides/notesSlide1.xml")
+            .unwrap()
+            .contains("&lt;&lt;Nom du client"));
+        assert!(package
+            .get_text("ppt/comments/comment1.xml")
+            .unwrap()
+            .contains("&lt;&lt;Org ID"));
+        assert!(package
+            .get_text("docProps/core.xml")
+            .unwrap()
+            .contains("&lt;&lt;Nom du client"));
+
+        // Notes and comments removed along with their references
+        let config = GenConfig {
+            strip_notes: true,
+            strip_comments: true,
+            ..Default::default()
+        };
+        let out = process_single_pptx(
+            &template,
+            &sample_row(),
+            "ALL",
+            dir.to_str().unwrap(),
+            &config,
+            Shared::default(),
+        )
+        .unwrap()
+        .path;
+        let package = Package::open(&out).unwrap();
+        assert!(!package.contains("ppt/notesSlides/notesSlide1.xml"));
+        assert!(!package.contains("ppt/comments/comment1.xml"));
+        assert!(package.relationships("ppt/slides/slide1.xml").is_empty());
+        assert_eq!(
+            package.get_text("[Content_Types].xml").unwrap(),
+            "<Types></Types>"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_doc_props_patterns() {
+        let dir = std::env::temp_dir().join("one_pager_test_doc_props_patterns");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let template = dir.join("template.pptx");
+        write_template(
+            &template,
+            &[
+                ("ppt/slides/slide1.xml", "<a:t>Slide</a:t>"),
+                (
+                    "docProps/core.xml",
+                    "<cp:coreProperties><dc:title>Template</dc:title></cp:coreProperties>",
+                ),
+            ],
+        );
+
+        let config = GenConfig {
+            doc_props: Some(DocPropsConfig {
+                title: Some("<<Nom du client>> & co: <<Rating|default:N/A>>".to_string()),
+                subject: Some("<<= Reviews * 2>> <<Notes|markdown>>".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut row = sample_row();
+        row.insert("Rating".to_string(), String::new());
+        row.insert("Notes".to_string(), "**Top**".to_string());
+        row.insert("Reviews".to_string(), "21".to_string());
+        let rendered = process_single_pptx(
+            &template,
+            &row,
+            "ALL",
+            dir.to_str().unwrap(),
+            &config,
+            Shared::default(),
+        )
+        .unwrap();
+        let core = Package::open(&rendered.path)
+            .unwrap()
+            .get_text("docProps/core.xml")
+            .unwrap();
+        assert!(core.contains("<dc:title>ACME &amp; co: N/A</dc:title>"));
+        assert!(core.contains("<dc:subject>42 Top</dc:subject>"));
+        assert_eq!(rendered.missing, vec!["Rating"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_hyperlink_targets_and_tooltips() {
+        let dir = std::env::temp_dir().join("one_pager_test_hyperlinks");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let template = dir.join("template.pptx");
+        write_template(
+            &template,
+            &[
+                (
+                    "ppt/slides/slide1.xml",
+                    r#"<a:r><a:rPr><a:hlinkClick r:id="rId2" tooltip="Open &lt;&lt;Nom du client&gt;&gt; dashboard"/></a:rPr><a:t>View</a:t></a:r>"#,
+                ),
+                (
+                    "ppt/slides/_rels/slide1.xml.rels",
+                    r#"<Relationships><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com/dash?org=%3C%3COrg%20ID%3E%3E" TargetMode="External"/></Relationships>"#,
+                ),
+            ],
+        );
+
+        let mut row = sample_row();
+        row.insert("Nom du client".to_string(), "Café \"Bar\"".to_string());
+        row.insert("Org ID".to_string(), "7&8".to_string());
+        let out = process_single_pptx(
+            &template,
+            &row,
+            "ALL",
+            dir.to_str().unwrap(),
+            &GenConfig::default(),
+            Shared::default(),
+        )
+        .unwrap()
+        .path;
+        let package = Package::open(&out).unwrap();
+
+        let slide = package.get_text("ppt/slides/slide1.xml").unwrap();
+        assert!(slide.contains(r#"tooltip="Open Café &quot;Bar&quot; dashboard""#));
+        let rels = package
+            .get_text("ppt/slides/_rels/slide1.xml.rels")
+            .unwrap();
+        assert!(rels.contains(r#"Target="https://example.com/dash?org=7%268""#));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_table_values_not_read_as_tags() {
+        let dir = std::env::temp_dir().join("one_pager_test_table_values");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let template = dir.join("template.pptx");
+        let cell = |text: &str| {
+            format!(
+                "<a:tc><a:txBody><a:bodyPr/><a:p><a:r><a:t>{}</a:t></a:r></a:p></a:txBody></a:tc>",
+                text
+            )
+        };
+        let slide = format!(
+            r#"<p:graphicFrame><p:nvGraphicFramePr><p:cNvPr id="4" name="KPI"/></p:nvGraphicFramePr><p:xfrm><a:ext cx="100" cy="20"/></p:xfrm><a:graphic><a:graphicData><a:tbl><a:tr h="10">{}</a:tr><a:tr h="10">{}</a:tr></a:tbl></a:graphicData></a:graphic></p:graphicFrame>"#,
+            cell("&lt;&lt;Nom du client&gt;&gt;"),
+            cell("x")
+        );
+        write_template(
+            &template,
+            &[
+                (
+                    slides::PRESENTATION_PART,
+                    r#"<p:sldIdLst><p:sldId id="256" r:id="rId2"/></p:sldIdLst>"#,
+                ),
+                (
+                    "ppt/_rels/presentation.xml.rels",
+                    r#"<Relationships><Relationship Id="rId2" Type="slide" Target="slides/slide1.xml"/></Relationships>"#,
+                ),
+                ("ppt/slides/slide1.xml", &slide),
+            ],
+        );
+
+        let mut row = sample_row();
+        row.insert("Q1_Note".to_string(), "<<Org ID>>".to_string());
+        let config = GenConfig {
+            table_bindings: vec![serde_json::from_str(
+                r#"{"shape": "KPI", "source": "columns", "groups": ["Q1_*"], "columns": ["Note"]}"#,
+            )
+            .unwrap()],
+            ..Default::default()
+        };
+        let out = process_single_pptx(
+            &template,
+            &row,
+            "ALL",
+            dir.to_str().unwrap(),
+            &config,
+            Shared::default(),
+        )
+        .unwrap()
+        .path;
+        let slide = Package::open(&out)
+            .unwrap()
+            .get_text("ppt/slides/slide1.xml")
+            .unwrap();
+        assert!(slide.contains("<a:t>ACME</a:t>"));
+        assert!(slide.contains("<a:t>&lt;&lt;Org ID&gt;&gt;</a:t>"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_docx_and_xlsx_templates() {
+        let dir = std::env::temp_dir().join("one_pager_test_documents");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let letter = dir.join("Letter.docx");
+        write_template(
+            &letter,
+            &[
+                (
+                    "word/document.xml",
+                    "<w:p><w:r><w:t>Dear &lt;&lt;Nom du </w:t></w:r><w:r><w:t>client&gt;&gt;</w:t></w:r></w:p><w:p><w:r><w:t>&lt;&lt;Notes|markdown&gt;&gt;</w:t></w:r></w:p>",
+                ),
+                ("word/styles.xml", "<w:style>&lt;&lt;Org ID&gt;&gt;</w:style>"),
+                ("word/footer1.xml", "<w:t>&lt;&lt;Org ID&gt;&gt;</w:t>"),
+            ],
+        );
+        let summary = dir.join("Summary.xlsx");
+        write_template(
+            &summary,
+            &[
+                (
+                    "xl/sharedStrings.xml",
+                    "<sst><si><t>&lt;&lt;Nom du client&gt;&gt;</t></si><si><t>&lt;&lt;Org ID&gt;&gt;</t></si><si><t>&lt;&lt;Notes|markdown&gt;&gt;</t></si></sst>",
+                ),
+                (
+                    "xl/worksheets/sheet1.xml",
+                    r#"<row><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c></row>"#,
+                ),
+            ],
+        );
+
+        let mut row = sample_row();
+        row.insert("Notes".to_string(), "**Top** rated\n- Fast".to_string());
+        let render = |template: &Path| {
+            process_single_pptx(
+                template,
+                &row,
+                "ALL",
+                dir.to_str().unwrap(),
+                &GenConfig::default(),
+                Shared::default(),
+            )
+            .unwrap()
+            .path
+        };
+        let out = render(&letter);
+        assert!(out.to_string_lossy().ends_with("_previous_quarter.docx"));
+        let package = Package::open(&out).unwrap();
+        assert!(package
+            .get_text("word/document.xml")
+            .unwrap()
+            .contains("Dear ACME</w:t>"));
+        assert!(package.get_text("word/document.xml").unwrap().contains(concat!(
+            r#"<w:p><w:r><w:rPr><w:b/></w:rPr><w:t xml:space="preserve">Top</w:t></w:r><w:r><w:t xml:space="preserve"> rated</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:ind w:left="360" w:hanging="360"/></w:pPr><w:r><w:t>•</w:t><w:tab/></w:r><w:r><w:t xml:space="preserve">Fast</w:t></w:r></w:p>"#
+        )));
+        assert_eq!(
+            package.get_text("word/footer1.xml").unwrap(),
+            "<w:t>42</w:t>"
+        );
+        assert!(package
+            .get_text("word/styles.xml")
+            .unwrap()
+            .contains("&lt;&lt;"));
+
+        let package = Package::open(&render(&summary)).unwrap();
+        assert_eq!(
+            package.get_text("xl/worksheets/sheet1.xml").unwrap(),
+            r#"<row><c r="A1" t="s"><v>0</v></c><c r="B1"><v>42</v></c></row>"#
+        );
+        assert!(package
+            .get_text("xl/sharedStrings.xml")
+            .unwrap()
+            .starts_with("<sst><si><t>ACME</t></si>"));
+        assert!(package
+            .get_text("xl/sharedStrings.xml")
+            .unwrap()
+            .contains(r#"<si><r><rPr><b/></rPr><t xml:space="preserve">Top</t></r>"#));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_root_template_rendered_per_language() {
+        let dir = std::env::temp_dir().join("one_pager_test_translations");
+        let _ = fs::remove_dir_all(&dir);
+        let templates = dir.join("templates");
+        let dictionaries = dir.join("i18n");
+        fs::create_dir_all(&templates).unwrap();
+        fs::create_dir_all(&dictionaries).unwrap();
+        write_template(
+            &templates.join("OnePager.pptx"),
+            &[(
+                "ppt/slides/slide1.xml",
+                "<a:t>&lt;&lt;t:title&gt;&gt;</a:t>",
+            )],
+        );
+        write_template(
+            &templates.join("Plain.pptx"),
+            &[(
+                "ppt/slides/slide1.xml",
+                "<a:t>&lt;&lt;Nom du client&gt;&gt;</a:t>",
+            )],
+        );
+        fs::write(
+            dictionaries.join("fr.json"),
+            r#"{"title": "Bilan de <<Nom du client>>"}"#,
+        )
+        .unwrap();
+        fs::write(dictionaries.join("en.json"), r#"{"title": "Review"}"#).unwrap();
+        let csv = dir.join("data.csv");
+        fs::write(
+            &csv,
+            "Org ID;Nom du client;JJ/MM/AAAA\n42;ACME;01/01/2026\n",
+        )
+        .unwrap();
+
+        let config = GenConfig {
+            standard_csv: Some(csv.to_string_lossy().to_string()),
+            template_dir: templates.to_string_lossy().to_string(),
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            languages: vec!["fr".to_string(), "en".to_string(), "de".to_string()],
+            translations_dir: Some(dictionaries.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let stats = generate_pptx(config, |_, _| {}).unwrap();
+        assert_eq!(stats.success_count, 3);
+
+        let decks: Vec<PathBuf> = WalkDir::new(dir.join("out"))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.into_path())
+            .filter(|p| p.extension().is_some_and(|e| e == "pptx"))
+            .collect();
+        let fr = decks
+            .iter()
+            .find(|p| p.to_string_lossy().ends_with("_previous_quarter_FR.pptx"))
+            .unwrap();
+        assert_eq!(
+            Package::open(fr)
+                .unwrap()
+                .get_text("ppt/slides/slide1.xml")
+                .unwrap(),
+            "<a:t>Bilan de ACME</a:t>"
+        );
+        assert!(decks
+            .iter()
+            .any(|p| p.to_string_lossy().ends_with("_previous_quarter_EN.pptx")));
+        // Without translation tags, once under the usual name
+        assert!(decks
+            .iter()
+            .any(|p| p.to_string_lossy().ends_with("_previous_quarter.pptx")));
+        assert_eq!(decks.len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_composition_skipped_without_source() {
+        let dir = std::env::temp_dir().join("one_pager_test_compositions");
+        let _ = fs::remove_dir_all(&dir);
+        let templates = dir.join("templates");
+        fs::create_dir_all(templates.join("FR")).unwrap();
+        fs::create_dir_all(templates.join("EN")).unwrap();
+        for path in ["FR/OnePager.pptx", "FR/Extra.pptx", "EN/OnePager.pptx"] {
+            write_template(
+                &templates.join(path),
+                &[(
+                    "ppt/slides/slide1.xml",
+                    "<a:t>&lt;&lt;Nom du client&gt;&gt;</a:t>",
+                )],
+            );
+        }
+        let csv = dir.join("data.csv");
+        fs::write(
+            &csv,
+            "Org ID;Nom du client;JJ/MM/AAAA\n42;ACME;01/01/2026\n",
+        )
+        .unwrap();
+
+        let config = GenConfig {
+            standard_csv: Some(csv.to_string_lossy().to_string()),
+            template_dir: templates.to_string_lossy().to_string(),
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            languages: vec!["FR".to_string(), "EN".to_string()],
+            compositions: vec![Composition {
+                name: "Combined".to_string(),
+                templates: vec![
+                    "{lang}/OnePager.pptx".to_string(),
+                    "{lang}/Extra.pptx".to_string(),
+                ],
+                replace_sources: true,
+            }],
+            ..Default::default()
+        };
+        let stats = generate_pptx(config, |_, _| {}).unwrap();
+        // The French combined deck, and the English template on its own
+        assert_eq!(stats.success_count, 2);
+        assert_eq!(stats.skipped_compositions.len(), 1);
+        assert!(stats.skipped_compositions[0].starts_with(&format!(
+            "{}: ",
+            Path::new("EN").join("Combined.pptx").display()
+        )));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_deck_errors_reported() {
+        let dir = std::env::temp_dir().join("one_pager_test_deck_errors");
+        let _ = fs::remove_dir_all(&dir);
+        let templates = dir.join("templates");
+        fs::create_dir_all(&templates).unwrap();
+        write_template(
+            &templates.join("OnePager.pptx"),
+            &[(
+                "ppt/slides/slide1.xml",
+                "<a:t>&lt;&lt;= 1 / 0&gt;&gt;</a:t>",
+            )],
+        );
+        let csv = dir.join("data.csv");
+        fs::write(
+            &csv,
+            "Org ID;Nom du client;JJ/MM/AAAA\n42;ACME;01/01/2026\n",
+        )
+        .unwrap();
+
+        let config = GenConfig {
+            standard_csv: Some(csv.to_string_lossy().to_string()),
+            template_dir: templates.to_string_lossy().to_string(),
+            output_dir: dir.join("out").to_string_lossy().to_string(),
+            languages: vec!["FR".to_string()],
+            ..Default::default()
+        };
+        let stats = generate_pptx(config, |_, _| {}).unwrap();
+        assert_eq!(stats.error_count, 1);
+        assert_eq!(stats.deck_errors.len(), 1);
+        assert_eq!(stats.deck_errors[0].org_id, "42");
+        assert!(matches!(
+            &stats.deck_errors[0].error,
+            OnePagerError::Mapping { message, .. } if message.starts_with("Division by zero")
+        ));
+
+        // The manifest keeps the error with its code
+        let manifest = WalkDir::new(dir.join("out"))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .find(|e| e.file_name() == crate::manifest::MANIFEST_FILE)
+            .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(manifest.path()).unwrap()).unwrap();
+        assert_eq!(json["entries"][0]["error"]["code"], "mapping");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
 
-fn scan_templates(dir: &str, languages: &[String]) -> Result<Vec<PathBuf>, String> {
+fn scan_templates(dir: &str, languages: &[String]) -> Result<Vec<PathBuf>, OnePagerError> {
     let mut files = Vec::new();
     let root = Path::new(dir);
 
     for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
         let path = entry.path();
-        if path.extension().map(|e| e == "pptx").unwrap_or(false) {
+        if DocumentKind::from_path(path).is_some() {
             if path
                 .file_name()
                 .unwrap()
@@ -243,11 +1267,11 @@ pub fn get_available_languages(dir: &str) -> Vec<String> {
     for lang in &known {
         let path = Path::new(dir).join(lang);
         if path.exists() && path.is_dir() {
-            // Check if it contains at least one pptx
+            // Check if it contains at least one template
             if let Ok(entries) = fs::read_dir(&path) {
                 if entries
                     .filter_map(|e| e.ok())
-                    .any(|e| e.path().extension().map(|x| x == "pptx").unwrap_or(false))
+                    .any(|e| DocumentKind::from_path(&e.path()).is_some())
                 {
                     found.push(lang.to_string());
                 }
@@ -267,9 +1291,34 @@ pub fn get_available_languages(dir: &str) -> Vec<String> {
 fn process_single_pptx(
     template_path: &Path,
     row: &HashMap<String, String>,
+    lang: &str,
     output_dir: &str,
-    mappings: &HashMap<String, String>,
-) -> Result<(), String> {
+    config: &GenConfig,
+    shared: Shared<'_>,
+) -> Result<Rendered, OnePagerError> {
+    let package =
+        Package::open(template_path).map_err(|e| OnePagerError::package(template_path, e))?;
+    render_pptx(
+        template_path,
+        package,
+        row,
+        lang,
+        output_dir,
+        config,
+        shared,
+    )
+}
+
+/// `<output_dir>/<Client>_<OrgID>/<date>_<OrgID>_<Client>_<suffix>.<ext>`,
+/// with `_<LANG>` appended for translated root templates and the extension
+/// of the template.
+/// The client folder is created on the way.
+fn output_path_for(
+    template_path: &Path,
+    row: &HashMap<String, String>,
+    output_dir: &str,
+    lang_suffix: Option<&str>,
+) -> PathBuf {
     // Prepare Output Path
     let client = row
         .get("Nom du client")
@@ -300,121 +1349,344 @@ fn process_single_pptx(
         "previous_quarter"
     };
 
-    let fname = format!("{}_{}_{}_{}.pptx", date_str, org_id, client_clean, suffix);
-    let output_path = target_folder.join(fname);
-
-    let file = File::open(template_path).map_err(|e| e.to_string())?;
-    let mut zip = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut fname = format!("{}_{}_{}_{}", date_str, org_id, client_clean, suffix);
+    if let Some(lang) = lang_suffix {
+        fname = format!("{}_{}", fname, lang);
+    }
+    let ext = DocumentKind::from_path(template_path).map_or("pptx", |k| k.extension());
+    let fname = format!("{}.{}", fname, ext);
+    target_folder.join(fname)
+}
 
-    let out_file = File::create(output_path).map_err(|e| e.to_string())?;
-    let mut zip_out = zip::ZipWriter::new(out_file);
+/// Fills an already loaded template for one row and writes it to the client folder.
+/// Returns the deck path and the names of the tags that had no value.
+fn render_pptx(
+    template_path: &Path,
+    mut package: Package,
+    row: &HashMap<String, String>,
+    lang: &str,
+    output_dir: &str,
+    config: &GenConfig,
+    shared: Shared<'_>,
+) -> Result<Rendered, OnePagerError> {
+    // Root templates rendered per language need distinct file names
+    let Shared {
+        translations,
+        fonts,
+        details,
+    } = shared;
+    let localized = translations.is_some()
+        && lang != "ALL"
+        && template_path.parent() == Some(Path::new(&config.template_dir));
+    let output_path = output_path_for(template_path, row, output_dir, localized.then_some(lang));
+
+    // Drop excluded slides first, their content is never resolved
+    let template_name = template_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let slide_rules: Vec<&SlideRule> = config
+        .slide_rules
+        .iter()
+        .filter(|r| {
+            r.template
+                .as_ref()
+                .is_none_or(|t| template_name.contains(t.as_str()))
+        })
+        .collect();
+    slides::apply_slide_rules(&mut package, &slide_rules, |name| {
+        lookup_tag(name, row, &config.mappings).cloned()
+    });
+    let shape_rules: Vec<&ShapeRule> = config
+        .shape_rules
+        .iter()
+        .filter(|r| {
+            r.template
+                .as_ref()
+                .is_none_or(|t| template_name.contains(t.as_str()))
+        })
+        .collect();
+    shapes::apply_shape_rules(
+        &mut package,
+        &shape_rules,
+        Path::new(&config.template_dir),
+        |name| lookup_tag(name, row, &config.mappings).cloned(),
+    )?;
+
+    // Strip annotations before resolving tags, no point filling removed parts
+    let mut stripped = HashSet::new();
+    for name in package.names() {
+        let kind = PartKind::of(name);
+        if (config.strip_notes && kind == PartKind::Notes && !name.starts_with("ppt/notesMasters/"))
+            || (config.strip_comments && kind == PartKind::Comments)
+        {
+            stripped.insert(name.to_string());
+        }
+    }
+    package.remove_parts(&stripped);
+
+    // Workbook cells holding just a numeric value are written as numbers
+    let kind = DocumentKind::from_path(template_path).unwrap_or(DocumentKind::Presentation);
+    if kind == DocumentKind::Workbook {
+        xlsx::numeric_cells(&mut package, |name| {
+            let value = lookup_tag(name, row, &config.mappings)?;
+            xlsx::as_number(value, config.cleaning.rules_for(name))
+        });
+    }
 
-    for i in 0..zip.len() {
-        let mut file = zip.by_index(i).map_err(|e| e.to_string())?;
-        let name = file.name().to_string();
-        let options = zip::write::SimpleFileOptions::default()
-            .compression_method(file.compression())
-            .unix_permissions(file.unix_mode().unwrap_or(0o644));
-
-        let mut content = Vec::new();
-        file.read_to_end(&mut content).map_err(|e| e.to_string())?;
-
-        if name.ends_with(".xml") {
-            let mut text = String::from_utf8_lossy(&content).to_string();
-            let mut changed = false;
-
-            // Helper for XML escaping
-            let escape_xml = |s: &str| -> String {
-                s.replace("&", "&amp;")
-                    .replace("<", "&lt;")
-                    .replace(">", "&gt;")
-                    .replace("\"", "&quot;")
-                    .replace("'", "&apos;")
+    let fallback = config
+        .fallback_values
+        .get(lang)
+        .or_else(|| config.fallback_values.get("ALL"))
+        .map(|s| s.as_str());
+    // Overflow is measured against the theme fonts when runs name none
+    let text_fit = config.text_fit.as_ref().zip(fonts);
+    let theme = text_fit.map(|_| fit::theme_fonts(&package));
+    let masters: HashMap<String, fit::Masters> = match text_fit {
+        Some(_) => package
+            .names()
+            .filter(|n| PartKind::of(n) == PartKind::Slide)
+            .map(|n| (n.to_string(), fit::masters(&package, n)))
+            .collect(),
+        None => HashMap::new(),
+    };
+    let no_masters = fit::Masters::default();
+    // Labels first as they may hold tags, then computed values, then filters
+    // and empty values, the remaining tags all have a value. `None` when the
+    // text holds no tag to resolve.
+    let resolve_tags =
+        |text: &str, missing: &mut Vec<String>| -> Result<Option<String>, TagError> {
+            let lookup = |name: &str| lookup_tag(name, row, &config.mappings).cloned();
+            let translated = match translations {
+                Some(translations) => translations.resolve(text, lang)?,
+                None => None,
+            };
+            let mut changed = translated.is_some();
+            let text = translated.unwrap_or_else(|| text.to_string());
+            let computed = expr::resolve_expressions(&text, lookup, &config.cleaning.defaults)?;
+            changed |= computed.is_some();
+            let text = computed.unwrap_or(text);
+            let style = |name: &str, value: &str, filters: &[&str]| {
+                let numbers = config.cleaning.rules_for(name);
+                styles::style_for(name, value, filters, &config.style_rules, numbers)
+            };
+            let resolved = tags::resolve(&text, kind, lookup, fallback, style);
+            missing.extend(resolved.missing);
+            changed |= resolved.text.is_some();
+            let text = resolved.text.unwrap_or(text);
+            Ok(replace_tags(&text, row, &config.mappings).or_else(|| changed.then_some(text)))
+        };
+    let mut missing = Vec::new();
+    let mut fitted = Vec::new();
+    for part in package.parts.iter_mut() {
+        if !config.part_scope.includes(PartKind::of(&part.name)) {
+            continue;
+        }
+        let replaced = if part.name.ends_with(".xml") && kind.holds_tags(&part.name) {
+            let text = String::from_utf8_lossy(&part.data).to_string();
+            let joined = match kind {
+                DocumentKind::Document => docx::join_split_tags(&text),
+                _ => None,
+            };
+            let changed = joined.is_some();
+            let text = joined.unwrap_or(text);
+            let tagged = match text_fit {
+                Some(_) if PartKind::of(&part.name) == PartKind::Slide => fit::tagged_shapes(&text),
+                _ => HashSet::new(),
             };
+            let resolved = resolve_tags(&text, &mut missing)
+                .map_err(|e| OnePagerError::mapping(template_path, &part.name, e))?;
+            let text = resolved.or_else(|| changed.then_some(text));
+            // Values may hold paragraphs and line breaks, laid out once all are in
+            let text = text.map(|text| match kind {
+                DocumentKind::Document => {
+                    let text = docx::rich_text(&text).unwrap_or(text);
+                    docx::line_breaks(&text).unwrap_or(text)
+                }
+                DocumentKind::Workbook => xlsx::rich_text(&text).unwrap_or(text),
+                DocumentKind::Presentation => {
+                    let text = richtext::apply(&text).unwrap_or(text);
+                    sanitize::line_breaks(&text).unwrap_or(text)
+                }
+            });
+            match (text, text_fit, &theme) {
+                (Some(text), Some((fit_config, fonts)), Some(theme)) if !tagged.is_empty() => {
+                    let masters = masters.get(&part.name).unwrap_or(&no_masters);
+                    let (fit_text, fits) = fit::fit_shapes(
+                        &text, &part.name, &tagged, fonts, theme, masters, fit_config,
+                    );
+                    fitted.extend(fits);
+                    Some(fit_text.unwrap_or(text))
+                }
+                (text, _, _) => text,
+            }
+        } else if part.name.ends_with(".rels") {
+            // Hyperlink targets live in the relationships, not in the text
+            let text = String::from_utf8_lossy(&part.data).to_string();
+            hyperlinks::resolve_targets(&text, |name| {
+                lookup_tag(name, row, &config.mappings).cloned()
+            })
+        } else {
+            None
+        };
+        if let Some(replaced) = replaced {
+            part.data = replaced.into_bytes();
+        }
+    }
 
-            // 1. Implicit Replacements
-            for (k, v) in row {
-                let val_escaped = escape_xml(v);
+    // Tables are filled once tags are resolved, their values are never read as tags
+    let table_bindings: Vec<&TableBinding> = config
+        .table_bindings
+        .iter()
+        .filter(|b| {
+            b.template
+                .as_ref()
+                .is_none_or(|t| template_name.contains(t.as_str()))
+        })
+        .collect();
+    table::apply_table_bindings(
+        &mut package,
+        &table_bindings,
+        details.unwrap_or(&Details::default()),
+        |name| lookup_tag(name, row, &config.mappings).cloned(),
+    );
 
-                // Variant A: Literal <<Key>> (Rare in PPTX xml but possible)
-                let tag = format!("<<{}>>", k);
-                // Variant B: Escaped &lt;&lt;Key&gt;&gt; (Standard PPTX)
-                let tag_escaped = format!("&lt;&lt;{}&gt;&gt;", escape_xml(k));
+    // The modification date is always that of the generation
+    let no_doc_props = DocPropsConfig::default();
+    let resolve = |pattern: &str| {
+        let escaped = escape_xml(pattern);
+        let resolved = resolve_tags(&escaped, &mut missing)
+            .map_err(|e| OnePagerError::mapping(template_path, "docProps", e))?;
+        // Properties are plain text, with no runs to style
+        Ok(richtext::strip_markers(&resolved.unwrap_or(escaped)))
+    };
+    doc_props::apply(
+        &mut package,
+        config.doc_props.as_ref().unwrap_or(&no_doc_props),
+        resolve,
+        chrono::Utc::now(),
+    )?;
+
+    package
+        .write_to(&output_path)
+        .map_err(|e| OnePagerError::io(&output_path, &e))?;
+    Ok(Rendered {
+        path: output_path,
+        missing,
+        fitted,
+        issues: Vec::new(),
+    })
+}
 
-                // Try original case
-                if text.contains(&tag) {
-                    text = text.replace(&tag, &val_escaped);
-                    changed = true;
-                }
-                if text.contains(&tag_escaped) {
-                    text = text.replace(&tag_escaped, &val_escaped);
-                    changed = true;
-                }
+/// Escapes a value for XML text or attributes, after removing the
+/// characters XML does not allow.
+pub(crate) fn escape_xml(s: &str) -> String {
+    sanitize::sanitize_value(s)
+        .replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+        .replace("'", "&apos;")
+}
+
+/// Finds the row value of a tag name: the column itself, its uppercase
+/// variant, or the column mapped to `<<name>>`.
+fn lookup_tag<'a>(
+    name: &str,
+    row: &'a HashMap<String, String>,
+    mappings: &HashMap<String, String>,
+) -> Option<&'a String> {
+    if let Some(v) = row.get(name) {
+        return Some(v);
+    }
+    if let Some((_, v)) = row.iter().find(|(k, _)| k.to_uppercase() == name) {
+        return Some(v);
+    }
+    let tag = format!("<<{}>>", name);
+    mappings
+        .iter()
+        .find(|(_, tag_raw)| **tag_raw == tag)
+        .and_then(|(csv_key, _)| row.get(csv_key))
+}
 
-                // Try uppercase
-                let k_upper = k.to_uppercase();
-                let tag_upper = format!("<<{}>>", k_upper);
-                let tag_escaped_upper = format!("&lt;&lt;{}&gt;&gt;", escape_xml(&k_upper));
+/// Resolves `<<Tag>>` placeholders in one XML part.
+/// Returns `None` when nothing was replaced.
+pub(crate) fn replace_tags(
+    xml: &str,
+    row: &HashMap<String, String>,
+    mappings: &HashMap<String, String>,
+) -> Option<String> {
+    let mut text = xml.to_string();
+    let mut changed = false;
+
+    // 1. Implicit Replacements
+    for (k, v) in row {
+        let val_escaped = escape_xml(v);
+
+        // Variant A: Literal <<Key>> (Rare in PPTX xml but possible)
+        let tag = format!("<<{}>>", k);
+        // Variant B: Escaped &lt;&lt;Key&gt;&gt; (Standard PPTX)
+        let tag_escaped = format!("&lt;&lt;{}&gt;&gt;", escape_xml(k));
+
+        // Try original case
+        if text.contains(&tag) {
+            text = text.replace(&tag, &val_escaped);
+            changed = true;
+        }
+        if text.contains(&tag_escaped) {
+            text = text.replace(&tag_escaped, &val_escaped);
+            changed = true;
+        }
 
-                if text.contains(&tag_upper) {
-                    text = text.replace(&tag_upper, &val_escaped);
-                    changed = true;
-                }
-                if text.contains(&tag_escaped_upper) {
-                    text = text.replace(&tag_escaped_upper, &val_escaped);
-                    changed = true;
-                }
-            }
+        // Try uppercase
+        let k_upper = k.to_uppercase();
+        let tag_upper = format!("<<{}>>", k_upper);
+        let tag_escaped_upper = format!("&lt;&lt;{}&gt;&gt;", escape_xml(&k_upper));
 
-            // 2. Explicit Mappings
-            for (csv_key, tag_raw) in mappings {
-                if let Some(val) = row.get(csv_key) {
-                    let val_escaped = escape_xml(val);
+        if text.contains(&tag_upper) {
+            text = text.replace(&tag_upper, &val_escaped);
+            changed = true;
+        }
+        if text.contains(&tag_escaped_upper) {
+            text = text.replace(&tag_escaped_upper, &val_escaped);
+            changed = true;
+        }
+    }
 
-                    // tag_raw is e.g. "<<NOM DU CLIENT>>"
-                    // We need to support it if it appears literally OR escaped in XML
+    // 2. Explicit Mappings
+    for (csv_key, tag_raw) in mappings {
+        if let Some(val) = row.get(csv_key) {
+            let val_escaped = escape_xml(val);
 
-                    // 1. Literal
-                    if text.contains(tag_raw) {
-                        text = text.replace(tag_raw, &val_escaped);
-                        changed = true;
-                    }
+            // tag_raw is e.g. "<<NOM DU CLIENT>>"
+            // We need to support it if it appears literally OR escaped in XML
 
-                    // 2. Escaped
-                    let tag_escaped_ptn = escape_xml(tag_raw); // <<..>> -> &lt;&lt;..&gt;&gt;
-                    if text.contains(&tag_escaped_ptn) {
-                        text = text.replace(&tag_escaped_ptn, &val_escaped);
-                        changed = true;
-                    }
-                }
+            // 1. Literal
+            if text.contains(tag_raw) {
+                text = text.replace(tag_raw, &val_escaped);
+                changed = true;
             }
 
-            if changed {
-                zip_out
-                    .start_file(name, options)
-                    .map_err(|e| e.to_string())?;
-                zip_out
-                    .write_all(text.as_bytes())
-                    .map_err(|e| e.to_string())?;
-                continue;
+            // 2. Escaped
+            let tag_escaped_ptn = escape_xml(tag_raw); // <<..>> -> &lt;&lt;..&gt;&gt;
+            if text.contains(&tag_escaped_ptn) {
+                text = text.replace(&tag_escaped_ptn, &val_escaped);
+                changed = true;
             }
         }
-
-        zip_out
-            .start_file(name, options)
-            .map_err(|e| e.to_string())?;
-        zip_out.write_all(&content).map_err(|e| e.to_string())?;
     }
 
-    zip_out.finish().map_err(|e| e.to_string())?;
-    Ok(())
+    if changed {
+        Some(text)
+    } else {
+        None
+    }
 }
 
 #[test]
 fn test_generation_e2e() {
     use std::fs;
-    use std::io::Write;
-    use std::path::Path;
+    use std::io::{Read, Write};
 
     // 1. Setup Temp Dirs
     let temp_dir = std::env::temp_dir().join("one_pager_test_e2e");
@@ -458,13 +1730,19 @@ fn test_generation_e2e() {
     let mut mappings = HashMap::new();
     mappings.insert("ScoreVal".to_string(), "<<MY_SCORE_TAG>>".to_string());
     mappings.insert("JJ/MM/AAAA".to_string(), "<<[JJ/MM/AAAA]>>".to_string());
+    let config = GenConfig {
+        mappings,
+        ..Default::default()
+    };
 
     // 4. Run Process
     let result = process_single_pptx(
         &template_path,
         &row,
+        "ALL",
         output_dir.to_str().unwrap(),
-        &mappings,
+        &config,
+        Shared::default(),
     );
     assert!(result.is_ok(), "Process failed: {:?}", result.err());
 
@@ -0,0 +1,129 @@
+use crate::error::OnePagerError;
+use crate::pptx_engine::GenConfig;
+use crate::table::TableSource;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Settings chosen in the window, which a project file cannot set.
+const WINDOW_SETTINGS: [&str; 8] = [
+    "standard_csv",
+    "prev_year_csv",
+    "template_dir",
+    "output_dir",
+    "languages",
+    "mappings",
+    "translations_dir",
+    "project_file",
+];
+
+/// Fills in the settings of the JSON project file named by `project_file`:
+/// an object holding any generation setting without a field in the window,
+/// e.g. `shape_rules`, `text_fit` or `cleaning`, under its config name.
+/// Unknown or window settings are errors rather than being ignored, relative
+/// file and folder paths are taken from the folder of the project file.
+pub fn apply(config: GenConfig) -> Result<GenConfig, OnePagerError> {
+    let Some(path) = config.project_file.clone().filter(|p| !p.is_empty()) else {
+        return Ok(config);
+    };
+    let path = Path::new(&path);
+    let invalid = |message: String| {
+        OnePagerError::config(format!("Invalid project file {:?}: {}", path, message))
+    };
+    let text = fs::read_to_string(path).map_err(|e| OnePagerError::io(path, &e))?;
+    let project: serde_json::Map<String, Value> =
+        serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+    let Value::Object(mut settings) =
+        serde_json::to_value(&config).map_err(|e| invalid(e.to_string()))?
+    else {
+        return Err(invalid("not a settings object".to_string()));
+    };
+    for (key, value) in project {
+        if WINDOW_SETTINGS.contains(&key.as_str()) {
+            return Err(invalid(format!("\"{}\" is chosen in the window", key)));
+        }
+        if !settings.contains_key(&key) {
+            return Err(invalid(format!("unknown setting \"{}\"", key)));
+        }
+        settings.insert(key, value);
+    }
+    let mut config: GenConfig =
+        serde_json::from_value(Value::Object(settings)).map_err(|e| invalid(e.to_string()))?;
+
+    // Relative paths are relative to the project file, the working folder
+    // of the app is arbitrary
+    let folder = path.parent().unwrap_or(Path::new(""));
+    let resolve = |file: &mut String| {
+        if !file.is_empty() && Path::new(file.as_str()).is_relative() {
+            *file = folder.join(&*file).to_string_lossy().to_string();
+        }
+    };
+    for lookup in &mut config.lookups {
+        resolve(&mut lookup.path);
+    }
+    for binding in &mut config.table_bindings {
+        if let TableSource::Detail { path, .. } = &mut binding.source {
+            resolve(path);
+        }
+    }
+    if let Some(text_fit) = &mut config.text_fit {
+        text_fit.font_dirs.iter_mut().for_each(resolve);
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_project(json: &str) -> Result<GenConfig, OnePagerError> {
+        let path = std::env::temp_dir().join("onepager_project_test.json");
+        fs::write(&path, json).unwrap();
+        apply(GenConfig {
+            template_dir: "templates".to_string(),
+            languages: vec!["FR".to_string()],
+            project_file: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_apply_project_file() {
+        let config = with_project(
+            r#"{"strip_notes": true, "fallback_values": {"ALL": "n/a"}, "slide_rules": []}"#,
+        )
+        .unwrap();
+        assert!(config.strip_notes);
+        assert_eq!(config.fallback_values["ALL"], "n/a");
+        assert_eq!(config.template_dir, "templates");
+        assert_eq!(config.languages, vec!["FR"]);
+
+        let unknown = with_project(r#"{"strip_note": true}"#).unwrap_err();
+        assert!(unknown
+            .to_string()
+            .contains("unknown setting \"strip_note\""));
+        let window = with_project(r#"{"template_dir": "other"}"#).unwrap_err();
+        assert!(window.to_string().contains("chosen in the window"));
+        assert!(with_project(r#"{"strip_notes": "yes"}"#).is_err());
+        let color = with_project(r#"{"style_rules": [{"tag": "Delta", "color": "red"}]}"#);
+        assert!(
+            matches!(color, Err(OnePagerError::Config { message }) if message.contains("invalid color \"red\""))
+        );
+
+        let config = with_project(
+            r#"{"lookups": [{"path": "data/managers.csv", "key": "Org ID", "namespace": "am"}], "text_fit": {"font_dirs": ["fonts", "/usr/share/fonts"]}}"#,
+        )
+        .unwrap();
+        let folder = std::env::temp_dir();
+        assert_eq!(
+            Path::new(&config.lookups[0].path),
+            folder.join("data/managers.csv")
+        );
+        let font_dirs = &config.text_fit.unwrap().font_dirs;
+        assert_eq!(Path::new(&font_dirs[0]), folder.join("fonts"));
+        assert_eq!(font_dirs[1], "/usr/share/fonts");
+
+        let config = apply(GenConfig::default()).unwrap();
+        assert!(!config.strip_notes);
+    }
+}
@@ -0,0 +1,445 @@
+use crate::styles::RunStyle;
+use regex::Regex;
+
+// Inserted into escaped values by the `paragraphs`, `markdown` and style
+// tag filters, then turned into DrawingML structure by `apply`
+const PARAGRAPH: char = '\u{1}';
+const BULLET: char = '\u{2}';
+const BOLD: char = '\u{3}';
+const ITALIC: char = '\u{4}';
+/// Followed by an encoded `RunStyle` and `STYLE_SPEC_END`
+const STYLE: char = '\u{5}';
+const STYLE_SPEC_END: char = '\u{6}';
+const STYLE_END: char = '\u{7}';
+const MARKERS: [char; 7] = [
+    PARAGRAPH,
+    BULLET,
+    BOLD,
+    ITALIC,
+    STYLE,
+    STYLE_SPEC_END,
+    STYLE_END,
+];
+
+/// Marks every line of an escaped value as its own paragraph.
+pub fn paragraphs(escaped: &str) -> String {
+    escaped.replace('\n', &PARAGRAPH.to_string())
+}
+
+/// Marks paragraphs, `- ` or `* ` bullet lines, `**bold**` and `*italic*`
+/// spans of an escaped value.
+pub fn markdown(escaped: &str) -> String {
+    let re_bold = Regex::new(r"\*\*(.+?)\*\*").unwrap();
+    let re_italic = Regex::new(r"\*([^*\s](?:[^*]*[^*\s])?)\*").unwrap();
+    escaped
+        .split('\n')
+        .map(|line| {
+            let (bullet, line) = match line.strip_prefix("- ").or(line.strip_prefix("* ")) {
+                Some(rest) => (BULLET.to_string(), rest),
+                None => (String::new(), line),
+            };
+            let line = re_bold.replace_all(line, format!("{}$1{}", BOLD, BOLD));
+            let line = re_italic.replace_all(&line, format!("{}$1{}", ITALIC, ITALIC));
+            format!("{}{}", bullet, line)
+        })
+        .collect::<Vec<_>>()
+        .join(&PARAGRAPH.to_string())
+}
+
+/// Marks an escaped value to be rendered with `style`.
+pub fn styled(escaped: &str, style: &RunStyle) -> String {
+    format!(
+        "{}{}{}{}{}",
+        STYLE,
+        style.encode(),
+        STYLE_SPEC_END,
+        escaped,
+        STYLE_END
+    )
+}
+
+/// Rewrites the DrawingML paragraphs holding marked values: new paragraphs
+/// keep the paragraph properties, bullets get a bullet character and
+/// emphasized or styled pieces become runs of their own with adjusted run
+/// properties. Markers outside of runs are dropped. Returns `None` without markers.
+pub fn apply(xml: &str) -> Option<String> {
+    if !xml.contains(MARKERS) {
+        return None;
+    }
+    let re_paragraph = Regex::new(r"(?s)<a:p(?:\s[^>]*)?>.*?</a:p>").unwrap();
+    let text = re_paragraph.replace_all(xml, |caps: &regex::Captures| {
+        if caps[0].contains(MARKERS) {
+            rewrite_paragraph(&caps[0])
+        } else {
+            caps[0].to_string()
+        }
+    });
+    Some(strip_markers(&text))
+}
+
+/// A piece of a marked value.
+pub(crate) enum Piece {
+    /// Text with the style it is rendered with, markdown emphasis included
+    Text(String, RunStyle),
+    /// Starts a new paragraph
+    Paragraph,
+    /// Starts a bulleted paragraph, or bullets the current one while empty
+    Bullet,
+}
+
+/// Splits the text of a run into its marked pieces.
+pub(crate) fn pieces(text: &str) -> Vec<Piece> {
+    let (mut bold, mut italic) = (false, false);
+    let mut style: Option<RunStyle> = None;
+    let mut piece = String::new();
+    let mut pieces = Vec::new();
+    let mut chars = text.chars();
+    loop {
+        let c = chars.next();
+        if let Some(c) = c.filter(|c| !MARKERS.contains(c)) {
+            piece.push(c);
+            continue;
+        }
+        if !piece.is_empty() {
+            let style = style.clone().unwrap_or_default();
+            let style = RunStyle {
+                bold: bold.then_some(true).or(style.bold),
+                italic: italic.then_some(true).or(style.italic),
+                color: style.color,
+            };
+            pieces.push(Piece::Text(std::mem::take(&mut piece), style));
+        }
+        match c {
+            Some(BOLD) => bold = !bold,
+            Some(ITALIC) => italic = !italic,
+            Some(PARAGRAPH) => pieces.push(Piece::Paragraph),
+            Some(BULLET) => pieces.push(Piece::Bullet),
+            Some(STYLE) => {
+                let spec: String = chars
+                    .by_ref()
+                    .take_while(|&c| c != STYLE_SPEC_END)
+                    .collect();
+                style = Some(RunStyle::decode(&spec));
+            }
+            Some(STYLE_END) => style = None,
+            Some(_) => {}
+            None => break,
+        }
+    }
+    pieces
+}
+
+/// Removes the markers left outside of runs, e.g. in attributes.
+pub(crate) fn strip_markers(xml: &str) -> String {
+    let re_style_spec = Regex::new("\u{5}[^\u{6}]*\u{6}").unwrap();
+    re_style_spec.replace_all(xml, "").replace(MARKERS, "")
+}
+
+pub(crate) fn holds_markers(xml: &str) -> bool {
+    xml.contains(MARKERS)
+}
+
+struct Paragraph {
+    bullet: bool,
+    content: String,
+}
+
+fn rewrite_paragraph(xml: &str) -> String {
+    let re_parts = Regex::new(
+        r"(?s)^(<a:p(?:\s[^>]*)?>)(<a:pPr\b(?:[^>]*?/>|.*?</a:pPr>))?(.*?)(<a:endParaRPr\b(?:[^>]*?/>|.*?</a:endParaRPr>))?</a:p>$",
+    )
+    .unwrap();
+    let Some(parts) = re_parts.captures(xml) else {
+        return xml.to_string();
+    };
+    let open = &parts[1];
+    let p_pr = parts.get(2).map_or("", |m| m.as_str());
+    let end_rpr = parts.get(4).map_or("", |m| m.as_str());
+    let body = &parts[3];
+
+    let re_run = Regex::new(
+        r"(?s)<a:r>\s*(<a:rPr\b(?:[^>]*?/>|.*?</a:rPr>))?\s*<a:t>([^<]*)</a:t>\s*</a:r>",
+    )
+    .unwrap();
+    let mut paragraphs = vec![Paragraph {
+        bullet: false,
+        content: String::new(),
+    }];
+    let mut last = 0;
+    for run in re_run.captures_iter(body) {
+        let whole = run.get(0).unwrap();
+        let current = paragraphs.last_mut().unwrap();
+        current.content.push_str(&body[last..whole.start()]);
+        last = whole.end();
+        let text = &run[2];
+        if !text.contains(MARKERS) {
+            current.content.push_str(whole.as_str());
+            continue;
+        }
+
+        let r_pr = run.get(1).map_or("", |m| m.as_str());
+        for piece in pieces(text) {
+            match piece {
+                Piece::Text(text, style) => {
+                    let r_pr = run_properties(r_pr, &style);
+                    let current = paragraphs.last_mut().unwrap();
+                    current
+                        .content
+                        .push_str(&format!("<a:r>{}<a:t>{}</a:t></a:r>", r_pr, text));
+                }
+                Piece::Paragraph => paragraphs.push(Paragraph {
+                    bullet: false,
+                    content: String::new(),
+                }),
+                Piece::Bullet if paragraphs.last().unwrap().content.is_empty() => {
+                    paragraphs.last_mut().unwrap().bullet = true;
+                }
+                Piece::Bullet => paragraphs.push(Paragraph {
+                    bullet: true,
+                    content: String::new(),
+                }),
+            }
+        }
+    }
+    paragraphs
+        .last_mut()
+        .unwrap()
+        .content
+        .push_str(&body[last..]);
+
+    let count = paragraphs.len();
+    paragraphs
+        .into_iter()
+        .enumerate()
+        .map(|(i, paragraph)| {
+            let p_pr = if paragraph.bullet {
+                bulleted(p_pr)
+            } else {
+                p_pr.to_string()
+            };
+            let end = if i + 1 == count { end_rpr } else { "" };
+            format!("{}{}{}{}</a:p>", open, p_pr, paragraph.content, end)
+        })
+        .collect()
+}
+
+/// Run properties with the style applied.
+fn run_properties(r_pr: &str, style: &RunStyle) -> String {
+    if style.bold.is_none() && style.italic.is_none() && style.color.is_none() {
+        return r_pr.to_string();
+    }
+    let mut r_pr = if r_pr.is_empty() {
+        "<a:rPr/>".to_string()
+    } else {
+        r_pr.to_string()
+    };
+    for (set, attr) in [(style.bold, "b"), (style.italic, "i")] {
+        if let Some(set) = set {
+            r_pr = set_attr(&r_pr, attr, if set { "1" } else { "0" });
+        }
+    }
+    if let Some(color) = &style.color {
+        r_pr = set_fill(&r_pr, color);
+    }
+    r_pr
+}
+
+/// Replaces the text fill of run properties with a solid color. Only the
+/// fill children of `<a:rPr>` are replaced, those of the outline, underline
+/// or effects are kept.
+fn set_fill(r_pr: &str, color: &str) -> String {
+    const FILLS: [&str; 6] = [
+        "a:noFill",
+        "a:solidFill",
+        "a:gradFill",
+        "a:blipFill",
+        "a:pattFill",
+        "a:grpFill",
+    ];
+    let r_pr = match r_pr.strip_suffix("/>") {
+        Some(open) => format!("{}></a:rPr>", open),
+        None => r_pr.to_string(),
+    };
+    let (start, end, children) = children(&r_pr);
+
+    // The fill follows the outline, first of the children otherwise
+    let fill = format!(r#"<a:solidFill><a:srgbClr val="{}"/></a:solidFill>"#, color);
+    let mut out = r_pr[..start].to_string();
+    let has_ln = children.first().is_some_and(|(name, _)| name == "a:ln");
+    if !has_ln {
+        out.push_str(&fill);
+    }
+    for (name, range) in &children {
+        if !FILLS.contains(&name.as_str()) {
+            out.push_str(&r_pr[range.clone()]);
+        }
+        if name == "a:ln" {
+            out.push_str(&fill);
+        }
+    }
+    out.push_str(&r_pr[end..]);
+    out
+}
+
+/// Content bounds of a properties element written open, and its direct
+/// children by name.
+fn children(xml: &str) -> (usize, usize, Vec<(String, std::ops::Range<usize>)>) {
+    let start = xml.find('>').map_or(xml.len(), |p| p + 1);
+    let end = xml.rfind("</").unwrap_or(xml.len()).max(start);
+    let re_tag = Regex::new(r"<(/?)([\w:.-]+)[^>]*?(/?)>").unwrap();
+    let mut children = Vec::new();
+    let mut depth = 0;
+    let mut child_start = start;
+    for caps in re_tag.captures_iter(&xml[start..end]) {
+        let whole = caps.get(0).unwrap();
+        let closing = &caps[1] == "/";
+        if !closing && depth == 0 {
+            child_start = start + whole.start();
+        }
+        if closing {
+            depth -= 1;
+        } else if &caps[3] != "/" {
+            depth += 1;
+        }
+        if depth == 0 {
+            children.push((caps[2].to_string(), child_start..start + whole.end()));
+        }
+    }
+    (start, end, children)
+}
+
+/// Replaces the `name` child of a properties element such as `<w:rPr>` with
+/// `child`, inserted where the schema sequence `order` puts it.
+pub(crate) fn set_child(props: &str, name: &str, child: &str, order: &[&str]) -> String {
+    let props = match props.strip_suffix("/>") {
+        Some(open) => {
+            let tag = open[1..].split_whitespace().next().unwrap_or_default();
+            format!("{}></{}>", open, tag)
+        }
+        None => props.to_string(),
+    };
+    let (start, end, children) = children(&props);
+    let rank = |n: &str| order.iter().position(|o| *o == n).unwrap_or(order.len());
+    let mut out = props[..start].to_string();
+    let mut inserted = false;
+    for (child_name, range) in &children {
+        if !inserted && rank(child_name) > rank(name) {
+            out.push_str(child);
+            inserted = true;
+        }
+        if child_name != name {
+            out.push_str(&props[range.clone()]);
+        }
+    }
+    if !inserted {
+        out.push_str(child);
+    }
+    out.push_str(&props[end..]);
+    out
+}
+
+/// Paragraph properties with a `•` bullet and a hanging indent.
+fn bulleted(p_pr: &str) -> String {
+    let re_bullet = Regex::new(r"(?s)<a:bu\w+\b(?:[^>]*?/>|.*?</a:bu\w+>)").unwrap();
+    let p_pr = if p_pr.is_empty() { "<a:pPr/>" } else { p_pr };
+    let p_pr = re_bullet.replace_all(p_pr, "").to_string();
+    let p_pr = set_attr(&p_pr, "marL", "285750");
+    let mut p_pr = set_attr(&p_pr, "indent", "-285750");
+    if let Some(open) = p_pr.strip_suffix("/>") {
+        p_pr = format!("{}></a:pPr>", open);
+    }
+    // Bullet elements come before tab stops and default run properties
+    let pos = ["<a:tabLst", "<a:defRPr", "<a:extLst", "</a:pPr>"]
+        .iter()
+        .filter_map(|tag| p_pr.find(tag))
+        .min()
+        .unwrap_or(p_pr.len());
+    p_pr.insert_str(pos, r#"<a:buFont typeface="Arial"/><a:buChar char="•"/>"#);
+    p_pr
+}
+
+/// Sets an attribute on the opening tag at the start of `xml`.
+pub(crate) fn set_attr(xml: &str, name: &str, value: &str) -> String {
+    let end = xml.find('>').unwrap_or(xml.len());
+    let end = if xml[..end].ends_with('/') {
+        end - 1
+    } else {
+        end
+    };
+    let open = &xml[..end];
+    let re_attr = Regex::new(&format!(r#"\s{}="[^"]*""#, name)).unwrap();
+    let open = re_attr.replace(open, "");
+    format!(r#"{} {}="{}"{}"#, open, name, value, &xml[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_markers() {
+        assert_eq!(
+            markdown("Up **12%** vs *Q3*\n- Reviews"),
+            "Up \u{3}12%\u{3} vs \u{4}Q3\u{4}\u{1}\u{2}Reviews"
+        );
+        assert_eq!(markdown("4 * 5 = 20"), "4 * 5 = 20");
+    }
+
+    #[test]
+    fn test_paragraphs_keep_properties() {
+        let value = paragraphs("1 rue X\nParis");
+        let xml = format!(
+            r#"<a:p><a:pPr algn="l"/><a:r><a:rPr sz="1200"/><a:t>{}</a:t></a:r><a:endParaRPr sz="1200"/></a:p>"#,
+            value
+        );
+        assert_eq!(
+            apply(&xml).unwrap(),
+            concat!(
+                r#"<a:p><a:pPr algn="l"/><a:r><a:rPr sz="1200"/><a:t>1 rue X</a:t></a:r></a:p>"#,
+                r#"<a:p><a:pPr algn="l"/><a:r><a:rPr sz="1200"/><a:t>Paris</a:t></a:r><a:endParaRPr sz="1200"/></a:p>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_styled_value_in_own_run() {
+        let style = RunStyle {
+            color: Some("FF0000".to_string()),
+            ..Default::default()
+        };
+        let xml = format!(
+            r#"<a:p><a:r><a:rPr sz="1200"><a:ln w="1"><a:solidFill><a:srgbClr val="000000"/></a:solidFill></a:ln><a:solidFill><a:schemeClr val="tx1"/></a:solidFill><a:latin typeface="Arial"/></a:rPr><a:t>Delta: {}</a:t></a:r></a:p>"#,
+            styled("-12%", &style)
+        );
+        assert_eq!(
+            apply(&xml).unwrap(),
+            concat!(
+                r#"<a:p><a:r><a:rPr sz="1200"><a:ln w="1"><a:solidFill><a:srgbClr val="000000"/></a:solidFill></a:ln><a:solidFill><a:schemeClr val="tx1"/></a:solidFill><a:latin typeface="Arial"/></a:rPr><a:t>Delta: </a:t></a:r>"#,
+                r#"<a:r><a:rPr sz="1200"><a:ln w="1"><a:solidFill><a:srgbClr val="000000"/></a:solidFill></a:ln><a:solidFill><a:srgbClr val="FF0000"/></a:solidFill><a:latin typeface="Arial"/></a:rPr><a:t>-12%</a:t></a:r></a:p>"#
+            )
+        );
+        let attribute = format!(r#"<p:cNvPr descr="{}"/>"#, styled("-12%", &style));
+        assert_eq!(apply(&attribute).unwrap(), r#"<p:cNvPr descr="-12%"/>"#);
+    }
+
+    #[test]
+    fn test_markdown_runs_and_bullets() {
+        let value = markdown("**Top** rated\n- Fast\n- *Kind*");
+        let xml = format!(
+            r#"<a:p><a:r><a:rPr lang="fr-FR" b="0"/><a:t>{}</a:t></a:r></a:p>"#,
+            value
+        );
+        let bullet = r#"<a:pPr marL="285750" indent="-285750"><a:buFont typeface="Arial"/><a:buChar char="•"/></a:pPr>"#;
+        assert_eq!(
+            apply(&xml).unwrap(),
+            format!(
+                concat!(
+                    r#"<a:p><a:r><a:rPr lang="fr-FR" b="1"/><a:t>Top</a:t></a:r><a:r><a:rPr lang="fr-FR" b="0"/><a:t> rated</a:t></a:r></a:p>"#,
+                    r#"<a:p>{b}<a:r><a:rPr lang="fr-FR" b="0"/><a:t>Fast</a:t></a:r></a:p>"#,
+                    r#"<a:p>{b}<a:r><a:rPr lang="fr-FR" b="0" i="1"/><a:t>Kind</a:t></a:r></a:p>"#
+                ),
+                b = bullet
+            )
+        );
+    }
+}
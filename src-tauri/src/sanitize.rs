@@ -0,0 +1,84 @@
+use crate::validate::is_xml_char;
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+/// Makes a CSV value safe to insert into XML: Unicode NFC, line breaks
+/// unified to `\n` (including Excel's vertical tab), characters XML 1.0
+/// does not allow removed.
+pub fn sanitize_value(value: &str) -> String {
+    value
+        .replace("\r\n", "\n")
+        .nfc()
+        .filter_map(|c| match c {
+            '\r' | '\u{0B}' | '\u{2028}' | '\u{2029}' => Some('\n'),
+            c if is_xml_char(c) => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Splits DrawingML runs whose text holds `\n` into runs separated by
+/// `<a:br/>`, every piece keeping the run properties.
+/// Returns `None` when no run has a line break.
+pub fn line_breaks(xml: &str) -> Option<String> {
+    if !xml.contains('\n') {
+        return None;
+    }
+    let re_run = Regex::new(
+        r"(?s)<a:r>(\s*<a:rPr\b(?:[^>]*?/>|.*?</a:rPr>))?\s*<a:t>([^<]*\n[^<]*)</a:t>\s*</a:r>",
+    )
+    .unwrap();
+    let replaced = re_run.replace_all(xml, |caps: &regex::Captures| {
+        let r_pr = caps.get(1).map_or("", |m| m.as_str().trim_start());
+        let br = if r_pr.is_empty() {
+            "<a:br/>".to_string()
+        } else {
+            format!("<a:br>{}</a:br>", r_pr)
+        };
+        caps[2]
+            .split('\n')
+            .map(|line| format!("<a:r>{}<a:t>{}</a:t></a:r>", r_pr, line))
+            .collect::<Vec<_>>()
+            .join(&br)
+    });
+    match replaced {
+        std::borrow::Cow::Owned(text) => Some(text),
+        std::borrow::Cow::Borrowed(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_value() {
+        assert_eq!(sanitize_value("Cafe\u{301}"), "Caf\u{e9}");
+        assert_eq!(sanitize_value("A\u{1A}B\u{0}C\u{FFFF}"), "ABC");
+        assert_eq!(
+            sanitize_value("1 rue\r\nParis\u{0B}France\rFR"),
+            "1 rue\nParis\nFrance\nFR"
+        );
+        assert_eq!(sanitize_value("Tab\there"), "Tab\there");
+    }
+
+    #[test]
+    fn test_line_breaks() {
+        let xml = r#"<a:p><a:r><a:rPr lang="fr-FR" sz="1200"/><a:t>1 rue
+Paris</a:t></a:r><a:r><a:t>Kept</a:t></a:r></a:p>"#;
+        assert_eq!(
+            line_breaks(xml).unwrap(),
+            concat!(
+                r#"<a:p><a:r><a:rPr lang="fr-FR" sz="1200"/><a:t>1 rue</a:t></a:r>"#,
+                r#"<a:br><a:rPr lang="fr-FR" sz="1200"/></a:br>"#,
+                r#"<a:r><a:rPr lang="fr-FR" sz="1200"/><a:t>Paris</a:t></a:r>"#,
+                r#"<a:r><a:t>Kept</a:t></a:r></a:p>"#
+            )
+        );
+        assert_eq!(
+            line_breaks("<a:r><a:t>A\nB</a:t></a:r>").unwrap(),
+            "<a:r><a:t>A</a:t></a:r><a:br/><a:r><a:t>B</a:t></a:r>"
+        );
+        assert!(line_breaks("<p:sld>\n<a:r><a:t>A</a:t></a:r></p:sld>").is_none());
+    }
+}
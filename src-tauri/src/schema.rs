@@ -0,0 +1,230 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Decimal,
+    /// `12%`, `12.5 %` or a plain number
+    Percent,
+    /// chrono format, e.g. `%d/%m/%Y`
+    Date {
+        format: String,
+    },
+    Enum {
+        values: Vec<String>,
+    },
+    Email,
+    Url,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ColumnRule {
+    #[serde(flatten)]
+    pub column_type: ColumnType,
+    #[serde(default)]
+    pub required: bool,
+    /// Inclusive bounds for numeric types
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+/// Column rules read from a JSON schema file:
+/// `{"columns": {"Rating": {"type": "decimal", "required": true, "min": 0, "max": 5}}}`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Schema {
+    pub columns: BTreeMap<String, ColumnRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    /// 1-based data row, 0 for header-level issues
+    pub row: usize,
+    pub column: String,
+    pub value: String,
+    pub message: String,
+}
+
+impl Schema {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid schema: {}", e))
+    }
+
+    /// Declared columns absent from the CSV header.
+    pub fn check_headers(&self, headers: &[String]) -> Vec<ValidationIssue> {
+        self.columns
+            .keys()
+            .filter(|c| !headers.contains(c))
+            .map(|c| ValidationIssue {
+                row: 0,
+                column: c.clone(),
+                value: String::new(),
+                message: "Column missing from the file".to_string(),
+            })
+            .collect()
+    }
+
+    pub fn validate_row(
+        &self,
+        row_number: usize,
+        row: &HashMap<String, String>,
+    ) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        for (column, rule) in &self.columns {
+            let Some(value) = row.get(column) else {
+                continue;
+            };
+            if let Err(message) = rule.check(value) {
+                issues.push(ValidationIssue {
+                    row: row_number,
+                    column: column.clone(),
+                    value: value.clone(),
+                    message,
+                });
+            }
+        }
+        issues
+    }
+}
+
+impl ColumnRule {
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return if self.required {
+                Err("Required value is empty".to_string())
+            } else {
+                Ok(())
+            };
+        }
+
+        let number = match &self.column_type {
+            ColumnType::Text => None,
+            ColumnType::Integer => Some(
+                value
+                    .parse::<i64>()
+                    .map(|i| i as f64)
+                    .map_err(|_| "Not an integer".to_string())?,
+            ),
+            ColumnType::Decimal => Some(parse_decimal(value).ok_or("Not a number")?),
+            ColumnType::Percent => Some(
+                parse_decimal(value.trim_end_matches('%').trim_end()).ok_or("Not a percentage")?,
+            ),
+            ColumnType::Date { format } => {
+                chrono::NaiveDate::parse_from_str(value, format)
+                    .map_err(|_| format!("Not a date in format {}", format))?;
+                None
+            }
+            ColumnType::Enum { values } => {
+                if !values.iter().any(|v| v == value) {
+                    return Err(format!("Expected one of: {}", values.join(", ")));
+                }
+                None
+            }
+            ColumnType::Email => {
+                let re = Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap();
+                if !re.is_match(value) {
+                    return Err("Not an email address".to_string());
+                }
+                None
+            }
+            ColumnType::Url => {
+                let re = Regex::new(r"(?i)^https?://[^\s/?#]+\.[^\s/?#]+\S*$").unwrap();
+                if !re.is_match(value) {
+                    return Err("Not a http(s) URL".to_string());
+                }
+                None
+            }
+        };
+
+        if let Some(n) = number {
+            if self.min.is_some_and(|min| n < min) || self.max.is_some_and(|max| n > max) {
+                return Err(format!(
+                    "Out of range [{}, {}]",
+                    self.min.map(|m| m.to_string()).unwrap_or_default(),
+                    self.max.map(|m| m.to_string()).unwrap_or_default()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Accepts a decimal comma when there is no dot ("4,5").
+fn parse_decimal(value: &str) -> Option<f64> {
+    let normalized = if value.contains('.') {
+        value.to_string()
+    } else {
+        value.replace(',', ".")
+    };
+    normalized.parse::<f64>().ok().filter(|f| f.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(json: &str) -> ColumnRule {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_column_rules() {
+        let revenue = rule(r#"{"type": "integer", "required": true, "min": 0}"#);
+        assert!(revenue.check("1200").is_ok());
+        assert!(revenue.check("12k").is_err());
+        assert!(revenue.check("-5").is_err());
+        assert!(revenue.check("").is_err());
+
+        let date = rule(r#"{"type": "date", "format": "%Y-%m-%d"}"#);
+        assert!(date.check("2026-10-18").is_ok());
+        assert!(date.check("2026-13-45").is_err());
+        assert!(date.check("").is_ok());
+
+        let rating = rule(r#"{"type": "decimal", "min": 0, "max": 5}"#);
+        assert!(rating.check("4,5").is_ok());
+        assert!(rating.check("5.1").is_err());
+
+        let share = rule(r#"{"type": "percent", "max": 100}"#);
+        assert!(share.check("12.5 %").is_ok());
+
+        let plan = rule(r#"{"type": "enum", "values": ["Basic", "Premium"]}"#);
+        assert!(plan.check("Premium").is_ok());
+        assert!(plan.check("Gold").is_err());
+
+        assert!(rule(r#"{"type": "email"}"#).check("am@partoo.co").is_ok());
+        assert!(rule(r#"{"type": "email"}"#).check("am@partoo").is_err());
+        assert!(rule(r#"{"type": "url"}"#)
+            .check("https://partoo.co/x?y=1")
+            .is_ok());
+        assert!(rule(r#"{"type": "url"}"#).check("partoo.co").is_err());
+    }
+
+    #[test]
+    fn test_validate_row_reports_columns() {
+        let schema: Schema = serde_json::from_str(
+            r#"{"columns": {"Revenue": {"type": "integer"}, "Email": {"type": "email", "required": true}}}"#,
+        )
+        .unwrap();
+        let row: HashMap<String, String> = [
+            ("Revenue".to_string(), "12k".to_string()),
+            ("Email".to_string(), "".to_string()),
+        ]
+        .into();
+        let issues = schema.validate_row(3, &row);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].column, "Email");
+        assert_eq!(issues[1].row, 3);
+        assert_eq!(issues[1].value, "12k");
+
+        let missing = schema.check_headers(&["Revenue".to_string()]);
+        assert_eq!(missing[0].column, "Email");
+    }
+}
@@ -0,0 +1,639 @@
+use crate::error::OnePagerError;
+use crate::ooxml::{self, Package};
+use crate::pptx_engine::escape_xml;
+use crate::richtext;
+use crate::slides;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::Path;
+
+const REL_IMAGE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+
+/// Shape elements of a slide tree with their non-visual properties.
+const SHAPE_ELEMENTS: [(&str, &str); 5] = [
+    ("p:sp", "p:nvSpPr"),
+    ("p:pic", "p:nvPicPr"),
+    ("p:grpSp", "p:nvGrpSpPr"),
+    ("p:graphicFrame", "p:nvGraphicFramePr"),
+    ("p:cxnSp", "p:nvCxnSpPr"),
+];
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ShapeAction {
+    /// Keeps the shape in the selection pane but hides it
+    Hide,
+    Delete,
+    /// Image file, relative to the template folder, may hold `<<Column>>`
+    Image {
+        image: String,
+    },
+    /// Lines become paragraphs, `<<tags>>` are resolved like any other
+    Text {
+        text: String,
+    },
+}
+
+/// Acts on the shapes whose name or alt text is `shape`, e.g. deletes the
+/// `Premium icons` group when `condition` holds.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ShapeRule {
+    pub shape: String,
+    /// Same syntax as slide rules, the rule always applies without one
+    #[serde(default)]
+    pub condition: Option<String>,
+    #[serde(flatten)]
+    pub action: ShapeAction,
+    /// Restricts the rule to templates whose file name contains this text
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// A shape of a slide: the whole element and its `<p:cNvPr>` tag.
+struct Shape {
+    range: Range<usize>,
+    nv_pr: Range<usize>,
+}
+
+/// Applies the rules whose condition holds to every slide, before tags are
+/// resolved so that replacement text may hold tags.
+pub fn apply_shape_rules(
+    package: &mut Package,
+    rules: &[&ShapeRule],
+    template_dir: &Path,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<(), OnePagerError> {
+    let rules: Vec<&ShapeRule> = rules
+        .iter()
+        .filter(|r| {
+            r.condition
+                .as_ref()
+                .is_none_or(|c| slides::evaluate_condition(c, &lookup))
+        })
+        .copied()
+        .collect();
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    for slide in slides::slide_order(package) {
+        let Some(mut xml) = package.get_text(&slide) else {
+            continue;
+        };
+        let mut changed = false;
+        // One media part per image and slide, whatever the number of shapes
+        let mut embedded: HashMap<String, String> = HashMap::new();
+        let mut deleted = HashSet::new();
+        for rule in &rules {
+            let mut from = 0;
+            while let Some(shape) = find_shape(&xml, from, &rule.shape) {
+                let element = &xml[shape.range.clone()];
+                let replacement = match &rule.action {
+                    ShapeAction::Hide => {
+                        let nv_pr = shape.nv_pr.start - shape.range.start
+                            ..shape.nv_pr.end - shape.range.start;
+                        let hidden = richtext::set_attr(&element[nv_pr.clone()], "hidden", "1");
+                        format!(
+                            "{}{}{}",
+                            &element[..nv_pr.start],
+                            hidden,
+                            &element[nv_pr.end..]
+                        )
+                    }
+                    ShapeAction::Delete => {
+                        deleted.extend(shape_ids(element));
+                        String::new()
+                    }
+                    ShapeAction::Image { image } => {
+                        let path = image_path(image, &lookup)?;
+                        if path.trim().is_empty() {
+                            element.to_string()
+                        } else {
+                            let id = match embedded.get(&path) {
+                                Some(id) => id.clone(),
+                                None => {
+                                    let id = embed_image(
+                                        package,
+                                        &slide,
+                                        &template_dir.join(path.trim()),
+                                    )?;
+                                    embedded.insert(path, id.clone());
+                                    id
+                                }
+                            };
+                            set_embed(element, &id)
+                        }
+                    }
+                    ShapeAction::Text { text } => replace_text_body(element, "p:txBody", text),
+                };
+                // Continue past the shape's own properties, its children may match too
+                from = shape.range.start
+                    + replacement
+                        .find("<p:cNvPr")
+                        .map_or(0, |pos| pos + "<p:cNvPr".len());
+                changed |= replacement != element;
+                xml.replace_range(shape.range, &replacement);
+            }
+        }
+        if changed {
+            let xml = remove_animations(&xml, &deleted).unwrap_or(xml);
+            package.set(&slide, xml.into_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Ids of a shape and of the shapes it groups.
+pub(crate) fn shape_ids(element: &str) -> Vec<String> {
+    let re_id = Regex::new(r#"<p:cNvPr\b[^>]*?\sid="(\d+)""#).unwrap();
+    re_id
+        .captures_iter(element)
+        .map(|c| c[1].to_string())
+        .collect()
+}
+
+/// Drops the animations and builds of removed shapes from the slide timing,
+/// then the sequences left empty. PowerPoint repairs a slide whose timing
+/// targets a missing shape. Returns `None` when nothing changed.
+pub(crate) fn remove_animations(xml: &str, ids: &HashSet<String>) -> Option<String> {
+    if ids.is_empty() {
+        return None;
+    }
+    let start = xml.find("<p:timing")?;
+    let end = xml[start..].find("</p:timing>")? + start + "</p:timing>".len();
+    let re_build =
+        Regex::new(r#"(?s)<p:bld\w+\b[^>]*?\sspid="(\d+)"[^>]*?(?:/>|>.*?</p:bld\w+>)"#).unwrap();
+    let re_target = Regex::new(r#"<p:spTgt\b[^>]*?\sspid="(\d+)""#).unwrap();
+    let re_empty = Regex::new(r"<p:childTnLst>\s*</p:childTnLst>|<p:childTnLst/>").unwrap();
+
+    let timing = &xml[start..end];
+    let mut timing = re_build
+        .replace_all(timing, |caps: &regex::Captures| {
+            if ids.contains(&caps[1]) {
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .into_owned();
+    // Every effect on a removed shape, then the time nodes left without children
+    loop {
+        let nodes = time_nodes(&timing);
+        let mut ranges: Vec<Range<usize>> = re_target
+            .captures_iter(&timing)
+            .filter(|c| ids.contains(&c[1]))
+            .map(|c| c.get(0).unwrap().start())
+            .chain(re_empty.find_iter(&timing).map(|m| m.start()))
+            .filter_map(|pos| {
+                nodes
+                    .iter()
+                    .filter(|node| node.contains(&pos))
+                    .max_by_key(|node| node.start)
+                    .cloned()
+            })
+            .collect();
+        if ranges.is_empty() {
+            break;
+        }
+        // Outer nodes take the nodes they hold along
+        ranges.sort_by_key(|r| r.start);
+        let mut kept = String::with_capacity(timing.len());
+        let mut last = 0;
+        for range in ranges {
+            if range.start < last {
+                continue;
+            }
+            kept.push_str(&timing[last..range.start]);
+            last = range.end;
+        }
+        kept.push_str(&timing[last..]);
+        timing = kept;
+    }
+
+    let re_empty_list = Regex::new(r"<p:(?:bldLst|tnLst)>\s*</p:(?:bldLst|tnLst)>").unwrap();
+    let timing = re_empty_list.replace_all(&timing, "");
+    let re_empty_timing = Regex::new(r"^<p:timing\b[^>]*>\s*</p:timing>$").unwrap();
+    let timing = if re_empty_timing.is_match(&timing) {
+        ""
+    } else {
+        &timing
+    };
+    let replaced = format!("{}{}{}", &xml[..start], timing, &xml[end..]);
+    (replaced != xml).then_some(replaced)
+}
+
+/// Ranges of the shapes named `name` or with `name` as alt text.
+pub(crate) fn shape_ranges(xml: &str, name: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut from = 0;
+    while let Some(shape) = find_shape(xml, from, name) {
+        from = shape.nv_pr.end;
+        ranges.push(shape.range);
+    }
+    ranges
+}
+
+/// Next shape from `from` whose `name` or `descr` is `target`. Attribute
+/// values are compared unescaped, PowerPoint writes `'` as is and other
+/// tools as `&apos;`.
+fn find_shape(xml: &str, from: usize, target: &str) -> Option<Shape> {
+    let re_nv_pr = Regex::new(r"<p:cNvPr\b[^>]*>").unwrap();
+    let re_name = Regex::new(r#"\s(?:name|descr)="([^"]*)""#).unwrap();
+    let shape = re_nv_pr
+        .find_iter(&xml[from..])
+        .map(|m| from + m.start()..from + m.end())
+        .filter(|nv_pr| {
+            re_name
+                .captures_iter(&xml[nv_pr.clone()])
+                .any(|c| unescape_xml(&c[1]) == target)
+        })
+        .find_map(|nv_pr| {
+            let range = enclosing_shape(xml, nv_pr.start)?;
+            Some(Shape { range, nv_pr })
+        });
+    shape
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Ranges of the `<p:par>`, `<p:seq>` and `<p:excl>` time nodes.
+fn time_nodes(timing: &str) -> Vec<Range<usize>> {
+    let re_node = Regex::new(r"<(/?)p:(?:par|seq|excl)\b[^>]*?(/?)>").unwrap();
+    let mut open = Vec::new();
+    let mut nodes = Vec::new();
+    for caps in re_node.captures_iter(timing) {
+        let whole = caps.get(0).unwrap();
+        match (&caps[1], &caps[2]) {
+            ("/", _) => {
+                if let Some(start) = open.pop() {
+                    nodes.push(start..whole.end());
+                }
+            }
+            (_, "/") => nodes.push(whole.range()),
+            _ => open.push(whole.start()),
+        }
+    }
+    nodes
+}
+
+/// Range of the shape element whose non-visual properties start with the
+/// `<p:cNvPr>` at `pos`.
+fn enclosing_shape(xml: &str, pos: usize) -> Option<Range<usize>> {
+    let before = &xml[..pos];
+    SHAPE_ELEMENTS.iter().find_map(|(element, nv)| {
+        let nv_start = before.rfind(&format!("<{}>", nv))?;
+        if !before[nv_start + nv.len() + 2..].trim().is_empty() {
+            return None;
+        }
+        let start = [format!("<{}>", element), format!("<{} ", element)]
+            .iter()
+            .filter_map(|open| before[..nv_start].rfind(open.as_str()))
+            .max()?;
+        let open_end = start + xml[start..].find('>')? + 1;
+        if !xml[open_end..nv_start].trim().is_empty() {
+            return None;
+        }
+        Some(start..element_end(xml, open_end, element)?)
+    })
+}
+
+/// End of the element opened before `from`, counting nested ones.
+fn element_end(xml: &str, from: usize, element: &str) -> Option<usize> {
+    let re_tag = Regex::new(&format!(
+        r"<(/?){}(?:\s[^>]*?)?(/?)>",
+        regex::escape(element)
+    ))
+    .unwrap();
+    let mut depth = 1;
+    for caps in re_tag.captures_iter(&xml[from..]) {
+        match (&caps[1], &caps[2]) {
+            ("/", _) => depth -= 1,
+            (_, "/") => {}
+            _ => depth += 1,
+        }
+        if depth == 0 {
+            return Some(from + caps.get(0).unwrap().end());
+        }
+    }
+    None
+}
+
+/// Replaces `<<Column>>` in a configured string with the row value.
+fn fill_tags(pattern: &str, lookup: &impl Fn(&str) -> Option<String>) -> String {
+    let re_tag = Regex::new(r"<<([^<>]+?)>>").unwrap();
+    re_tag
+        .replace_all(pattern, |caps: &regex::Captures| {
+            lookup(caps[1].trim()).unwrap_or_default()
+        })
+        .into_owned()
+}
+
+/// The image file of a rule with the row values in. Values must stay
+/// relative paths inside the template folder.
+fn image_path(
+    pattern: &str,
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<String, OnePagerError> {
+    let re_tag = Regex::new(r"<<([^<>]+?)>>").unwrap();
+    for caps in re_tag.captures_iter(pattern) {
+        let value = lookup(caps[1].trim()).unwrap_or_default();
+        let value = value.trim();
+        let relative = !value.starts_with(['/', '\\'])
+            && !value.contains(':')
+            && value
+                .split(['/', '\\'])
+                .all(|segment| segment.trim() != "..");
+        if !relative {
+            return Err(OnePagerError::config(format!(
+                "Image path from {} leaves the template folder: {}",
+                &caps[0], value
+            )));
+        }
+    }
+    Ok(fill_tags(pattern, lookup))
+}
+
+fn image_content_type(ext: &str) -> Option<&'static str> {
+    match ext {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "bmp" => Some("image/bmp"),
+        "tif" | "tiff" => Some("image/tiff"),
+        _ => None,
+    }
+}
+
+/// Adds the image as a media part related to `slide`, returns the relationship id.
+fn embed_image(package: &mut Package, slide: &str, path: &Path) -> Result<String, OnePagerError> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let content_type = image_content_type(&ext).ok_or_else(|| {
+        OnePagerError::config(format!("Unsupported image format: {}", path.display()))
+    })?;
+    let data = std::fs::read(path).map_err(|e| OnePagerError::io(path, &e))?;
+
+    let media = (1..)
+        .map(|n| format!("ppt/media/shape_image{}.{}", n, ext))
+        .find(|name| !package.contains(name))
+        .unwrap();
+    package.set(&media, data);
+    package.ensure_default(&ext, content_type);
+    Ok(package.add_relationship(slide, REL_IMAGE, &ooxml::relative_target(slide, &media)))
+}
+
+/// Points the picture fill of a shape to another image.
+fn set_embed(shape: &str, id: &str) -> String {
+    let re_blip = Regex::new(r#"(<a:blip\b[^>]*?\sr:embed=")[^"]*(")"#).unwrap();
+    re_blip
+        .replace_all(shape, |caps: &regex::Captures| {
+            format!("{}{}{}", &caps[1], id, &caps[2])
+        })
+        .into_owned()
+}
+
+/// Rebuilds the paragraphs of the `body` text body (`p:txBody` for shapes,
+/// `a:txBody` for table cells) from `text`, keeping the body and list styles
+/// and the formatting of the first paragraph and run.
+pub(crate) fn replace_text_body(element: &str, body: &str, text: &str) -> String {
+    let re_body = Regex::new(&format!(
+        r"(?s)(<{0}>.*?)(<a:p[\s>/].*)?</{0}>",
+        regex::escape(body)
+    ))
+    .unwrap();
+    let re_p_pr = Regex::new(r"(?s)<a:pPr\b(?:[^>]*?/>|.*?</a:pPr>)").unwrap();
+    let re_r_pr = Regex::new(r"(?s)<a:rPr\b(?:[^>]*?/>|.*?</a:rPr>)").unwrap();
+    let re_end_pr = Regex::new(r"(?s)<a:endParaRPr\b(?:[^>]*?/>|.*?</a:endParaRPr>)").unwrap();
+    let Some(caps) = re_body.captures(element) else {
+        return element.to_string();
+    };
+    let paragraphs = caps.get(2).map_or("", |m| m.as_str());
+    let p_pr = re_p_pr.find(paragraphs).map_or("", |m| m.as_str());
+    // Empty cells only carry the formatting of their end of paragraph
+    let r_pr = match re_r_pr.find(paragraphs) {
+        Some(m) => m.as_str().to_string(),
+        None => re_end_pr.find(paragraphs).map_or(String::new(), |m| {
+            m.as_str().replace("a:endParaRPr", "a:rPr")
+        }),
+    };
+    let paragraphs: String = text
+        .split('\n')
+        .map(|line| match line {
+            "" => format!("<a:p>{}</a:p>", p_pr),
+            line => format!(
+                "<a:p>{}<a:r>{}<a:t>{}</a:t></a:r></a:p>",
+                p_pr,
+                r_pr,
+                escape_xml(line)
+            ),
+        })
+        .collect();
+    let range = caps.get(0).unwrap().range();
+    format!(
+        "{}{}{}</{}>{}",
+        &element[..range.start],
+        &caps[1],
+        paragraphs,
+        body,
+        &element[range.end..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "Plan" => Some("Premium".to_string()),
+            "Client" => Some("ACME".to_string()),
+            _ => None,
+        }
+    }
+
+    fn slide_package(slide: &str) -> Package {
+        let mut package = Package::default();
+        package.set(
+            slides::PRESENTATION_PART,
+            br#"<p:sldIdLst><p:sldId id="256" r:id="rId2"/></p:sldIdLst>"#.to_vec(),
+        );
+        package.set(
+            "ppt/_rels/presentation.xml.rels",
+            br#"<Relationships><Relationship Id="rId2" Type="slide" Target="slides/slide1.xml"/></Relationships>"#.to_vec(),
+        );
+        package.set("ppt/slides/slide1.xml", slide.as_bytes().to_vec());
+        package
+    }
+
+    fn rule(shape: &str, condition: Option<&str>, action: ShapeAction) -> ShapeRule {
+        ShapeRule {
+            shape: shape.to_string(),
+            condition: condition.map(str::to_string),
+            action,
+            template: None,
+        }
+    }
+
+    #[test]
+    fn test_hide_delete_and_text() {
+        let slide = concat!(
+            r#"<p:spTree><p:grpSp><p:nvGrpSpPr><p:cNvPr id="2" name="Premium icons"/></p:nvGrpSpPr>"#,
+            r#"<p:grpSp><p:nvGrpSpPr><p:cNvPr id="3" name="Inner"/></p:nvGrpSpPr></p:grpSp>"#,
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="4" name="Star"/></p:nvSpPr></p:sp></p:grpSp>"#,
+            r#"<p:pic><p:nvPicPr><p:cNvPr id="5" name="Picture 5" descr="Badge"/></p:nvPicPr></p:pic>"#,
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="6" name="Intro"/></p:nvSpPr><p:txBody><a:bodyPr/>"#,
+            r#"<a:p><a:pPr algn="ctr"/><a:r><a:rPr lang="fr-FR" b="1"/><a:t>Old</a:t></a:r></a:p><a:p/></p:txBody></p:sp></p:spTree>"#
+        );
+        let mut package = slide_package(slide);
+        let rules = [
+            rule(
+                "Premium icons",
+                Some("Plan != Premium"),
+                ShapeAction::Delete,
+            ),
+            rule("Star", Some("Plan == Premium"), ShapeAction::Hide),
+            rule("Badge", None, ShapeAction::Delete),
+            rule(
+                "Intro",
+                None,
+                ShapeAction::Text {
+                    text: "Hello <<Client>>\n\nR&D".to_string(),
+                },
+            ),
+        ];
+        let rules: Vec<&ShapeRule> = rules.iter().collect();
+        apply_shape_rules(&mut package, &rules, Path::new("."), lookup).unwrap();
+        assert_eq!(
+            package.get_text("ppt/slides/slide1.xml").unwrap(),
+            concat!(
+                r#"<p:spTree><p:grpSp><p:nvGrpSpPr><p:cNvPr id="2" name="Premium icons"/></p:nvGrpSpPr>"#,
+                r#"<p:grpSp><p:nvGrpSpPr><p:cNvPr id="3" name="Inner"/></p:nvGrpSpPr></p:grpSp>"#,
+                r#"<p:sp><p:nvSpPr><p:cNvPr id="4" name="Star" hidden="1"/></p:nvSpPr></p:sp></p:grpSp>"#,
+                r#"<p:sp><p:nvSpPr><p:cNvPr id="6" name="Intro"/></p:nvSpPr><p:txBody><a:bodyPr/>"#,
+                r#"<a:p><a:pPr algn="ctr"/><a:r><a:rPr lang="fr-FR" b="1"/><a:t>Hello &lt;&lt;Client&gt;&gt;</a:t></a:r></a:p>"#,
+                r#"<a:p><a:pPr algn="ctr"/></a:p>"#,
+                r#"<a:p><a:pPr algn="ctr"/><a:r><a:rPr lang="fr-FR" b="1"/><a:t>R&amp;D</a:t></a:r></a:p></p:txBody></p:sp></p:spTree>"#
+            )
+        );
+
+        // Deleting a group takes its children along
+        let rules = [rule("Premium icons", None, ShapeAction::Delete)];
+        let rules: Vec<&ShapeRule> = rules.iter().collect();
+        apply_shape_rules(&mut package, &rules, Path::new("."), lookup).unwrap();
+        let xml = package.get_text("ppt/slides/slide1.xml").unwrap();
+        assert!(xml.starts_with(r#"<p:spTree><p:sp><p:nvSpPr><p:cNvPr id="6""#));
+
+        // Names are matched as PowerPoint shows them
+        let xml = concat!(
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="2" name="Client's logo"/></p:nvSpPr></p:sp>"#,
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="3" name="Client&apos;s logo"/></p:nvSpPr></p:sp>"#,
+            r#"<p:sp><p:nvSpPr><p:cNvPr id="4" name="R&amp;D"/></p:nvSpPr></p:sp>"#
+        );
+        assert_eq!(shape_ranges(xml, "Client's logo").len(), 2);
+        assert_eq!(shape_ranges(xml, "R&D").len(), 1);
+    }
+
+    #[test]
+    fn test_remove_animations() {
+        let effect = |id: &str| {
+            format!(
+                concat!(
+                    r#"<p:par><p:cTn fill="hold"><p:stCondLst><p:cond delay="indefinite"/></p:stCondLst><p:childTnLst>"#,
+                    r#"<p:par><p:cTn presetID="10" presetClass="entr" fill="hold"><p:childTnLst>"#,
+                    r#"<p:animEffect transition="in" filter="fade"><p:cBhvr><p:cTn dur="500"/><p:tgtEl><p:spTgt spid="{}"/></p:tgtEl></p:cBhvr></p:animEffect>"#,
+                    r#"</p:childTnLst></p:cTn></p:par></p:childTnLst></p:cTn></p:par>"#
+                ),
+                id
+            )
+        };
+        let slide = format!(
+            concat!(
+                r#"<p:sld><p:cSld/><p:timing><p:tnLst><p:par><p:cTn id="1" nodeType="tmRoot"><p:childTnLst>"#,
+                r#"<p:seq concurrent="1"><p:cTn id="2" nodeType="mainSeq"><p:childTnLst>{}{}</p:childTnLst></p:cTn>"#,
+                r#"<p:prevCondLst><p:cond evt="onPrev"><p:tgtEl><p:sldTgt/></p:tgtEl></p:cond></p:prevCondLst></p:seq>"#,
+                r#"</p:childTnLst></p:cTn></p:par></p:tnLst>"#,
+                r#"<p:bldLst><p:bldP spid="2" grpId="0"/><p:bldP spid="3" grpId="0" animBg="1"/></p:bldLst></p:timing></p:sld>"#
+            ),
+            effect("2"),
+            effect("3")
+        );
+
+        let pruned = remove_animations(&slide, &HashSet::from(["2".to_string()])).unwrap();
+        assert!(!pruned.contains(r#"spid="2""#));
+        assert_eq!(pruned.matches(r#"<p:spTgt spid="3"/>"#).count(), 1);
+        assert!(pruned.contains(r#"<p:bldLst><p:bldP spid="3" grpId="0" animBg="1"/></p:bldLst>"#));
+
+        // Without any animation left the timing goes
+        let ids = HashSet::from(["2".to_string(), "3".to_string()]);
+        assert_eq!(
+            remove_animations(&slide, &ids).unwrap(),
+            "<p:sld><p:cSld/></p:sld>"
+        );
+        assert!(remove_animations(&slide, &HashSet::from(["9".to_string()])).is_none());
+    }
+
+    #[test]
+    fn test_swap_image() {
+        let dir = std::env::temp_dir().join("one_pager_test_shape_image");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ACME.png"), b"png").unwrap();
+
+        let slide = concat!(
+            r#"<p:pic><p:nvPicPr><p:cNvPr id="2" name="Logo"/></p:nvPicPr>"#,
+            r#"<p:blipFill><a:blip r:embed="rId2"/></p:blipFill></p:pic>"#
+        );
+        let mut package = slide_package(slide);
+        package.set(
+            "ppt/slides/_rels/slide1.xml.rels",
+            br#"<Relationships><Relationship Id="rId2" Type="image" Target="../media/image1.png"/></Relationships>"#.to_vec(),
+        );
+        let rules = [rule(
+            "Logo",
+            None,
+            ShapeAction::Image {
+                image: "<<Client>>.png".to_string(),
+            },
+        )];
+        let rules: Vec<&ShapeRule> = rules.iter().collect();
+        apply_shape_rules(&mut package, &rules, &dir, lookup).unwrap();
+
+        assert!(package
+            .get_text("ppt/slides/slide1.xml")
+            .unwrap()
+            .contains(r#"<a:blip r:embed="rId3"/>"#));
+        assert_eq!(package.get("ppt/media/shape_image1.png").unwrap(), b"png");
+        let rel = &package.relationships("ppt/slides/slide1.xml")[1];
+        assert_eq!(rel.target, "../media/shape_image1.png");
+
+        let rules = [rule(
+            "Logo",
+            None,
+            ShapeAction::Image {
+                image: "Missing.png".to_string(),
+            },
+        )];
+        let rules: Vec<&ShapeRule> = rules.iter().collect();
+        assert!(apply_shape_rules(&mut package, &rules, &dir, lookup).is_err());
+
+        // Values cannot point outside the template folder
+        for value in ["../secret", "/etc/logo", "C:\\logo", "a\\..\\..\\logo"] {
+            let lookup = |_: &str| Some(value.to_string());
+            assert!(image_path("logos/<<Client>>.png", &lookup).is_err());
+        }
+        let lookup = |_: &str| Some("ACME".to_string());
+        assert_eq!(
+            image_path("logos/<<Client>>.png", &lookup).unwrap(),
+            "logos/ACME.png"
+        );
+    }
+}
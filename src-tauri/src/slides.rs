@@ -0,0 +1,285 @@
+use crate::ooxml::{self, Package};
+use crate::shapes;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+pub const PRESENTATION_PART: &str = "ppt/presentation.xml";
+
+/// Keeps a slide only when `condition` holds for the row.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SlideRule {
+    /// 1-based slide position in the template
+    pub slide: usize,
+    pub condition: String,
+    /// Restricts the rule to templates whose file name contains this text
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Evaluates `Column`, `!Column`, `Column == value` or `Column != value`.
+/// A bare column holds when its value is not empty, `0`, `false`, `no` or `non`.
+pub fn evaluate_condition(condition: &str, lookup: &impl Fn(&str) -> Option<String>) -> bool {
+    let condition = condition.trim();
+    if let Some((name, expected)) = condition.split_once("!=") {
+        return lookup(name.trim()).unwrap_or_default() != unquote(expected);
+    }
+    if let Some((name, expected)) = condition.split_once("==") {
+        return lookup(name.trim()).unwrap_or_default() == unquote(expected);
+    }
+    if let Some(name) = condition.strip_prefix('!') {
+        return !is_truthy(&lookup(name.trim()).unwrap_or_default());
+    }
+    is_truthy(&lookup(condition).unwrap_or_default())
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+pub(crate) fn is_truthy(value: &str) -> bool {
+    let lower = value.trim().to_lowercase();
+    !matches!(lower.as_str(), "" | "0" | "false" | "no" | "non")
+}
+
+/// Slide parts in presentation order.
+pub fn slide_order(package: &Package) -> Vec<String> {
+    let Some(presentation) = package.get_text(PRESENTATION_PART) else {
+        return Vec::new();
+    };
+    let rels = package.relationships(PRESENTATION_PART);
+    let re_sld_id = Regex::new(r#"<p:sldId\s[^>]*?r:id="([^"]+)"[^>]*?/>"#).unwrap();
+    re_sld_id
+        .captures_iter(&presentation)
+        .filter_map(|caps| rels.iter().find(|r| r.id == caps[1]))
+        .map(|r| ooxml::resolve_target(PRESENTATION_PART, &r.target))
+        .collect()
+}
+
+/// Drops the slides whose `<<#slide-if ...>>` markers or config rules fail,
+/// and removes the marker shapes from the kept ones.
+/// Returns the removed slide parts.
+pub fn apply_slide_rules(
+    package: &mut Package,
+    rules: &[&SlideRule],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Vec<String> {
+    let re_marker = Regex::new(r"(?:<<|&lt;&lt;)#slide-if\s+(.+?)(?:>>|&gt;&gt;)").unwrap();
+    let mut removed = Vec::new();
+
+    for (i, slide) in slide_order(package).into_iter().enumerate() {
+        let Some(xml) = package.get_text(&slide) else {
+            continue;
+        };
+
+        let mut keep = rules
+            .iter()
+            .filter(|r| r.slide == i + 1)
+            .all(|r| evaluate_condition(&r.condition, &lookup));
+        for caps in re_marker.captures_iter(&xml) {
+            let condition = caps[1]
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&");
+            keep &= evaluate_condition(&condition, &lookup);
+        }
+
+        if keep {
+            if re_marker.is_match(&xml) {
+                package.set(&slide, remove_marker_shapes(&xml, &re_marker).into_bytes());
+            }
+        } else {
+            removed.push(slide);
+        }
+    }
+
+    if !removed.is_empty() {
+        remove_slides(package, &removed);
+    }
+    removed
+}
+
+/// Removes each marker along with the `<p:sp>` holding it, and its animations.
+fn remove_marker_shapes(xml: &str, re_marker: &Regex) -> String {
+    let mut out = xml.to_string();
+    let mut ids = HashSet::new();
+    while let Some(m) = re_marker.find(&out) {
+        let before = &out[..m.start()];
+        let open = before
+            .rfind("<p:sp>")
+            .filter(|&start| before.rfind("</p:sp>").is_none_or(|end| end < start));
+        let range = match (open, out[m.end()..].find("</p:sp>")) {
+            (Some(start), Some(end)) => start..m.end() + end + "</p:sp>".len(),
+            _ => m.range(),
+        };
+        ids.extend(shapes::shape_ids(&out[range.clone()]));
+        out.replace_range(range, "");
+    }
+    shapes::remove_animations(&out, &ids).unwrap_or(out)
+}
+
+fn remove_slides(package: &mut Package, slides: &[String]) {
+    let rels = package.relationships(PRESENTATION_PART);
+    let removed_ids: HashSet<String> = rels
+        .iter()
+        .filter(|r| slides.contains(&ooxml::resolve_target(PRESENTATION_PART, &r.target)))
+        .map(|r| r.id.clone())
+        .collect();
+
+    if let Some(presentation) = package.get_text(PRESENTATION_PART) {
+        let re_sld_id =
+            Regex::new(r#"<p:sldId\s[^>]*?id="(\d+)"[^>]*?r:id="([^"]+)"[^>]*?/>"#).unwrap();
+        let mut removed_sld_ids = HashSet::new();
+        let pruned = re_sld_id.replace_all(&presentation, |caps: &regex::Captures| {
+            if removed_ids.contains(&caps[2]) {
+                removed_sld_ids.insert(caps[1].to_string());
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        });
+
+        // Sections list slides by their sldId too
+        let re_section_id = Regex::new(r#"<p14:sldId\s+id="(\d+)"\s*/>"#).unwrap();
+        let pruned = re_section_id.replace_all(&pruned, |caps: &regex::Captures| {
+            if removed_sld_ids.contains(&caps[1]) {
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        });
+        package.set(PRESENTATION_PART, pruned.into_owned().into_bytes());
+    }
+
+    // Speaker notes belong to their slide and go with it
+    let mut parts: HashSet<String> = slides.iter().cloned().collect();
+    for slide in slides {
+        for rel in package.relationships(slide) {
+            if rel.rel_type.ends_with("/notesSlide") {
+                parts.insert(ooxml::resolve_target(slide, &rel.target));
+            }
+        }
+    }
+
+    // Hyperlinks jumping to a removed slide would dangle once their
+    // relationship is gone
+    let mut jumps: Vec<(String, HashSet<String>)> = Vec::new();
+    for rels_path in package.names().filter(|n| n.ends_with(".rels")) {
+        let source = ooxml::source_part_for(rels_path);
+        if parts.contains(&source) {
+            continue;
+        }
+        let ids: HashSet<String> = package
+            .relationships(&source)
+            .into_iter()
+            .filter(|r| !r.external && slides.contains(&ooxml::resolve_target(&source, &r.target)))
+            .map(|r| r.id)
+            .collect();
+        if !ids.is_empty() && source != PRESENTATION_PART {
+            jumps.push((source, ids));
+        }
+    }
+    package.remove_parts(&parts);
+
+    let re_link = Regex::new(
+        r#"(?s)<a:(?:hlinkClick|hlinkMouseOver)\s[^>]*?r:id="([^"]*)"[^>]*?(?:/>|>.*?</a:(?:hlinkClick|hlinkMouseOver)>)"#,
+    )
+    .unwrap();
+    for (source, ids) in jumps {
+        let Some(xml) = package.get_text(&source) else {
+            continue;
+        };
+        let pruned = re_link.replace_all(&xml, |caps: &regex::Captures| {
+            if ids.contains(&caps[1]) {
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        });
+        if let std::borrow::Cow::Owned(pruned) = pruned {
+            package.set(&source, pruned.into_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ooxml::CONTENT_TYPES;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HasReviews" => Some("1".to_string()),
+            "Plan" => Some("Premium".to_string()),
+            "Empty" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_condition() {
+        assert!(evaluate_condition("HasReviews", &lookup));
+        assert!(!evaluate_condition("Empty", &lookup));
+        assert!(!evaluate_condition("Unknown", &lookup));
+        assert!(evaluate_condition("!Empty", &lookup));
+        assert!(evaluate_condition("Plan == \"Premium\"", &lookup));
+        assert!(evaluate_condition("Plan != Basic", &lookup));
+    }
+
+    #[test]
+    fn test_slides_removed_from_package() {
+        let mut package = Package::default();
+        package.set(
+            CONTENT_TYPES,
+            br#"<Types><Override PartName="/ppt/slides/slide1.xml" ContentType="s"/><Override PartName="/ppt/slides/slide2.xml" ContentType="s"/><Override PartName="/ppt/slides/slide3.xml" ContentType="s"/></Types>"#.to_vec(),
+        );
+        package.set(
+            PRESENTATION_PART,
+            br#"<p:sldIdLst><p:sldId id="256" r:id="rId2"/><p:sldId id="257" r:id="rId3"/><p:sldId id="258" r:id="rId4"/></p:sldIdLst>"#.to_vec(),
+        );
+        package.set(
+            "ppt/_rels/presentation.xml.rels",
+            br#"<Relationships><Relationship Id="rId2" Type="slide" Target="slides/slide1.xml"/><Relationship Id="rId3" Type="slide" Target="slides/slide2.xml"/><Relationship Id="rId4" Type="slide" Target="slides/slide3.xml"/></Relationships>"#.to_vec(),
+        );
+        package.set(
+            "ppt/slides/slide2.xml",
+            b"<p:sp><a:t>&lt;&lt;#slide-if Empty&gt;&gt;</a:t></p:sp>".to_vec(),
+        );
+        package.set("ppt/slides/slide3.xml", b"<p:sp/>".to_vec());
+        package.set(
+            "ppt/slides/slide1.xml",
+            br#"<p:spTree><p:sp><a:t>&lt;&lt;#slide-if HasReviews&gt;&gt;</a:t></p:sp><p:sp><a:r><a:rPr><a:hlinkClick r:id="rId2" action="ppaction://hlinksldjump"/></a:rPr><a:t>Reviews</a:t></a:r></p:sp><p:sp><a:r><a:rPr><a:hlinkClick r:id="rId3" action="ppaction://hlinksldjump"><a:snd r:embed="rId4"/></a:hlinkClick></a:rPr><a:t>Next</a:t></a:r></p:sp><p:sp><a:r><a:rPr><a:hlinkClick r:id="rId5"/></a:rPr><a:t>Site</a:t></a:r></p:sp></p:spTree>"#.to_vec(),
+        );
+        package.set(
+            "ppt/slides/_rels/slide1.xml.rels",
+            br#"<Relationships><Relationship Id="rId2" Type="slide" Target="slide2.xml"/><Relationship Id="rId3" Type="slide" Target="../slides/slide3.xml"/><Relationship Id="rId5" Type="hyperlink" Target="https://example.com" TargetMode="External"/></Relationships>"#.to_vec(),
+        );
+
+        let rule = SlideRule {
+            slide: 3,
+            condition: "Plan == Basic".to_string(),
+            template: None,
+        };
+        let removed = apply_slide_rules(&mut package, &[&rule], lookup);
+        assert_eq!(
+            removed,
+            vec!["ppt/slides/slide2.xml", "ppt/slides/slide3.xml"]
+        );
+
+        assert_eq!(
+            package.get_text("ppt/slides/slide1.xml").unwrap(),
+            r#"<p:spTree><p:sp><a:r><a:rPr></a:rPr><a:t>Reviews</a:t></a:r></p:sp><p:sp><a:r><a:rPr></a:rPr><a:t>Next</a:t></a:r></p:sp><p:sp><a:r><a:rPr><a:hlinkClick r:id="rId5"/></a:rPr><a:t>Site</a:t></a:r></p:sp></p:spTree>"#
+        );
+        assert_eq!(package.relationships("ppt/slides/slide1.xml")[0].id, "rId5");
+        assert!(!package.contains("ppt/slides/slide2.xml"));
+        assert_eq!(
+            package.get_text(PRESENTATION_PART).unwrap(),
+            r#"<p:sldIdLst><p:sldId id="256" r:id="rId2"/></p:sldIdLst>"#
+        );
+        assert_eq!(package.relationships(PRESENTATION_PART).len(), 1);
+        assert!(!package.get_text(CONTENT_TYPES).unwrap().contains("slide2"));
+    }
+}
@@ -0,0 +1,207 @@
+use crate::csv_handler::{parse_number, CleaningRules};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Run properties set on the run holding a value.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct RunStyle {
+    /// `RRGGBB` solid fill of the text
+    #[serde(default, deserialize_with = "hex_color")]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub bold: Option<bool>,
+    #[serde(default)]
+    pub italic: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StyleCondition {
+    #[default]
+    Always,
+    Negative,
+    Positive,
+    Zero,
+}
+
+/// Styles the values of a tag, e.g. negative `Delta_Rating` in red.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StyleRule {
+    pub tag: String,
+    #[serde(default)]
+    pub when: StyleCondition,
+    #[serde(flatten)]
+    pub style: RunStyle,
+}
+
+impl StyleCondition {
+    fn holds(self, value: &str, rules: &CleaningRules) -> bool {
+        if self == StyleCondition::Always {
+            return true;
+        }
+        // Deltas come as `+3`, `-12%` or with a typographic minus
+        let number = value
+            .trim()
+            .trim_end_matches('%')
+            .trim_start_matches('+')
+            .replace('\u{2212}', "-");
+        match parse_number(&number, rules) {
+            Some(n) if self == StyleCondition::Negative => n < 0.0,
+            Some(n) if self == StyleCondition::Positive => n > 0.0,
+            Some(n) => n == 0.0,
+            None => false,
+        }
+    }
+}
+
+impl RunStyle {
+    fn merge(&mut self, other: RunStyle) {
+        self.color = other.color.or(self.color.take());
+        self.bold = other.bold.or(self.bold);
+        self.italic = other.italic.or(self.italic);
+    }
+
+    /// Compact form carried inside rendered text, e.g. `FF0000;b1;i0`.
+    pub fn encode(&self) -> String {
+        let mut fields = vec![self.color.clone().unwrap_or_default()];
+        if let Some(bold) = self.bold {
+            fields.push(format!("b{}", bold as u8));
+        }
+        if let Some(italic) = self.italic {
+            fields.push(format!("i{}", italic as u8));
+        }
+        fields.join(";")
+    }
+
+    pub fn decode(encoded: &str) -> Self {
+        let mut fields = encoded.split(';');
+        let color = fields.next().filter(|c| !c.is_empty()).map(str::to_string);
+        let mut style = RunStyle {
+            color,
+            ..Default::default()
+        };
+        for field in fields {
+            match field {
+                "b1" | "b0" => style.bold = Some(field == "b1"),
+                "i1" | "i0" => style.italic = Some(field == "i1"),
+                _ => {}
+            }
+        }
+        style
+    }
+}
+
+/// `color:HEX`, `bold` or `italic`, optionally suffixed with `_if_negative`,
+/// `_if_positive` or `_if_zero`, e.g. `color_if_negative:FF0000`.
+fn parse_filter(filter: &str) -> Option<(StyleCondition, RunStyle)> {
+    let (directive, argument) = match filter.split_once(':') {
+        Some((directive, argument)) => (directive.trim(), Some(argument.trim())),
+        None => (filter.trim(), None),
+    };
+    let (property, when) = match directive.split_once("_if_") {
+        Some((property, "negative")) => (property, StyleCondition::Negative),
+        Some((property, "positive")) => (property, StyleCondition::Positive),
+        Some((property, "zero")) => (property, StyleCondition::Zero),
+        Some(_) => return None,
+        None => (directive, StyleCondition::Always),
+    };
+    let mut style = RunStyle::default();
+    match (property, argument) {
+        ("color", Some(hex)) if is_hex_color(hex) => style.color = Some(normalize_color(hex)),
+        ("bold", None) => style.bold = Some(true),
+        ("italic", None) => style.italic = Some(true),
+        _ => return None,
+    }
+    Some((when, style))
+}
+
+fn is_hex_color(value: &str) -> bool {
+    let hex = value.trim_start_matches('#');
+    hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn normalize_color(value: &str) -> String {
+    value.trim_start_matches('#').to_uppercase()
+}
+
+/// Configured colors are checked and normalized like directive ones, as
+/// they are written into the run properties as they are.
+fn hex_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let value = value.trim();
+    if !is_hex_color(value) {
+        return Err(serde::de::Error::custom(format!(
+            "invalid color \"{}\", expected RRGGBB",
+            value
+        )));
+    }
+    Ok(Some(normalize_color(value)))
+}
+
+/// Style of a tag value from the configured rules, then the tag filters,
+/// later ones overriding earlier ones. `None` when nothing applies.
+pub fn style_for(
+    name: &str,
+    value: &str,
+    filters: &[&str],
+    rules: &[StyleRule],
+    numbers: &CleaningRules,
+) -> Option<RunStyle> {
+    let configured = rules
+        .iter()
+        .filter(|r| r.tag == name)
+        .map(|r| (r.when, r.style.clone()));
+    let mut style = None;
+    for (when, rule_style) in configured.chain(filters.iter().filter_map(|f| parse_filter(f))) {
+        if when.holds(value, numbers) {
+            style
+                .get_or_insert_with(RunStyle::default)
+                .merge(rule_style);
+        }
+    }
+    style
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_for_filters_and_rules() {
+        let numbers = CleaningRules {
+            decimal_comma: true,
+            ..Default::default()
+        };
+        let filters = ["color_if_negative:ff0000", "color_if_positive:#00B050"];
+        let red = style_for("Delta", "-0,5", &filters, &[], &numbers).unwrap();
+        assert_eq!(red.color.as_deref(), Some("FF0000"));
+        let green = style_for("Delta", "+12%", &filters, &[], &numbers).unwrap();
+        assert_eq!(green.color.as_deref(), Some("00B050"));
+        assert!(style_for("Delta", "0", &filters, &[], &numbers).is_none());
+        assert!(style_for("Delta", "n.c.", &filters, &[], &numbers).is_none());
+
+        let rules: Vec<StyleRule> = serde_json::from_str(
+            r#"[{"tag": "Delta", "when": "negative", "color": "C00000", "bold": true}]"#,
+        )
+        .unwrap();
+        let style = style_for("Delta", "\u{2212}3", &["italic"], &rules, &numbers).unwrap();
+        assert_eq!(
+            style,
+            RunStyle {
+                color: Some("C00000".to_string()),
+                bold: Some(true),
+                italic: Some(true),
+            }
+        );
+        assert_eq!(RunStyle::decode(&style.encode()), style);
+
+        let rules: Vec<StyleRule> =
+            serde_json::from_str(r##"[{"tag": "Delta", "color": "#c00000"}]"##).unwrap();
+        assert_eq!(rules[0].style.color.as_deref(), Some("C00000"));
+        for color in ["red", "C00", "#C0000G"] {
+            let json = format!(r#"[{{"tag": "Delta", "color": "{}"}}]"#, color);
+            assert!(serde_json::from_str::<Vec<StyleRule>>(&json).is_err());
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::manifest::ManifestEntry;
+use crate::ooxml::Package;
+use crate::pptx_engine::escape_xml;
+use crate::table;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SummaryConfig {
+    #[serde(default)]
+    pub format: SummaryFormat,
+    /// Row columns added after the fixed ones, e.g. `["Rating", "Reviews_Q4"]`
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Optional index deck template (relative to `template_dir` or absolute).
+    /// Its table rows holding tags are repeated for every generated deck.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+const FIXED_COLUMNS: [&str; 6] = [
+    "Client", "Org ID", "Language", "Template", "Output", "Status",
+];
+
+/// One summary line per planned deck, keyed by column name.
+pub fn summary_rows(
+    config: &SummaryConfig,
+    entries: &[ManifestEntry],
+    rows: &[&HashMap<String, String>],
+) -> Vec<HashMap<String, String>> {
+    entries
+        .iter()
+        .zip(rows)
+        .map(|(entry, row)| {
+            let template = Path::new(&entry.template)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            let status = match (&entry.error, entry.issues.first()) {
+                (Some(e), _) => format!("Error: {}", e),
+                (None, Some(issue)) => format!("Needs repair: {}: {}", issue.part, issue.message),
+                (None, None) => "OK".to_string(),
+            };
+            let mut line: HashMap<String, String> = [
+                ("Client", entry.client.clone()),
+                ("Org ID", entry.org_id.clone()),
+                ("Language", entry.language.clone()),
+                ("Template", template),
+                ("Output", entry.output.clone().unwrap_or_default()),
+                ("Status", status),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+            for field in &config.fields {
+                line.insert(field.clone(), row.get(field).cloned().unwrap_or_default());
+            }
+            line
+        })
+        .collect()
+}
+
+fn columns(config: &SummaryConfig) -> Vec<String> {
+    FIXED_COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .chain(config.fields.iter().cloned())
+        .collect()
+}
+
+/// Writes `summary.csv` or `summary.xlsx` into the run folder.
+pub fn write_summary(
+    run_dir: &Path,
+    config: &SummaryConfig,
+    lines: &[HashMap<String, String>],
+) -> Result<PathBuf, String> {
+    let columns = columns(config);
+    match config.format {
+        SummaryFormat::Csv => {
+            let path = run_dir.join("summary.csv");
+            let mut wtr = csv::Writer::from_path(&path).map_err(|e| e.to_string())?;
+            wtr.write_record(&columns).map_err(|e| e.to_string())?;
+            for line in lines {
+                let record: Vec<&str> = columns
+                    .iter()
+                    .map(|c| line.get(c).map(|s| s.as_str()).unwrap_or(""))
+                    .collect();
+                wtr.write_record(&record).map_err(|e| e.to_string())?;
+            }
+            wtr.flush().map_err(|e| e.to_string())?;
+            Ok(path)
+        }
+        SummaryFormat::Xlsx => {
+            let path = run_dir.join("summary.xlsx");
+            xlsx_workbook(&columns, lines)
+                .write_to(&path)
+                .map_err(|e| e.to_string())?;
+            Ok(path)
+        }
+    }
+}
+
+/// A single-sheet workbook with a header row. Numeric values without
+/// leading zeros become number cells, everything else inline strings.
+fn xlsx_workbook(columns: &[String], lines: &[HashMap<String, String>]) -> Package {
+    let mut sheet = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        "\r\n",
+        r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#
+    ));
+    let header: Vec<&str> = columns.iter().map(|c| c.as_str()).collect();
+    sheet.push_str(&xlsx_row(1, &header));
+    for (i, line) in lines.iter().enumerate() {
+        let values: Vec<&str> = columns
+            .iter()
+            .map(|c| line.get(c).map(|s| s.as_str()).unwrap_or(""))
+            .collect();
+        sheet.push_str(&xlsx_row(i + 2, &values));
+    }
+    sheet.push_str("</sheetData></worksheet>");
+
+    let mut package = Package::default();
+    package.set(
+        crate::ooxml::CONTENT_TYPES,
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            "\r\n",
+            r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/></Types>"#
+        )
+        .as_bytes()
+        .to_vec(),
+    );
+    package.add_relationship(
+        "",
+        "http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument",
+        "xl/workbook.xml",
+    );
+    package.set(
+        "xl/workbook.xml",
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            "\r\n",
+            r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Summary" sheetId="1" r:id="rId1"/></sheets></workbook>"#
+        )
+        .as_bytes()
+        .to_vec(),
+    );
+    package.add_relationship(
+        "xl/workbook.xml",
+        "http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet",
+        "worksheets/sheet1.xml",
+    );
+    package.set("xl/worksheets/sheet1.xml", sheet.into_bytes());
+    package
+}
+
+fn xlsx_row(number: usize, values: &[&str]) -> String {
+    let mut row = format!(r#"<row r="{}">"#, number);
+    for (i, value) in values.iter().enumerate() {
+        let cell_ref = format!("{}{}", column_letter(i), number);
+        let is_number = !value.is_empty()
+            && value.parse::<f64>().is_ok_and(|f| f.is_finite())
+            && !(value.len() > 1 && value.starts_with('0') && !value.starts_with("0."));
+        if is_number {
+            row.push_str(&format!(r#"<c r="{}"><v>{}</v></c>"#, cell_ref, value));
+        } else {
+            row.push_str(&format!(
+                r#"<c r="{}" t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#,
+                cell_ref,
+                escape_xml(value)
+            ));
+        }
+    }
+    row.push_str("</row>");
+    row
+}
+
+/// 0 -> A, 25 -> Z, 26 -> AA
+fn column_letter(mut index: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push((b'A' + (index % 26) as u8) as char);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Renders the index deck: table rows with tags repeat per summary line,
+/// the remaining tags resolve against `run_values` (e.g. "Deck Count").
+pub fn render_index(
+    template: &Path,
+    output: &Path,
+    lines: &[HashMap<String, String>],
+    run_values: &HashMap<String, String>,
+    render: impl Fn(&str, &HashMap<String, String>) -> Option<String>,
+) -> Result<(), String> {
+    let mut package = Package::open(template)?;
+    for part in package.parts.iter_mut() {
+        if !part.name.starts_with("ppt/slides/") || !part.name.ends_with(".xml") {
+            continue;
+        }
+        let xml = String::from_utf8_lossy(&part.data).to_string();
+        let repeated = table::repeat_rows(&xml, lines, |row_xml, line| {
+            render(row_xml, line).unwrap_or_else(|| row_xml.to_string())
+        });
+        let xml = repeated.unwrap_or(xml);
+        let xml = render(&xml, run_values).unwrap_or(xml);
+        part.data = xml.into_bytes();
+    }
+    package.write_to(output).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_letter() {
+        assert_eq!(column_letter(0), "A");
+        assert_eq!(column_letter(25), "Z");
+        assert_eq!(column_letter(26), "AA");
+        assert_eq!(column_letter(27), "AB");
+    }
+
+    #[test]
+    fn test_xlsx_row_cell_types() {
+        let row = xlsx_row(2, &["ACME & Co", "4.5", "007", ""]);
+        assert!(row.contains(
+            r#"<c r="A2" t="inlineStr"><is><t xml:space="preserve">ACME &amp; Co</t></is></c>"#
+        ));
+        assert!(row.contains(r#"<c r="B2"><v>4.5</v></c>"#));
+        assert!(
+            row.contains(r#"<c r="C2" t="inlineStr"><is><t xml:space="preserve">007</t></is></c>"#)
+        );
+    }
+
+    #[test]
+    fn test_summary_rows_and_csv() {
+        let entry = ManifestEntry {
+            template: "/t/FR/OnePager.pptx".to_string(),
+            client: "ACME".to_string(),
+            org_id: "42".to_string(),
+            language: "FR".to_string(),
+            output: Some("ACME_42/deck.pptx".to_string()),
+            pdf: None,
+            error: None,
+            missing: Vec::new(),
+            issues: Vec::new(),
+            fitted: Vec::new(),
+        };
+        let row: HashMap<String, String> = [("Rating".to_string(), "4.5".to_string())].into();
+        let config = SummaryConfig {
+            fields: vec!["Rating".to_string()],
+            ..Default::default()
+        };
+        let lines = summary_rows(&config, &[entry], &[&row]);
+        assert_eq!(lines[0]["Template"], "OnePager.pptx");
+        assert_eq!(lines[0]["Status"], "OK");
+
+        let dir = std::env::temp_dir().join("one_pager_test_summary");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = write_summary(&dir, &config, &lines).unwrap();
+        let csv = std::fs::read_to_string(path).unwrap();
+        assert_eq!(
+            csv,
+            "Client,Org ID,Language,Template,Output,Status,Rating\nACME,42,FR,OnePager.pptx,ACME_42/deck.pptx,OK,4.5\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
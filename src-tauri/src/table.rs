@@ -0,0 +1,474 @@
+use crate::csv_handler::{read_csv_with, CleaningConfig};
+use crate::error::OnePagerError;
+use crate::lookup::normalize_key;
+use crate::ooxml::Package;
+use crate::{shapes, slides};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum TableSource {
+    /// Rows of a detail CSV whose `detail_key` column (defaults to `key`)
+    /// matches the `key` value of the deck
+    Detail {
+        path: String,
+        key: String,
+        #[serde(default)]
+        detail_key: Option<String>,
+    },
+    /// One row per column group of the deck, e.g. `Q1_*`, `Q2_*`
+    Columns { groups: Vec<String> },
+}
+
+/// Fills the native table of a graphic frame, picked by name or alt text,
+/// with one table row per data row between the header and footer rows.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TableBinding {
+    pub shape: String,
+    #[serde(flatten)]
+    pub source: TableSource,
+    /// Data column of each table column, left to right. For column groups
+    /// the part matched by `*`, or `*` alone for the group label (`Q1`).
+    pub columns: Vec<String>,
+    #[serde(default = "default_header_rows")]
+    pub header_rows: usize,
+    /// Rows kept below the data, e.g. totals
+    #[serde(default)]
+    pub footer_rows: usize,
+    /// Restricts the binding to templates whose file name contains this text
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+fn default_header_rows() -> usize {
+    1
+}
+
+/// Rows of a detail file by normalized key value.
+type DetailIndex = HashMap<String, Vec<HashMap<String, String>>>;
+
+/// Detail rows of the bindings by file and key column, loaded once per run.
+#[derive(Debug, Default)]
+pub struct Details {
+    by_source: HashMap<(String, String), DetailIndex>,
+}
+
+impl Details {
+    pub fn load(
+        bindings: &[TableBinding],
+        cleaning: &CleaningConfig,
+    ) -> Result<Self, OnePagerError> {
+        let mut details = Details::default();
+        for binding in bindings {
+            let TableSource::Detail {
+                path,
+                key,
+                detail_key,
+            } = &binding.source
+            else {
+                continue;
+            };
+            let detail_key = detail_key.as_deref().unwrap_or(key);
+            let source = (path.clone(), detail_key.to_string());
+            if details.by_source.contains_key(&source) {
+                continue;
+            }
+            let mut by_key = DetailIndex::new();
+            for row in read_csv_with(path, usize::MAX, cleaning)? {
+                let Some(value) = row.get(detail_key) else {
+                    return Err(OnePagerError::config(format!(
+                        "Table {}: column {} not found in {}",
+                        binding.shape, detail_key, path
+                    )));
+                };
+                by_key.entry(normalize_key(value)).or_default().push(row);
+            }
+            details.by_source.insert(source, by_key);
+        }
+        Ok(details)
+    }
+
+    fn rows(&self, path: &str, detail_key: &str, value: &str) -> &[HashMap<String, String>] {
+        self.by_source
+            .get(&(path.to_string(), detail_key.to_string()))
+            .and_then(|by_key| by_key.get(&normalize_key(value)))
+            .map_or(&[], |rows| rows.as_slice())
+    }
+}
+
+/// Cell values of the table rows bound for the deck.
+fn binding_rows(
+    binding: &TableBinding,
+    details: &Details,
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Vec<Vec<String>> {
+    match &binding.source {
+        TableSource::Detail {
+            path,
+            key,
+            detail_key,
+        } => {
+            let value = lookup(key).unwrap_or_default();
+            details
+                .rows(path, detail_key.as_deref().unwrap_or(key), &value)
+                .iter()
+                .map(|row| {
+                    binding
+                        .columns
+                        .iter()
+                        .map(|c| row.get(c).cloned().unwrap_or_default())
+                        .collect()
+                })
+                .collect()
+        }
+        // Groups without any value, e.g. a quarter still to come, get no row
+        TableSource::Columns { groups } => groups
+            .iter()
+            .filter_map(|group| {
+                let label = group.replace('*', "");
+                let label = label.trim_matches(|c: char| c == '_' || c == ' ' || c == '-');
+                let mut has_values = false;
+                let values = binding
+                    .columns
+                    .iter()
+                    .map(|c| match c.as_str() {
+                        "*" => label.to_string(),
+                        c => {
+                            let value = lookup(&group.replace('*', c)).unwrap_or_default();
+                            has_values |= !value.is_empty();
+                            value
+                        }
+                    })
+                    .collect();
+                has_values.then_some(values)
+            })
+            .collect(),
+    }
+}
+
+/// Fills the bound tables of every slide. Runs once tags are resolved,
+/// values are inserted as plain text.
+pub fn apply_table_bindings(
+    package: &mut Package,
+    bindings: &[&TableBinding],
+    details: &Details,
+    lookup: impl Fn(&str) -> Option<String>,
+) {
+    if bindings.is_empty() {
+        return;
+    }
+    let data: Vec<Vec<Vec<String>>> = bindings
+        .iter()
+        .map(|b| binding_rows(b, details, &lookup))
+        .collect();
+
+    for slide in slides::slide_order(package) {
+        let Some(mut xml) = package.get_text(&slide) else {
+            continue;
+        };
+        let mut changed = false;
+        for (binding, rows) in bindings.iter().zip(&data) {
+            // From the end so that earlier ranges stay valid
+            for range in shapes::shape_ranges(&xml, &binding.shape).into_iter().rev() {
+                let filled = fill_table(
+                    &xml[range.clone()],
+                    &binding.columns,
+                    rows,
+                    binding.header_rows,
+                    binding.footer_rows,
+                );
+                if let Some(filled) = filled {
+                    xml.replace_range(range, &filled);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            package.set(&slide, xml.into_bytes());
+        }
+    }
+}
+
+/// Rebuilds the data rows of the table in `frame` from copies of its first
+/// data row, or of the existing row at the same position, and sets the frame
+/// height to the sum of the row heights. `None` without a table or data row.
+fn fill_table(
+    frame: &str,
+    columns: &[String],
+    rows: &[Vec<String>],
+    header_rows: usize,
+    footer_rows: usize,
+) -> Option<String> {
+    let start = frame.find("<a:tbl>")? + "<a:tbl>".len();
+    let end = frame.rfind("</a:tbl>")?;
+    let table = &frame[start..end];
+    let ranges = row_ranges(table);
+    if ranges.len() <= header_rows + footer_rows {
+        return None;
+    }
+    let templates = &ranges[header_rows..ranges.len() - footer_rows];
+
+    let mut filled = table[..templates[0].start].to_string();
+    for (i, values) in rows.iter().enumerate() {
+        let template = &templates[i.min(templates.len() - 1)];
+        filled.push_str(&fill_row(&table[template.clone()], columns.len(), values));
+    }
+    filled.push_str(&table[templates[templates.len() - 1].end..]);
+
+    let re_height = Regex::new(r#"<a:tr\b[^>]*?\sh="(\d+)""#).unwrap();
+    let height: u64 = re_height
+        .captures_iter(&filled)
+        .filter_map(|c| c[1].parse::<u64>().ok())
+        .sum();
+    let re_ext = Regex::new(r#"(?s)(<p:xfrm\b.*?<a:ext\b[^>]*?\scy=")\d+(")"#).unwrap();
+    let head = re_ext.replace(&frame[..start], |caps: &regex::Captures| {
+        format!("{}{}{}", &caps[1], height, &caps[2])
+    });
+    Some(format!("{}{}{}", head, filled, &frame[end..]))
+}
+
+/// Sets the text of the first `count` cells of a row, merged continuation
+/// cells aside. A cell continuing the one above still takes up its column,
+/// its value is left out.
+fn fill_row(row_xml: &str, count: usize, values: &[String]) -> String {
+    let re_cell = Regex::new(r"(?s)<a:tc(?:\s[^>]*)?>.*?</a:tc>").unwrap();
+    let mut column = 0;
+    re_cell
+        .replace_all(row_xml, |caps: &regex::Captures| {
+            let cell = &caps[0];
+            let open = &cell[..cell.find('>').unwrap_or(cell.len())];
+            if open.contains("hMerge=\"1\"") || column >= count {
+                return cell.to_string();
+            }
+            if open.contains("vMerge=\"1\"") {
+                column += 1;
+                return cell.to_string();
+            }
+            let value = values.get(column).map_or("", |v| v.as_str());
+            column += 1;
+            shapes::replace_text_body(cell, "a:txBody", value)
+        })
+        .into_owned()
+}
+
+fn row_ranges(xml: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut from = 0;
+    while let Some(start) = find_row_start(&xml[from..]) {
+        let start = from + start;
+        let Some(end) = xml[start..].find("</a:tr>") else {
+            break;
+        };
+        from = start + end + "</a:tr>".len();
+        ranges.push(start..from);
+    }
+    ranges
+}
+
+/// Repeats every `<a:tr>` holding a tag once per data row, rendering the
+/// copies with `render(row_xml, data_row)`. Rows without tags (headers,
+/// totals) are kept as they are. Returns `None` when the XML has no such row.
+pub fn repeat_rows(
+    xml: &str,
+    rows: &[HashMap<String, String>],
+    render: impl Fn(&str, &HashMap<String, String>) -> String,
+) -> Option<String> {
+    let mut out = String::with_capacity(xml.len());
+    let mut rest = xml;
+    let mut changed = false;
+
+    while let Some(start) = find_row_start(rest) {
+        let Some(end) = rest[start..]
+            .find("</a:tr>")
+            .map(|e| start + e + "</a:tr>".len())
+        else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let row_xml = &rest[start..end];
+        if row_xml.contains("&lt;&lt;") {
+            for data in rows {
+                out.push_str(&render(row_xml, data));
+            }
+            changed = true;
+        } else {
+            out.push_str(row_xml);
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+
+    if changed {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+fn find_row_start(xml: &str) -> Option<usize> {
+    let mut from = 0;
+    while let Some(pos) = xml[from..].find("<a:tr") {
+        let pos = from + pos;
+        // Skip `<a:tr>` look-alikes such as `<a:trPr>`
+        if matches!(xml.as_bytes().get(pos + 5), Some(b'>') | Some(b' ')) {
+            return Some(pos);
+        }
+        from = pos + 5;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeat_rows() {
+        let xml = "<a:tbl><a:tr h=\"1\"><a:t>Client</a:t></a:tr><a:tr h=\"1\"><a:t>&lt;&lt;Client&gt;&gt;</a:t></a:tr></a:tbl>";
+        let rows: Vec<HashMap<String, String>> = ["A", "B"]
+            .iter()
+            .map(|c| [("Client".to_string(), c.to_string())].into())
+            .collect();
+        let out = repeat_rows(xml, &rows, |row_xml, data| {
+            row_xml.replace("&lt;&lt;Client&gt;&gt;", &data["Client"])
+        })
+        .unwrap();
+        assert_eq!(
+            out,
+            "<a:tbl><a:tr h=\"1\"><a:t>Client</a:t></a:tr><a:tr h=\"1\"><a:t>A</a:t></a:tr><a:tr h=\"1\"><a:t>B</a:t></a:tr></a:tbl>"
+        );
+        assert!(repeat_rows("<a:tbl/>", &rows, |x, _| x.to_string()).is_none());
+    }
+
+    fn table_slide(rows: &str) -> Package {
+        let mut package = Package::default();
+        package.set(
+            slides::PRESENTATION_PART,
+            br#"<p:sldIdLst><p:sldId id="256" r:id="rId2"/></p:sldIdLst>"#.to_vec(),
+        );
+        package.set(
+            "ppt/_rels/presentation.xml.rels",
+            br#"<Relationships><Relationship Id="rId2" Type="slide" Target="slides/slide1.xml"/></Relationships>"#.to_vec(),
+        );
+        let slide = format!(
+            r#"<p:graphicFrame><p:nvGraphicFramePr><p:cNvPr id="4" name="KPI"/></p:nvGraphicFramePr><p:xfrm><a:off x="0" y="0"/><a:ext cx="100" cy="999"/></p:xfrm><a:graphic><a:graphicData><a:tbl><a:tblGrid/>{}</a:tbl></a:graphicData></a:graphic></p:graphicFrame>"#,
+            rows
+        );
+        package.set("ppt/slides/slide1.xml", slide.into_bytes());
+        package
+    }
+
+    fn cell(text: &str) -> String {
+        format!(
+            r#"<a:tc><a:txBody><a:bodyPr/><a:p><a:r><a:rPr b="1"/><a:t>{}</a:t></a:r></a:p></a:txBody><a:tcPr/></a:tc>"#,
+            text
+        )
+    }
+
+    #[test]
+    fn test_column_groups() {
+        let header = format!(
+            r#"<a:tr h="10">{}{}</a:tr>"#,
+            cell("Quarter"),
+            cell("Rating")
+        );
+        let data = format!(r#"<a:tr h="20">{}{}</a:tr>"#, cell("x"), cell("y"));
+        let total = format!(r#"<a:tr h="30">{}{}</a:tr>"#, cell("Total"), cell("9"));
+        let mut package = table_slide(&format!("{}{}{}", header, data, total));
+
+        let binding: TableBinding = serde_json::from_str(
+            r#"{"shape": "KPI", "source": "columns", "groups": ["Q1_*", "Q2_*", "Q3_*"], "columns": ["*", "Rating"], "footer_rows": 1}"#,
+        )
+        .unwrap();
+        let lookup = |name: &str| match name {
+            "Q1_Rating" => Some("4.1".to_string()),
+            "Q2_Rating" => Some("R&D".to_string()),
+            _ => None,
+        };
+        apply_table_bindings(&mut package, &[&binding], &Details::default(), lookup);
+
+        let expected = format!(
+            r#"<a:ext cx="100" cy="80"/></p:xfrm><a:graphic><a:graphicData><a:tbl><a:tblGrid/>{}<a:tr h="20">{}{}</a:tr><a:tr h="20">{}{}</a:tr>{}</a:tbl>"#,
+            header,
+            cell("Q1"),
+            cell("4.1"),
+            cell("Q2"),
+            cell("R&amp;D"),
+            total
+        );
+        assert!(package
+            .get_text("ppt/slides/slide1.xml")
+            .unwrap()
+            .contains(&expected));
+    }
+
+    #[test]
+    fn test_detail_rows() {
+        let dir = std::env::temp_dir().join("one_pager_test_table_details");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reviews.csv");
+        std::fs::write(
+            &path,
+            "Org ID,Site,Rating\n1,Lyon,4.5\n2,Nice,3.9\n1,Paris,4.8\n",
+        )
+        .unwrap();
+        let binding = TableBinding {
+            shape: "KPI".to_string(),
+            source: TableSource::Detail {
+                path: path.to_string_lossy().to_string(),
+                key: "Org ID".to_string(),
+                detail_key: None,
+            },
+            columns: vec!["Site".to_string(), "Rating".to_string()],
+            header_rows: 0,
+            footer_rows: 0,
+            template: None,
+        };
+        let details =
+            Details::load(std::slice::from_ref(&binding), &CleaningConfig::default()).unwrap();
+
+        let empty =
+            r#"<a:tc><a:txBody><a:bodyPr/><a:p><a:endParaRPr sz="900"/></a:p></a:txBody></a:tc>"#;
+        let merged = r#"<a:tc hMerge="1"><a:txBody><a:bodyPr/><a:p/></a:txBody></a:tc>"#;
+        let below = r#"<a:tc vMerge="1"><a:txBody><a:bodyPr/><a:p/></a:txBody></a:tc>"#;
+        // Placeholder rows beyond the data are dropped
+        let row = format!(r#"<a:tr h="5">{}{}{}</a:tr>"#, empty, merged, empty);
+        let mut package = table_slide(&row.repeat(3));
+        let lookup = |name: &str| (name == "Org ID").then(|| " 1".to_string());
+        apply_table_bindings(&mut package, &[&binding], &details, lookup);
+
+        let xml = package.get_text("ppt/slides/slide1.xml").unwrap();
+        let filled = |a: &str, b: &str| {
+            format!(
+                r#"<a:tr h="5"><a:tc><a:txBody><a:bodyPr/><a:p><a:r><a:rPr sz="900"/><a:t>{}</a:t></a:r></a:p></a:txBody></a:tc>{}<a:tc><a:txBody><a:bodyPr/><a:p><a:r><a:rPr sz="900"/><a:t>{}</a:t></a:r></a:p></a:txBody></a:tc></a:tr>"#,
+                a, merged, b
+            )
+        };
+        assert!(xml.contains(&format!(
+            r#"cy="10"/></p:xfrm><a:graphic><a:graphicData><a:tbl><a:tblGrid/>{}{}</a:tbl>"#,
+            filled("Lyon", "4.5"),
+            filled("Paris", "4.8")
+        )));
+
+        // The rating column of a vertically merged row stays empty
+        let binding = TableBinding {
+            columns: vec![
+                "Org ID".to_string(),
+                "Site".to_string(),
+                "Rating".to_string(),
+            ],
+            ..binding
+        };
+        let row = format!(r#"<a:tr h="5">{}{}{}</a:tr>"#, empty, below, empty);
+        let mut package = table_slide(&row);
+        apply_table_bindings(&mut package, &[&binding], &details, lookup);
+        let xml = package.get_text("ppt/slides/slide1.xml").unwrap();
+        assert!(xml.contains(&format!(
+            r#"{}<a:tc><a:txBody><a:bodyPr/><a:p><a:r><a:rPr sz="900"/><a:t>4.5</a:t>"#,
+            below
+        )));
+        assert!(!xml.contains("Lyon"));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::docx;
+use crate::ooxml::DocumentKind;
+use crate::richtext;
+use crate::shapes;
+use crate::styles::RunStyle;
+use regex::Regex;
+use std::collections::HashSet;
+use std::ops::Range;
+
+const REMOVE_PARAGRAPH: &str = "\u{0}remove-paragraph\u{0}";
+const REMOVE_SHAPE: &str = "\u{0}remove-shape\u{0}";
+
+/// Outcome of resolving the tags of one part whose value is missing or that
+/// carry filters.
+#[derive(Debug, Default)]
+pub struct Resolved {
+    /// `None` when the part is unchanged
+    pub text: Option<String>,
+    /// Tag names whose value was empty, once per occurrence
+    pub missing: Vec<String>,
+}
+
+/// Resolves `<<Name|filter|...>>` tags and empty plain tags.
+///
+/// Filters: `default:Text` renders `Text` for an empty value, `required`
+/// removes the enclosing paragraph and `required:shape` the enclosing shape.
+/// Documents have no shapes there, `required:shape` removes the paragraph.
+/// Workbook strings have no paragraphs, both leave the string empty.
+/// `paragraphs` renders every line of the value as a paragraph and
+/// `markdown` also handles `**bold**`, `*italic*` and `- ` bullets.
+/// `style` gives the run style of a value from its name and filters.
+/// Other empty tags get `fallback` when given. Plain tags with an unstyled
+/// value are left for the regular replacement, unknown plain tags are left
+/// untouched.
+pub fn resolve(
+    xml: &str,
+    kind: DocumentKind,
+    lookup: impl Fn(&str) -> Option<String>,
+    fallback: Option<&str>,
+    style: impl Fn(&str, &str, &[&str]) -> Option<RunStyle>,
+) -> Resolved {
+    let re_tag = Regex::new(r"(?:<<|&lt;&lt;)([^<>]+?)(?:>>|&gt;&gt;)").unwrap();
+    let mut missing = Vec::new();
+    let mut removals = false;
+
+    let text = re_tag.replace_all(xml, |caps: &regex::Captures| {
+        let mut filters = caps[1].split('|');
+        let name = unescape_xml(filters.next().unwrap_or_default().trim());
+        let filters: Vec<&str> = filters.map(str::trim).collect();
+        let value = lookup(&name);
+
+        if let Some(value) = value.as_ref().filter(|v| !v.is_empty()) {
+            let style = style(&name, value, &filters);
+            if filters.is_empty() && style.is_none() {
+                return caps[0].to_string();
+            }
+            let escaped = crate::pptx_engine::escape_xml(value);
+            let escaped = if filters.contains(&"markdown") {
+                richtext::markdown(&escaped)
+            } else if filters.contains(&"paragraphs") {
+                richtext::paragraphs(&escaped)
+            } else {
+                escaped
+            };
+            return match style {
+                Some(style) => richtext::styled(&escaped, &style),
+                None => escaped,
+            };
+        }
+        if value.is_none() && filters.is_empty() {
+            return caps[0].to_string();
+        }
+
+        missing.push(name);
+        if let Some(default) = filters.iter().find_map(|f| f.strip_prefix("default:")) {
+            return default.to_string();
+        }
+        match filters.iter().find(|f| f.starts_with("required")) {
+            Some(&"required:shape") => {
+                removals = true;
+                REMOVE_SHAPE.to_string()
+            }
+            Some(_) => {
+                removals = true;
+                REMOVE_PARAGRAPH.to_string()
+            }
+            None => fallback
+                .map(crate::pptx_engine::escape_xml)
+                .unwrap_or_default(),
+        }
+    });
+
+    let mut text = text.into_owned();
+    if removals {
+        match kind {
+            DocumentKind::Presentation => {
+                let ids: HashSet<String> = remove_enclosing(&mut text, REMOVE_SHAPE, "p:sp")
+                    .iter()
+                    .flat_map(|shape| shapes::shape_ids(shape))
+                    .collect();
+                if let Some(pruned) = shapes::remove_animations(&text, &ids) {
+                    text = pruned;
+                }
+                remove_enclosing(&mut text, REMOVE_PARAGRAPH, "a:p");
+            }
+            DocumentKind::Document => {
+                text = text.replace(REMOVE_SHAPE, REMOVE_PARAGRAPH);
+                if !remove_enclosing(&mut text, REMOVE_PARAGRAPH, "w:p").is_empty() {
+                    if let Some(kept) = docx::keep_paragraphs(&text) {
+                        text = kept;
+                    }
+                }
+            }
+            DocumentKind::Workbook => {
+                text = text.replace(REMOVE_SHAPE, "").replace(REMOVE_PARAGRAPH, "");
+            }
+        }
+    }
+    Resolved {
+        text: (text != xml).then_some(text),
+        missing,
+    }
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Removes every `marker` together with the `<element>` holding it, or just
+/// the marker when it sits outside such an element. Returns the removed
+/// elements.
+fn remove_enclosing(xml: &mut String, marker: &str, element: &str) -> Vec<String> {
+    let mut removed = Vec::new();
+    while let Some(pos) = xml.find(marker) {
+        let range = match enclosing(xml, pos, element) {
+            Some(range) => {
+                removed.push(xml[range.clone()].to_string());
+                range
+            }
+            None => pos..pos + marker.len(),
+        };
+        xml.replace_range(range, "");
+    }
+    removed
+}
+
+/// Range of the innermost `<element>` around `pos`.
+fn enclosing(xml: &str, pos: usize, element: &str) -> Option<Range<usize>> {
+    let before = &xml[..pos];
+    let close = format!("</{}>", element);
+    let start = [format!("<{}>", element), format!("<{} ", element)]
+        .iter()
+        .filter_map(|open| before.rfind(open.as_str()))
+        .max()?;
+    if before[start..].contains(&close) {
+        return None;
+    }
+    let end = xml[pos..].find(&close)? + pos + close.len();
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "Rating" => Some(String::new()),
+            "Previous Rating" => Some("4.5".to_string()),
+            "Name & Co" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    fn no_style(_: &str, _: &str, _: &[&str]) -> Option<RunStyle> {
+        None
+    }
+
+    #[test]
+    fn test_default_and_fallback() {
+        let xml = "<a:t>From &lt;&lt;Rating|default:N/A&gt;&gt; to &lt;&lt;Previous Rating|default:?&gt;&gt;</a:t><a:t>&lt;&lt;Name &amp; Co&gt;&gt;&lt;&lt;Unknown&gt;&gt;</a:t>";
+        let resolved = resolve(
+            xml,
+            DocumentKind::Presentation,
+            lookup,
+            Some("n.c."),
+            no_style,
+        );
+        assert_eq!(
+            resolved.text.unwrap(),
+            "<a:t>From N/A to 4.5</a:t><a:t>n.c.&lt;&lt;Unknown&gt;&gt;</a:t>"
+        );
+        assert_eq!(resolved.missing, vec!["Rating", "Name & Co"]);
+
+        let plain = "<a:t>&lt;&lt;Previous Rating&gt;&gt;</a:t>";
+        assert!(resolve(
+            plain,
+            DocumentKind::Presentation,
+            lookup,
+            Some("n.c."),
+            no_style
+        )
+        .text
+        .is_none());
+    }
+
+    #[test]
+    fn test_required_removes_paragraph_or_shape() {
+        let xml = concat!(
+            "<p:sp><p:txBody><a:p><a:pPr/><a:r><a:t>Rating: &lt;&lt;Rating|required&gt;&gt;</a:t></a:r></a:p>",
+            "<a:p><a:r><a:t>Kept</a:t></a:r></a:p></p:txBody></p:sp>",
+            "<p:sp><p:nvSpPr><p:cNvPr id=\"5\" name=\"Rating\"/></p:nvSpPr><a:p><a:r><a:t>&lt;&lt;Missing|required:shape&gt;&gt;</a:t></a:r></a:p></p:sp>",
+            "<p:timing><p:bldLst><p:bldP spid=\"5\" grpId=\"0\"/></p:bldLst></p:timing>"
+        );
+        let resolved = resolve(xml, DocumentKind::Presentation, lookup, None, no_style);
+        assert_eq!(
+            resolved.text.unwrap(),
+            "<p:sp><p:txBody><a:p><a:r><a:t>Kept</a:t></a:r></a:p></p:txBody></p:sp>"
+        );
+        assert_eq!(resolved.missing, vec!["Rating", "Missing"]);
+    }
+
+    #[test]
+    fn test_styled_plain_tag() {
+        let style = |name: &str, _: &str, _: &[&str]| {
+            (name == "Previous Rating").then(|| RunStyle {
+                bold: Some(true),
+                ..Default::default()
+            })
+        };
+        let xml = "<a:t>&lt;&lt;Previous Rating&gt;&gt;</a:t>";
+        assert_eq!(
+            resolve(xml, DocumentKind::Presentation, lookup, None, style)
+                .text
+                .unwrap(),
+            "<a:t>\u{5};b1\u{6}4.5\u{7}</a:t>"
+        );
+    }
+
+    #[test]
+    fn test_paragraph_filters() {
+        let lookup = |_: &str| Some("**A** & B\nC".to_string());
+        let xml = "<a:t>&lt;&lt;X|markdown&gt;&gt;</a:t><a:t>&lt;&lt;X|paragraphs&gt;&gt;</a:t>";
+        assert_eq!(
+            resolve(xml, DocumentKind::Presentation, lookup, None, no_style)
+                .text
+                .unwrap(),
+            "<a:t>\u{3}A\u{3} &amp; B\u{1}C</a:t><a:t>**A** &amp; B\u{1}C</a:t>"
+        );
+    }
+}
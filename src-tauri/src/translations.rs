@@ -0,0 +1,137 @@
+use crate::docx;
+use crate::error::{OnePagerError, TagError};
+use crate::ooxml::{self, Package};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const TAG_PATTERN: &str = r"(?:<<|&lt;&lt;)t:([^<>]+?)(?:>>|&gt;&gt;)";
+
+/// Label dictionaries by language code, read from `<dir>/<lang>.json` files
+/// holding flat `{"key": "text"}` objects.
+#[derive(Debug, Clone, Default)]
+pub struct Translations {
+    dictionaries: HashMap<String, HashMap<String, String>>,
+}
+
+impl Translations {
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, OnePagerError> {
+        let dir = dir.as_ref();
+        let mut dictionaries = HashMap::new();
+        for entry in fs::read_dir(dir).map_err(|e| OnePagerError::io(dir, &e))? {
+            let path = entry.map_err(|e| OnePagerError::io(dir, &e))?.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            let lang = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_uppercase();
+            let text = fs::read_to_string(&path).map_err(|e| OnePagerError::io(&path, &e))?;
+            let dictionary: HashMap<String, String> = serde_json::from_str(&text).map_err(|e| {
+                OnePagerError::config(format!("Invalid dictionary {:?}: {}", path, e))
+            })?;
+            dictionaries.insert(lang, dictionary);
+        }
+        Ok(Translations { dictionaries })
+    }
+
+    pub fn has_language(&self, lang: &str) -> bool {
+        self.dictionaries.contains_key(&lang.to_uppercase())
+    }
+
+    /// Replaces `<<t:key>>` tags with the text of `lang`. The text may hold
+    /// row tags of its own, they are resolved afterwards.
+    /// Returns `None` when the part has no translation tag.
+    pub fn resolve(&self, xml: &str, lang: &str) -> Result<Option<String>, TagError> {
+        let re_tag = Regex::new(TAG_PATTERN).unwrap();
+        let Some(first) = re_tag.captures(xml) else {
+            return Ok(None);
+        };
+        let tag = |key: &str| format!("<<t:{}>>", key.trim().replace("&amp;", "&"));
+        let dictionary = self
+            .dictionaries
+            .get(&lang.to_uppercase())
+            .ok_or_else(|| TagError {
+                tag: tag(&first[1]),
+                message: format!("No translation dictionary for {}", lang),
+            })?;
+
+        let mut missing = None;
+        let text = re_tag.replace_all(xml, |caps: &regex::Captures| {
+            let key = caps[1].trim().replace("&amp;", "&");
+            match dictionary.get(&key) {
+                Some(text) => crate::pptx_engine::escape_xml(text),
+                None => {
+                    missing.get_or_insert(key);
+                    caps[0].to_string()
+                }
+            }
+        });
+        match missing {
+            Some(key) => Err(TagError {
+                tag: tag(&key),
+                message: format!("Missing translation for {}", lang),
+            }),
+            None => Ok(Some(text.into_owned())),
+        }
+    }
+}
+
+/// Whether a template holds `<<t:key>>` tags, including tags split across
+/// Word runs.
+pub fn holds_tags(package: &Package) -> bool {
+    let re_tag = Regex::new(TAG_PATTERN).unwrap();
+    package
+        .names()
+        .filter(|name| ooxml::is_xml_part(name))
+        .filter_map(|name| package.get_text(name))
+        .any(|xml| re_tag.is_match(&docx::join_split_tags(&xml).unwrap_or(xml)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_translations() {
+        let dir = std::env::temp_dir().join("onepager_translations_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("fr.json"),
+            r#"{"rating": "Note moyenne", "hello": "Bonjour <<Nom du client>>"}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("en.json"), r#"{"rating": "Average rating"}"#).unwrap();
+
+        let translations = Translations::load(&dir).unwrap();
+        assert!(translations.has_language("fr"));
+        let xml = "<a:t>&lt;&lt;t:rating&gt;&gt;</a:t><a:t>&lt;&lt;t:hello&gt;&gt;</a:t>";
+        assert_eq!(
+            translations.resolve(xml, "FR").unwrap().unwrap(),
+            "<a:t>Note moyenne</a:t><a:t>Bonjour &lt;&lt;Nom du client&gt;&gt;</a:t>"
+        );
+        assert_eq!(
+            translations.resolve(xml, "EN").unwrap_err(),
+            TagError {
+                tag: "<<t:hello>>".to_string(),
+                message: "Missing translation for EN".to_string()
+            }
+        );
+        assert!(translations.resolve(xml, "DE").is_err());
+        assert!(translations.resolve("<a:t/>", "DE").unwrap().is_none());
+
+        let mut package = Package::default();
+        package.set("ppt/slides/slide1.xml", b"<a:t>Rating</a:t>".to_vec());
+        assert!(!holds_tags(&package));
+        package.set(
+            "word/document.xml",
+            b"<w:r><w:t>&lt;&lt;t:</w:t></w:r><w:r><w:t>rating&gt;&gt;</w:t></w:r>".to_vec(),
+        );
+        assert!(holds_tags(&package));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
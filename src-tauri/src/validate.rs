@@ -0,0 +1,225 @@
+use crate::ooxml::{self, Package, CONTENT_TYPES};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A structural problem of a written package, the kind PowerPoint reports
+/// as "needs repair".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageIssue {
+    pub part: String,
+    pub message: String,
+}
+
+/// An output deck that failed validation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationFailure {
+    pub output: String,
+    pub issues: Vec<PackageIssue>,
+}
+
+/// Re-opens a written package and checks it.
+pub fn validate_file(path: &Path) -> Vec<PackageIssue> {
+    match Package::open(path) {
+        Ok(package) => validate_package(&package),
+        Err(e) => vec![PackageIssue {
+            part: String::new(),
+            message: e.to_string(),
+        }],
+    }
+}
+
+/// Checks that every XML part is well-formed with legal XML 1.0 characters,
+/// that every part has a content type and that internal relationship
+/// targets exist.
+pub fn validate_package(package: &Package) -> Vec<PackageIssue> {
+    let mut issues = Vec::new();
+    let issue = |part: &str, message: String| PackageIssue {
+        part: part.to_string(),
+        message,
+    };
+
+    if !package.contains(CONTENT_TYPES) {
+        issues.push(issue(CONTENT_TYPES, "Missing part".to_string()));
+    }
+    for part in &package.parts {
+        if !ooxml::is_xml_part(&part.name) {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&part.data);
+        if let Err(message) = check_xml(&text) {
+            issues.push(issue(&part.name, message));
+        }
+    }
+    if !issues.is_empty() {
+        // Content types and relationships can't be trusted past a broken part
+        return issues;
+    }
+
+    for name in package.names() {
+        if name == CONTENT_TYPES || name.ends_with('/') {
+            continue;
+        }
+        if package.content_type(name).is_none() {
+            issues.push(issue(name, "No content type declared".to_string()));
+        }
+    }
+
+    for part in package.parts.iter().filter(|p| p.name.ends_with(".rels")) {
+        let source = ooxml::source_part_for(&part.name);
+        let xml = String::from_utf8_lossy(&part.data);
+        for rel in ooxml::parse_relationships(&xml) {
+            if rel.external {
+                continue;
+            }
+            let target = ooxml::resolve_target(&source, &rel.target);
+            if !package.contains(&target) {
+                issues.push(issue(
+                    &part.name,
+                    format!("{} targets missing part {}", rel.id, target),
+                ));
+            }
+        }
+    }
+    issues
+}
+
+/// Well-formedness of one part, including entity and character references.
+pub fn check_xml(text: &str) -> Result<(), String> {
+    let mut reader = Reader::from_str(text);
+    let mut depth = 0usize;
+    let mut roots = 0usize;
+    loop {
+        let position = reader.buffer_position();
+        let at = |message: String| format!("{} (offset {})", message, position);
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("{} (offset {})", e, reader.error_position()))?;
+        match event {
+            Event::Start(e) => {
+                check_element(&e).map_err(at)?;
+                if depth == 0 {
+                    roots += 1;
+                }
+                depth += 1;
+            }
+            Event::Empty(e) => {
+                check_element(&e).map_err(at)?;
+                if depth == 0 {
+                    roots += 1;
+                }
+            }
+            Event::End(_) => depth -= 1,
+            Event::Text(e) => {
+                let text = e.unescape().map_err(|e| at(e.to_string()))?;
+                if depth == 0 && !text.trim().is_empty() {
+                    return Err(at("Text outside the root element".to_string()));
+                }
+                check_chars(&text).map_err(at)?;
+            }
+            Event::CData(e) => {
+                check_chars(&String::from_utf8_lossy(&e)).map_err(at)?;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    match (depth, roots) {
+        (0, 1) => Ok(()),
+        (0, 0) => Err("No root element".to_string()),
+        (0, _) => Err("Several root elements".to_string()),
+        _ => Err("Unclosed element at end of part".to_string()),
+    }
+}
+
+fn check_element(e: &BytesStart) -> Result<(), String> {
+    for attr in e.attributes() {
+        let attr = attr.map_err(|e| e.to_string())?;
+        let value = attr.unescape_value().map_err(|e| e.to_string())?;
+        check_chars(&value)?;
+    }
+    Ok(())
+}
+
+fn check_chars(text: &str) -> Result<(), String> {
+    match text.chars().find(|c| !is_xml_char(*c)) {
+        Some(c) => Err(format!("Illegal XML character U+{:04X}", c as u32)),
+        None => Ok(()),
+    }
+}
+
+/// `Char` production of XML 1.0. Surrogates are not `char`s, the CSV
+/// reader replaces encoded ones with U+FFFD.
+pub fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..='\u{10FFFF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deck(slide: &str) -> Package {
+        let mut package = Package::default();
+        package.set(
+            CONTENT_TYPES,
+            concat!(
+                r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+                r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+                r#"<Override PartName="/ppt/presentation.xml" ContentType="application/vnd.openxmlformats-officedocument.presentationml.presentation.main+xml"/>"#,
+                r#"<Override PartName="/ppt/slides/slide1.xml" ContentType="application/vnd.openxmlformats-officedocument.presentationml.slide+xml"/>"#,
+                r#"</Types>"#
+            )
+            .as_bytes()
+            .to_vec(),
+        );
+        package.set("ppt/presentation.xml", b"<p:presentation/>".to_vec());
+        package.add_relationship(
+            "ppt/presentation.xml",
+            "http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide",
+            "slides/slide1.xml",
+        );
+        package.set("ppt/slides/slide1.xml", slide.as_bytes().to_vec());
+        package
+    }
+
+    #[test]
+    fn test_check_xml() {
+        assert!(check_xml(r#"<?xml version="1.0"?><a:p x="1">A &amp; B</a:p>"#).is_ok());
+        assert!(check_xml("<a:p><a:t>x</a:p>").is_err());
+        assert!(check_xml("<a:p><a:t>x</a:t>").is_err());
+        assert!(check_xml("<a:t>a &b c</a:t>").is_err());
+        assert!(check_xml(r#"<a:t descr="A "B"">x</a:t>"#).is_err());
+        let err = check_xml("<a:t>Caf\u{1A}</a:t>").unwrap_err();
+        assert!(err.starts_with("Illegal XML character U+001A"));
+        assert!(check_xml("<a:t>&#x1A;</a:t>").is_err());
+    }
+
+    #[test]
+    fn test_validate_package() {
+        assert!(validate_package(&deck("<p:sld><a:t>Fine</a:t></p:sld>")).is_empty());
+
+        let issues = validate_package(&deck("<p:sld><a:t>Broken</p:sld>"));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].part, "ppt/slides/slide1.xml");
+
+        let mut package = deck("<p:sld/>");
+        package.add_relationship(
+            "ppt/slides/slide1.xml",
+            "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image",
+            "../media/image1.png",
+        );
+        package.set("ppt/media/image2.png", vec![0]);
+        let messages: Vec<String> = validate_package(&package)
+            .into_iter()
+            .map(|i| format!("{}: {}", i.part, i.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "ppt/media/image2.png: No content type declared",
+                "ppt/slides/_rels/slide1.xml.rels: rId1 targets missing part ppt/media/image1.png",
+            ]
+        );
+    }
+}
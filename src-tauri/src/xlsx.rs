@@ -0,0 +1,250 @@
+use crate::csv_handler::{parse_number, CleaningRules};
+use crate::ooxml::Package;
+use crate::richtext::{self, Piece};
+use crate::styles::RunStyle;
+use regex::Regex;
+
+const SHARED_STRINGS: &str = "xl/sharedStrings.xml";
+
+/// Children of a rich text `<rPr>` in schema order
+const RUN_PROPERTIES: [&str; 15] = [
+    "rFont",
+    "charset",
+    "family",
+    "b",
+    "i",
+    "strike",
+    "outline",
+    "shadow",
+    "condense",
+    "extend",
+    "color",
+    "sz",
+    "u",
+    "vertAlign",
+    "scheme",
+];
+
+/// A value written as a number cell: parses as a number and is not a code
+/// with leading zeros such as `00123`.
+pub fn as_number(value: &str, rules: &CleaningRules) -> Option<f64> {
+    let trimmed = value.trim().trim_start_matches(['-', '+']);
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if trimmed.starts_with('0') && digits > 1 {
+        return None;
+    }
+    parse_number(value, rules)
+}
+
+/// Text of a string item or inline string, its runs concatenated.
+fn string_text(xml: &str) -> String {
+    let re_t = Regex::new(r"<t(?:\s[^>]*)?>([^<]*)</t>").unwrap();
+    re_t.captures_iter(xml).map(|c| c[1].to_string()).collect()
+}
+
+/// Turns the cells whose whole text is a single `<<tag>>` with a numeric
+/// value into number cells, so that they sum and format in Excel. `number`
+/// gives the value of a tag name when it is a number. Other cells are left
+/// to the tag resolution of the strings.
+pub fn numeric_cells(package: &mut Package, number: impl Fn(&str) -> Option<f64>) {
+    let re_si = Regex::new(r"(?s)<si>(.*?)</si>|<si/>").unwrap();
+    let shared: Vec<String> = package
+        .get_text(SHARED_STRINGS)
+        .map(|xml| {
+            re_si
+                .captures_iter(&xml)
+                .map(|c| c.get(1).map_or(String::new(), |m| string_text(m.as_str())))
+                .collect()
+        })
+        .unwrap_or_default();
+    let re_cell = Regex::new(r"(?s)<c((?:\s[^>]*?[^/])?)>(.*?)</c>").unwrap();
+    let re_type = Regex::new(r#"\st="([^"]*)""#).unwrap();
+    let re_value = Regex::new(r"<v>([^<]*)</v>").unwrap();
+    let re_is = Regex::new(r"(?s)<is>(.*?)</is>").unwrap();
+    let re_tag = Regex::new(r"^(?:<<|&lt;&lt;)([^<>|]+?)(?:>>|&gt;&gt;)$").unwrap();
+
+    let sheets: Vec<String> = package
+        .names()
+        .filter(|n| n.starts_with("xl/worksheets/sheet") && n.ends_with(".xml"))
+        .map(str::to_string)
+        .collect();
+    for sheet in sheets {
+        let Some(xml) = package.get_text(&sheet) else {
+            continue;
+        };
+        let replaced = re_cell.replace_all(&xml, |caps: &regex::Captures| {
+            let attrs = &caps[1];
+            let text = match re_type.captures(attrs).as_ref().map(|t| &t[1]) {
+                Some("s") => re_value
+                    .captures(&caps[2])
+                    .and_then(|v| v[1].parse::<usize>().ok())
+                    .and_then(|i| shared.get(i).cloned()),
+                Some("inlineStr") => re_is.captures(&caps[2]).map(|is| string_text(&is[1])),
+                _ => None,
+            };
+            let value = text
+                .and_then(|text| {
+                    re_tag
+                        .captures(text.trim())
+                        .map(|t| unescape_xml(t[1].trim()))
+                })
+                .and_then(|name| number(&name));
+            match value {
+                Some(value) => format!("<c{}><v>{}</v></c>", re_type.replace(attrs, ""), value),
+                None => caps[0].to_string(),
+            }
+        });
+        if let std::borrow::Cow::Owned(replaced) = replaced {
+            package.set(&sheet, replaced.into_bytes());
+        }
+    }
+}
+
+/// Rewrites the shared and inline strings holding values marked by the
+/// `paragraphs`, `markdown` and style tag filters as rich text: cells have
+/// no paragraphs, so these become line breaks and bullets a `• ` prefix,
+/// emphasized or styled pieces become runs of their own. Returns `None`
+/// without markers.
+pub fn rich_text(xml: &str) -> Option<String> {
+    if !richtext::holds_markers(xml) {
+        return None;
+    }
+    let re_string = Regex::new(r"(?s)<(si|is)>(.*?)</(?:si|is)>").unwrap();
+    let text = re_string.replace_all(xml, |caps: &regex::Captures| {
+        if richtext::holds_markers(&caps[2]) {
+            format!("<{0}>{1}</{0}>", &caps[1], rewrite_string(&caps[2]))
+        } else {
+            caps[0].to_string()
+        }
+    });
+    Some(richtext::strip_markers(&text))
+}
+
+fn rewrite_string(xml: &str) -> String {
+    // Runs, or the text of a plain string
+    let re_run = Regex::new(
+        r"(?s)<r>\s*(<rPr\b(?:[^>]*?/>|.*?</rPr>))?\s*<t(?:\s[^>]*)?>([^<]*)</t>\s*</r>|^\s*<t(?:\s[^>]*)?>([^<]*)</t>",
+    )
+    .unwrap();
+    let mut out = String::with_capacity(xml.len());
+    let mut last = 0;
+    let mut line_start = true;
+    for run in re_run.captures_iter(xml) {
+        let whole = run.get(0).unwrap();
+        out.push_str(&xml[last..whole.start()]);
+        last = whole.end();
+        let r_pr = run.get(1).map_or("", |m| m.as_str());
+        let text = run.get(2).or(run.get(3)).map_or("", |m| m.as_str());
+        for piece in richtext::pieces(text) {
+            let (text, r_pr) = match piece {
+                Piece::Text(text, style) => (text, run_properties(r_pr, &style)),
+                Piece::Paragraph => ("\n".to_string(), r_pr.to_string()),
+                Piece::Bullet if line_start => ("• ".to_string(), r_pr.to_string()),
+                Piece::Bullet => ("\n• ".to_string(), r_pr.to_string()),
+            };
+            line_start = text.ends_with('\n');
+            out.push_str(&format!(
+                r#"<r>{}<t xml:space="preserve">{}</t></r>"#,
+                r_pr, text
+            ));
+        }
+    }
+    out.push_str(&xml[last..]);
+    out
+}
+
+/// Run properties with the style applied.
+fn run_properties(r_pr: &str, style: &RunStyle) -> String {
+    if style.bold.is_none() && style.italic.is_none() && style.color.is_none() {
+        return r_pr.to_string();
+    }
+    let mut r_pr = if r_pr.is_empty() {
+        "<rPr/>".to_string()
+    } else {
+        r_pr.to_string()
+    };
+    for (set, name) in [(style.bold, "b"), (style.italic, "i")] {
+        let child = match set {
+            Some(true) => format!("<{}/>", name),
+            Some(false) => format!(r#"<{} val="0"/>"#, name),
+            None => continue,
+        };
+        r_pr = richtext::set_child(&r_pr, name, &child, &RUN_PROPERTIES);
+    }
+    if let Some(color) = &style.color {
+        let child = format!(r#"<color rgb="FF{}"/>"#, color);
+        r_pr = richtext::set_child(&r_pr, "color", &child, &RUN_PROPERTIES);
+    }
+    r_pr
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numeric_cells() {
+        let mut package = Package::default();
+        package.set(
+            SHARED_STRINGS,
+            br#"<sst><si><t>Rating</t></si><si><t>&lt;&lt;Rating&gt;&gt;</t></si><si><r><t>&lt;&lt;Org </t></r><r><t>ID&gt;&gt;</t></r></si><si><t>&lt;&lt;Client&gt;&gt;</t></si></sst>"#.to_vec(),
+        );
+        package.set(
+            "xl/worksheets/sheet1.xml",
+            br#"<sheetData><row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" s="3" t="s"><v>1</v></c><c r="C1" t="s"><v>2</v></c><c r="D1" t="s"><v>3</v></c><c r="E1" t="inlineStr"><is><t>&lt;&lt;Rating&gt;&gt;</t></is></c><c r="F1"><v>7</v></c></row></sheetData>"#.to_vec(),
+        );
+        let rules = CleaningRules {
+            decimal_comma: true,
+            ..Default::default()
+        };
+        numeric_cells(&mut package, |name| match name {
+            "Rating" => as_number("4,5", &rules),
+            "Org ID" => as_number("00123", &rules),
+            _ => as_number("ACME", &rules),
+        });
+        assert_eq!(
+            package.get_text("xl/worksheets/sheet1.xml").unwrap(),
+            r#"<sheetData><row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" s="3"><v>4.5</v></c><c r="C1" t="s"><v>2</v></c><c r="D1" t="s"><v>3</v></c><c r="E1"><v>4.5</v></c><c r="F1"><v>7</v></c></row></sheetData>"#
+        );
+        assert_eq!(as_number("-12", &rules), Some(-12.0));
+        assert_eq!(as_number("0,5", &rules), Some(0.5));
+    }
+
+    #[test]
+    fn test_rich_text() {
+        let style = RunStyle {
+            color: Some("FF0000".to_string()),
+            ..Default::default()
+        };
+        let value = format!(
+            "{}{}",
+            richtext::markdown("**Top** rated\n- Fast"),
+            richtext::styled(" -12%", &style)
+        );
+        let xml = format!(
+            r#"<sst><si><t>{}</t><phoneticPr fontId="1"/></si><si><r><rPr><sz val="9"/><rFont val="Arial"/></rPr><t>Up {}</t></r></si><si><t>Plain</t></si></sst>"#,
+            value,
+            richtext::paragraphs("1 rue\nParis")
+        );
+        assert_eq!(
+            rich_text(&xml).unwrap(),
+            concat!(
+                r#"<sst><si><r><rPr><b/></rPr><t xml:space="preserve">Top</t></r><r><t xml:space="preserve"> rated</t></r>"#,
+                "<r><t xml:space=\"preserve\">\n</t></r><r><t xml:space=\"preserve\">• </t></r><r><t xml:space=\"preserve\">Fast</t></r>",
+                r#"<r><rPr><color rgb="FFFF0000"/></rPr><t xml:space="preserve"> -12%</t></r><phoneticPr fontId="1"/></si>"#,
+                r#"<si><r><rPr><sz val="9"/><rFont val="Arial"/></rPr><t xml:space="preserve">Up 1 rue</t></r>"#,
+                "<r><rPr><sz val=\"9\"/><rFont val=\"Arial\"/></rPr><t xml:space=\"preserve\">\n</t></r>",
+                r#"<r><rPr><sz val="9"/><rFont val="Arial"/></rPr><t xml:space="preserve">Paris</t></r></si><si><t>Plain</t></si></sst>"#
+            )
+        );
+        assert!(rich_text("<sst><si><t>Plain</t></si></sst>").is_none());
+    }
+}
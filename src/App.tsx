@@ -20,17 +20,111 @@ const DEFAULT_MAPPINGS = {
   "#reviewsTopPOI2": "<<#reviewsTopPOINotes2>>",
 };
 
+interface ValidationIssue {
+  row: number;
+  column: string;
+  value: string;
+  message: string;
+}
+
+interface CsvReport {
+  valid: boolean;
+  delimiter: string;
+  encoding: string;
+  headers: string[];
+  duplicate_headers: string[];
+  row_count: number;
+  ragged_rows: { row: number; fields: number; expected: number }[];
+  missing_key_columns: string[];
+  empty_keys: { row: number; column: string }[];
+  duplicate_keys: { column: string; value: string; rows: number[] }[];
+  fill_rates: { column: string; filled: number; rate: number }[];
+  issues: ValidationIssue[];
+}
+
+const formatIssue = (i: ValidationIssue) =>
+  i.row > 0 ? `Row ${i.row}, ${i.column}: ${i.message} ("${i.value}")` : `${i.column ? i.column + ': ' : ''}${i.message}`;
+
+const reportWarnings = (r: CsvReport) => [
+  ...r.duplicate_headers.map(h => `Duplicate header: ${h}`),
+  ...r.missing_key_columns.map(c => `Missing key column: ${c}`),
+  ...r.ragged_rows.map(x => `Row ${x.row}: ${x.fields} fields, expected ${x.expected}`),
+  ...r.empty_keys.map(x => `Row ${x.row}: empty ${x.column}`),
+  ...r.duplicate_keys.map(x => `Duplicate ${x.column} "${x.value}" on rows ${x.rows.join(', ')}`),
+  ...r.issues.map(formatIssue),
+];
+
+function CsvReportSummary({ report }: { report?: CsvReport }) {
+  if (!report) return null;
+  const warnings = reportWarnings(report);
+  const sparse = report.fill_rates.filter(f => f.rate < 0.5);
+  return (
+    <div className="px-1 text-[11px] font-mono text-zinc-500 space-y-0.5">
+      <div>{report.row_count} rows · {report.headers.length} columns · "{report.delimiter}" · {report.encoding}</div>
+      {sparse.length > 0 && (
+        <div className="text-zinc-600">Mostly empty: {sparse.map(f => `${f.column} (${Math.round(f.rate * 100)}%)`).join(', ')}</div>
+      )}
+      {warnings.slice(0, 3).map((w, i) => <div key={i} className="text-amber-500">{w}</div>)}
+      {warnings.length > 3 && <div className="text-amber-500">+{warnings.length - 3} more</div>}
+    </div>
+  );
+}
+
+type OnePagerError =
+  | { code: 'io'; path: string; kind: 'not_found' | 'permission_denied' | 'locked' | 'other'; message: string }
+  | { code: 'csv'; path: string; line: number | null; column: number | null; message: string }
+  | { code: 'zip' | 'template'; path: string; message: string }
+  | { code: 'xml'; path: string; part: string; message: string }
+  | { code: 'mapping'; path: string; part: string; tag: string; message: string }
+  | { code: 'config' | 'output'; message: string };
+
+const fileName = (path: string) => path.split(/[\\/]/).pop() || path;
+
+const describeError = (e: unknown): string => {
+  if (typeof e !== 'object' || e === null || !('code' in e)) return `Error: ${e}`;
+  const err = e as OnePagerError;
+  switch (err.code) {
+    case 'io':
+      if (err.kind === 'locked') return `${fileName(err.path)} is open in another program. Close it and retry.`;
+      if (err.kind === 'not_found') return `${fileName(err.path)} not found. Check the selected path.`;
+      if (err.kind === 'permission_denied') return `No permission to access ${fileName(err.path)}.`;
+      return `${fileName(err.path)}: ${err.message}`;
+    case 'csv':
+      return `${fileName(err.path)}${err.line ? `, line ${err.line}` : ''}${err.column ? `, column ${err.column}` : ''}: ${err.message}`;
+    case 'zip':
+      return `${fileName(err.path)} is not a valid Office file, re-save it from PowerPoint, Word or Excel: ${err.message}`;
+    case 'xml':
+      return `${fileName(err.path)} (${err.part}): ${err.message}`;
+    case 'template':
+      return `Template ${fileName(err.path)}: ${err.message}`;
+    case 'mapping':
+      return `${fileName(err.path)} (${err.part}), ${err.tag}: ${err.message}`;
+    default:
+      return `Error: ${err.message}`;
+  }
+};
+
 interface GenStats {
   total_files: number;
   total_time_secs: number;
   success_count: number;
   error_count: number;
+  missing_values?: Record<string, number>;
+  unmatched_lookup_keys?: Record<string, string[]>;
+  validation_failures?: { output: string; issues: { part: string; message: string }[] }[];
+  fitted_shapes?: number;
+  unchecked_shapes?: number;
+  skipped_compositions?: string[];
+  deck_errors?: { template: string; org_id: string; language: string; error: OnePagerError }[];
 }
 
 function App() {
   const [config, setConfig] = useState({
     standardCsv: "",
     prevYearCsv: "",
+    schemaPath: "",
+    translationsDir: "",
+    projectFile: "",
     templateDir: "",
     outputDir: "",
     languages: { fr: true, en: true, de: true, it: true, es: true },
@@ -53,25 +147,41 @@ function App() {
     template: 'idle' as 'idle' | 'checking' | 'valid' | 'invalid',
   });
 
+  const [csvReports, setCsvReports] = useState<{ standard?: CsvReport; prevYear?: CsvReport }>({});
+
   const [availableLangs, setAvailableLangs] = useState<string[]>([]);
 
   // Validate Standard CSV
   useEffect(() => {
-    if (!config.standardCsv) { setFileStatus(prev => ({ ...prev, standard: 'idle' })); return; }
+    if (!config.standardCsv) { setFileStatus(prev => ({ ...prev, standard: 'idle' })); setCsvReports(prev => ({ ...prev, standard: undefined })); return; }
     setFileStatus(prev => ({ ...prev, standard: 'checking' }));
-    invoke<boolean>('validate_csv_cmd', { path: config.standardCsv })
-      .then(res => setFileStatus(prev => ({ ...prev, standard: res ? 'valid' : 'invalid' })))
-      .catch(() => setFileStatus(prev => ({ ...prev, standard: 'invalid' })));
-  }, [config.standardCsv]);
+    invoke<CsvReport>('validate_csv_cmd', { path: config.standardCsv, schemaPath: config.schemaPath || null, projectFile: config.projectFile || null })
+      .then(res => {
+        setFileStatus(prev => ({ ...prev, standard: res.valid ? 'valid' : 'invalid' }));
+        setCsvReports(prev => ({ ...prev, standard: res }));
+      })
+      .catch(e => {
+        setFileStatus(prev => ({ ...prev, standard: 'invalid' }));
+        setCsvReports(prev => ({ ...prev, standard: undefined }));
+        setLogs(prev => [describeError(e), ...prev]);
+      });
+  }, [config.standardCsv, config.schemaPath, config.projectFile]);
 
   // Validate PrevYear CSV
   useEffect(() => {
-    if (!config.prevYearCsv) { setFileStatus(prev => ({ ...prev, prevYear: 'idle' })); return; }
+    if (!config.prevYearCsv) { setFileStatus(prev => ({ ...prev, prevYear: 'idle' })); setCsvReports(prev => ({ ...prev, prevYear: undefined })); return; }
     setFileStatus(prev => ({ ...prev, prevYear: 'checking' }));
-    invoke<boolean>('validate_csv_cmd', { path: config.prevYearCsv })
-      .then(res => setFileStatus(prev => ({ ...prev, prevYear: res ? 'valid' : 'invalid' })))
-      .catch(() => setFileStatus(prev => ({ ...prev, prevYear: 'invalid' })));
-  }, [config.prevYearCsv]);
+    invoke<CsvReport>('validate_csv_cmd', { path: config.prevYearCsv, schemaPath: config.schemaPath || null, projectFile: config.projectFile || null })
+      .then(res => {
+        setFileStatus(prev => ({ ...prev, prevYear: res.valid ? 'valid' : 'invalid' }));
+        setCsvReports(prev => ({ ...prev, prevYear: res }));
+      })
+      .catch(e => {
+        setFileStatus(prev => ({ ...prev, prevYear: 'invalid' }));
+        setCsvReports(prev => ({ ...prev, prevYear: undefined }));
+        setLogs(prev => [describeError(e), ...prev]);
+      });
+  }, [config.prevYearCsv, config.schemaPath, config.projectFile]);
 
   // Validate Templates & Sync
   // Validate Templates & Sync
@@ -136,7 +246,9 @@ function App() {
           template_dir: config.templateDir,
           output_dir: config.outputDir,
           languages: langKeys,
-          mappings: mappings
+          mappings: mappings,
+          translations_dir: config.translationsDir || null,
+          project_file: config.projectFile || null
         }
       });
       setStats(res);
@@ -147,9 +259,9 @@ function App() {
         origin: { y: 0.6 },
         colors: ['#6366f1', '#a855f7', '#ec4899', '#ffffff']
       });
-    } catch (e: any) {
+    } catch (e) {
       setStatus("error");
-      setLogs(prev => [`Error: ${e}`, ...prev]);
+      setLogs(prev => [describeError(e), ...prev]);
     } finally { setIsGenerating(false); }
   };
 
@@ -224,7 +336,7 @@ function App() {
                       <div className="flex gap-3 items-start">
                         <div className="w-2 h-2 rounded-full bg-fuchsia-500 mt-2 shrink-0" />
                         <p className="text-zinc-400 text-sm leading-relaxed">
-                          <strong className="text-zinc-200">Templates Directory:</strong> Must contain language folders (e.g., <code>/FR</code>, <code>/EN</code>) with your <code>.pptx</code> files inside.
+                          <strong className="text-zinc-200">Templates Directory:</strong> Must contain language folders (e.g., <code>/FR</code>, <code>/EN</code>) with your <code>.pptx</code>, <code>.docx</code> or <code>.xlsx</code> files inside.
                         </p>
                       </div>
                       <div className="flex gap-3 items-start">
@@ -406,6 +518,7 @@ function App() {
                 status={fileStatus.standard}
                 onFileSelect={p => setConfig({ ...config, standardCsv: p })}
               />
+              <CsvReportSummary report={csvReports.standard} />
               <DropZone
                 label="Previous Year CSV"
                 accept={['csv']}
@@ -413,6 +526,13 @@ function App() {
                 status={fileStatus.prevYear}
                 onFileSelect={p => setConfig({ ...config, prevYearCsv: p })}
               />
+              <CsvReportSummary report={csvReports.prevYear} />
+              <DropZone
+                label="Column Schema (optional)"
+                accept={['json']}
+                value={config.schemaPath}
+                onFileSelect={p => setConfig({ ...config, schemaPath: p })}
+              />
             </div>
 
             <div className="space-y-3">
@@ -431,6 +551,18 @@ function App() {
                   value={config.outputDir}
                   onFileSelect={p => setConfig({ ...config, outputDir: p })}
                 />
+                <DropZone
+                  directory
+                  label="Translations (optional)"
+                  value={config.translationsDir}
+                  onFileSelect={p => setConfig({ ...config, translationsDir: p })}
+                />
+                <DropZone
+                  label="Project Settings (optional)"
+                  accept={['json']}
+                  value={config.projectFile}
+                  onFileSelect={p => setConfig({ ...config, projectFile: p })}
+                />
               </div>
             </div>
 
@@ -561,6 +693,42 @@ function App() {
                     </div>
                   </div>
                 )}
+
+                {status === 'success' && stats?.missing_values && Object.keys(stats.missing_values).length > 0 && (
+                  <p className="text-[11px] font-mono text-amber-500 text-center">
+                    Empty values: {Object.entries(stats.missing_values).map(([tag, n]) => `${tag} (${n})`).join(', ')}
+                  </p>
+                )}
+                {status === 'success' && stats?.unmatched_lookup_keys && Object.keys(stats.unmatched_lookup_keys).length > 0 && (
+                  <p className="text-[11px] font-mono text-amber-500 text-center">
+                    Unmatched lookups: {Object.entries(stats.unmatched_lookup_keys).map(([ns, keys]) => `${ns} (${keys.length})`).join(', ')}
+                  </p>
+                )}
+                {status === 'success' && !!stats?.fitted_shapes && (
+                  <p className="text-[11px] font-mono text-amber-500 text-center">
+                    {stats.fitted_shapes} text box(es) adjusted to fit, see manifest.json
+                  </p>
+                )}
+                {status === 'success' && !!stats?.unchecked_shapes && (
+                  <p className="text-[11px] font-mono text-amber-500 text-center">
+                    {stats.unchecked_shapes} text box(es) not checked for overflow, size unknown, see manifest.json
+                  </p>
+                )}
+                {status === 'success' && stats?.skipped_compositions && stats.skipped_compositions.length > 0 && (
+                  <p className="text-[11px] font-mono text-amber-500 text-center">
+                    Combined decks not built: {stats.skipped_compositions.join(', ')}
+                  </p>
+                )}
+                {status === 'success' && stats?.deck_errors && stats.deck_errors.length > 0 && (
+                  <p className="text-[11px] font-mono text-red-400 text-center">
+                    {stats.deck_errors.length} deck(s) failed: {describeError(stats.deck_errors[0].error)}
+                  </p>
+                )}
+                {status === 'success' && stats?.validation_failures && stats.validation_failures.length > 0 && (
+                  <p className="text-[11px] font-mono text-red-400 text-center">
+                    {stats.validation_failures.length} deck(s) need repair, see manifest.json: {stats.validation_failures.slice(0, 3).map(f => fileName(f.output)).join(', ')}
+                  </p>
+                )}
               </div>
             </div>
 